@@ -22,7 +22,6 @@ pub mod music;
 pub mod net;
 pub mod networkmanager;
 pub mod notify;
-#[cfg(feature = "notmuch")]
 pub mod notmuch;
 pub mod nvidia_gpu;
 pub mod pacman;
@@ -63,7 +62,6 @@ use self::music::*;
 use self::net::*;
 use self::networkmanager::*;
 use self::notify::*;
-#[cfg(feature = "notmuch")]
 use self::notmuch::*;
 use self::nvidia_gpu::*;
 use self::pacman::*;
@@ -90,6 +88,8 @@ use crate::config::SharedConfig;
 use crate::errors::*;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
+use crate::signals::convert_to_valid_signal;
+use crate::subprocess::command_succeeds;
 use crate::widgets::I3BarWidget;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -170,17 +170,44 @@ pub trait Block {
     /// Sends click events to the block.
     ///
     /// Here you can react to the user clicking your block. The I3BarEvent instance contains all
-    /// fields to describe the click action, including mouse button and location down to the pixel.
+    /// fields to describe the click action, including mouse button and the modifier keys held.
     /// You may also update the internal state here.
     ///
     /// If block uses more that one widget, use the event.instance property to determine which widget was clicked.
     fn click(&mut self, _event: &I3BarEvent) -> Result<()> {
         Ok(())
     }
+
+    /// Whether middle-clicking the block updates it right away (after its `click`), instead of
+    /// waiting for its next scheduled update. Blocks fetching data over the network opt in to
+    /// this, blocks with their own middle-click action shouldn't.
+    fn refresh_on_middle_click(&self) -> bool {
+        false
+    }
+
+    /// Whether the block wants to be updated by the scheduler right after its last `click`, to
+    /// go on with the interval its `update` returns (e.g. to animate a change).
+    fn update_after_click(&self) -> bool {
+        false
+    }
+
+    /// The width (in pixels) of the gap after this block, if it overrides the default.
+    ///
+    /// This is set with the common `spacing` option, blocks don't need to implement it.
+    fn spacing(&self) -> Option<usize> {
+        None
+    }
+
+    /// The separator drawn in front of this block, if it overrides the theme's separator.
+    ///
+    /// This is set with the common `block_separator` option, blocks don't need to implement it.
+    fn separator(&self) -> Option<&str> {
+        None
+    }
 }
 
 macro_rules! block {
-    ($block_type:ident, $id:expr, $block_config:expr, $shared_config:expr, $update_request:expr) => {{
+    ($block_type:ident, $name:expr, $id:expr, $block_config:expr, $shared_config:expr, $update_request:expr) => {{
         // Extract base(common) config
         let common_config = BaseBlockConfig::extract(&mut $block_config);
         let mut common_config = BaseBlockConfig::deserialize(common_config)
@@ -193,20 +220,50 @@ macro_rules! block {
         if let Some(overrides) = common_config.icons_format {
             $shared_config.icons_format_override(overrides);
         }
+        if let Some(ref overrides) = common_config.icons_overrides {
+            $shared_config.icons_override(overrides);
+        }
+
+        // If the signal is not in the valid range we return an error
+        let signal = common_config
+            .signal
+            .map(convert_to_valid_signal)
+            .transpose()?;
+
+        // The block is still created when its `if_command` fails, so that its config is checked
+        let hidden = common_config
+            .if_command
+            .as_deref()
+            .map_or(false, |command| !command_succeeds(command));
 
         // Extract block-specific config
         let block_config = <$block_type as ConfigBlock>::Config::deserialize($block_config)
             .configuration_error("Failed to deserialize block config.")?;
 
-        let mut block = $block_type::new($id, block_config, $shared_config, $update_request)?;
+        let mut block =
+            $block_type::new($id, block_config, $shared_config.clone(), $update_request)?;
         if let Some(overrided) = block.override_on_click() {
             *overrided = common_config.on_click.take();
         }
 
         Ok(Box::new(BaseBlock {
-            name: stringify!($block_type).to_string(),
+            name: $name.to_string(),
             inner: block,
             on_click: common_config.on_click,
+            on_middle_click: common_config.on_middle_click,
+            on_right_click: common_config.on_right_click,
+            spacing: common_config.spacing,
+            separator: common_config.block_separator,
+            signal,
+            shared_config: $shared_config,
+            error: None,
+            interval: None,
+            hidden,
+            click_debouncer: ClickDebouncer::new(
+                common_config
+                    .click_debounce
+                    .unwrap_or(DEFAULT_CLICK_DEBOUNCE),
+            ),
         }) as Box<dyn Block>)
     }};
 }
@@ -220,63 +277,247 @@ pub fn create_block(
 ) -> Result<Box<dyn Block>> {
     match name {
         // Please keep these in alphabetical order.
-        "apt" => block!(Apt, id, block_config, shared_config, update_request),
-        "backlight" => block!(Backlight, id, block_config, shared_config, update_request),
-        "battery" => block!(Battery, id, block_config, shared_config, update_request),
-        "bluetooth" => block!(Bluetooth, id, block_config, shared_config, update_request),
-        "cpu" => block!(Cpu, id, block_config, shared_config, update_request),
-        "custom" => block!(Custom, id, block_config, shared_config, update_request),
-        "custom_dbus" => block!(CustomDBus, id, block_config, shared_config, update_request),
-        "disk_space" => block!(DiskSpace, id, block_config, shared_config, update_request),
-        "docker" => block!(Docker, id, block_config, shared_config, update_request), ///////
+        "apt" => block!(Apt, name, id, block_config, shared_config, update_request),
+        "backlight" => block!(
+            Backlight,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "battery" => block!(
+            Battery,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "bluetooth" => block!(
+            Bluetooth,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "cpu" => block!(Cpu, name, id, block_config, shared_config, update_request),
+        "custom" => block!(
+            Custom,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "custom_dbus" => block!(
+            CustomDBus,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "disk_space" => block!(
+            DiskSpace,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "docker" => block!(
+            Docker,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ), ///////
         "focused_window" => block!(
             FocusedWindow,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "github" => block!(
+            Github,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "hueshift" => block!(
+            Hueshift,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "ibus" => block!(IBus, name, id, block_config, shared_config, update_request),
+        "kdeconnect" => block!(
+            KDEConnect,
+            name,
             id,
             block_config,
             shared_config,
             update_request
         ),
-        "github" => block!(Github, id, block_config, shared_config, update_request),
-        "hueshift" => block!(Hueshift, id, block_config, shared_config, update_request),
-        "ibus" => block!(IBus, id, block_config, shared_config, update_request),
-        "kdeconnect" => block!(KDEConnect, id, block_config, shared_config, update_request),
         "keyboard_layout" => block!(
             KeyboardLayout,
+            name,
             id,
             block_config,
             shared_config,
             update_request
         ),
-        "load" => block!(Load, id, block_config, shared_config, update_request),
+        "load" => block!(Load, name, id, block_config, shared_config, update_request),
         #[cfg(feature = "maildir")]
-        "maildir" => block!(Maildir, id, block_config, shared_config, update_request),
-        "memory" => block!(Memory, id, block_config, shared_config, update_request),
-        "music" => block!(Music, id, block_config, shared_config, update_request),
-        "net" => block!(Net, id, block_config, shared_config, update_request),
+        "maildir" => block!(
+            Maildir,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "memory" => block!(
+            Memory,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "music" => block!(Music, name, id, block_config, shared_config, update_request),
+        "net" => block!(Net, name, id, block_config, shared_config, update_request),
         "networkmanager" => block!(
             NetworkManager,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "notify" => block!(
+            Notify,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "notmuch" => block!(
+            Notmuch,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "nvidia_gpu" => block!(
+            NvidiaGpu,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "pacman" => block!(
+            Pacman,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "pomodoro" => block!(
+            Pomodoro,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "sound" => block!(Sound, name, id, block_config, shared_config, update_request),
+        "speedtest" => block!(
+            SpeedTest,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "taskwarrior" => block!(
+            Taskwarrior,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "temperature" => block!(
+            Temperature,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "template" => block!(
+            Template,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "time" => block!(Time, name, id, block_config, shared_config, update_request), /////////
+        "toggle" => block!(
+            Toggle,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "uptime" => block!(
+            Uptime,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "watson" => block!(
+            Watson,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "weather" => block!(
+            Weather,
+            name,
+            id,
+            block_config,
+            shared_config,
+            update_request
+        ),
+        "xrandr" => block!(
+            Xrandr,
+            name,
             id,
             block_config,
             shared_config,
             update_request
         ),
-        "notify" => block!(Notify, id, block_config, shared_config, update_request),
-        #[cfg(feature = "notmuch")]
-        "notmuch" => block!(Notmuch, id, block_config, shared_config, update_request),
-        "nvidia_gpu" => block!(NvidiaGpu, id, block_config, shared_config, update_request),
-        "pacman" => block!(Pacman, id, block_config, shared_config, update_request),
-        "pomodoro" => block!(Pomodoro, id, block_config, shared_config, update_request),
-        "sound" => block!(Sound, id, block_config, shared_config, update_request),
-        "speedtest" => block!(SpeedTest, id, block_config, shared_config, update_request),
-        "taskwarrior" => block!(Taskwarrior, id, block_config, shared_config, update_request),
-        "temperature" => block!(Temperature, id, block_config, shared_config, update_request),
-        "template" => block!(Template, id, block_config, shared_config, update_request),
-        "time" => block!(Time, id, block_config, shared_config, update_request), /////////
-        "toggle" => block!(Toggle, id, block_config, shared_config, update_request),
-        "uptime" => block!(Uptime, id, block_config, shared_config, update_request),
-        "watson" => block!(Watson, id, block_config, shared_config, update_request),
-        "weather" => block!(Weather, id, block_config, shared_config, update_request),
-        "xrandr" => block!(Xrandr, id, block_config, shared_config, update_request),
         other => Err(BlockError(other.to_string(), "Unknown block!".to_string())),
     }
 }
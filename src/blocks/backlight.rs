@@ -7,6 +7,7 @@
 //! brightness levels using `xrandr`, see the
 //! [`Xrandr`](../xrandr/struct.Xrandr.html) block.
 
+use std::cmp::min;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
@@ -20,9 +21,13 @@ use serde_derive::Deserialize;
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::config::{LogicalDirection, Scrolling};
+use crate::de::deserialize_duration;
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
+use crate::widgets::progressbar::ProgressBarWidget;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
@@ -58,25 +63,53 @@ fn clamp_root_scaling(root_scaling: f64) -> f64 {
     }
 }
 
+/// The names of the devices in `/sys/class/backlight`, sorted
+fn backlight_devices() -> Result<Vec<String>> {
+    let mut devices = Path::new("/sys/class/backlight")
+        .read_dir() // Iterate over entries in the directory.
+        .block_error("backlight", "Failed to read backlight device directory")?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect::<Vec<_>>();
+    devices.sort();
+    Ok(devices)
+}
+
+/// How much a device with `name` and the sysfs `kind` (`raw`, `platform` or `firmware`) is
+/// preferred, lowest first. The panel's own backlight (`raw`, such as `intel_backlight` or
+/// `amdgpu_bl0`) usually works best, and external monitors controlled over DDC/CI come last.
+fn device_rank(name: &str, kind: &str) -> u8 {
+    if name.starts_with("ddcci") {
+        return 3;
+    }
+    match kind {
+        "raw" => 0,
+        "platform" => 1,
+        _ => 2,
+    }
+}
+
 impl BacklitDevice {
-    /// Use the default backlit device, i.e. the first one found in the
-    /// `/sys/class/backlight` directory.
+    /// Use the preferred backlit device of the `/sys/class/backlight` directory (see
+    /// `device_rank`).
     pub fn default(root_scaling: f64) -> Result<Self> {
-        let devices = Path::new("/sys/class/backlight")
-            .read_dir() // Iterate over entries in the directory.
-            .block_error("backlight", "Failed to read backlight device directory")?;
-
-        let first_device = devices
-            .take(1)
-            .next()
-            .block_error("backlight", "No backlit devices found")?
-            .block_error("backlight", "Failed to read default device file")?;
+        let device = backlight_devices()?
+            .into_iter()
+            .min_by_key(|name| {
+                let kind = std::fs::read_to_string(
+                    Path::new("/sys/class/backlight").join(name).join("type"),
+                )
+                .unwrap_or_default();
+                device_rank(name, kind.trim())
+            })
+            .block_error("backlight", "No backlit devices found")?;
 
-        let max_brightness = read_brightness(&first_device.path().join("max_brightness"))?;
+        let device_path = Path::new("/sys/class/backlight").join(device);
+        let max_brightness = read_brightness(&device_path.join("max_brightness"))?;
 
         Ok(BacklitDevice {
             max_brightness,
-            device_path: first_device.path(),
+            device_path,
             root_scaling: clamp_root_scaling(root_scaling),
         })
     }
@@ -86,11 +119,17 @@ impl BacklitDevice {
     pub fn from_device(device: String, root_scaling: f64) -> Result<Self> {
         let device_path = Path::new("/sys/class/backlight").join(device);
         if !device_path.exists() {
+            let available = backlight_devices().unwrap_or_default();
             return Err(BlockError(
                 "backlight".to_string(),
                 format!(
-                    "Backlight device '{}' does not exist",
-                    device_path.to_string_lossy()
+                    "Backlight device '{}' does not exist, available devices: {}",
+                    device_path.to_string_lossy(),
+                    if available.is_empty() {
+                        "none".to_string()
+                    } else {
+                        available.join(", ")
+                    }
                 ),
             ));
         }
@@ -123,7 +162,8 @@ impl BacklitDevice {
             _ => 100,
         };
         let ratio = (safe_value as f64 / 100.0).powf(self.root_scaling);
-        let raw = std::cmp::max(1, (ratio * (self.max_brightness as f64)).round() as u64);
+        let raw = (ratio * (self.max_brightness as f64)).round() as u64;
+        let raw = raw.clamp(1, std::cmp::max(1, self.max_brightness));
 
         let file = OpenOptions::new()
             .write(true)
@@ -143,10 +183,8 @@ impl BacklitDevice {
     }
 
     fn set_brightness_via_dbus(&self, raw_value: u64) -> Result<()> {
-        let device_name = self
-            .device_path
-            .file_name()
-            .and_then(|x| x.to_str())
+        let device_name = Some(self.name())
+            .filter(|name| !name.is_empty())
             .block_error("backlight", "Malformed device path")?;
 
         let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
@@ -166,6 +204,14 @@ impl BacklitDevice {
             .map(|_| ())
     }
 
+    /// The name of the device in `/sys/class/backlight`
+    pub fn name(&self) -> &str {
+        self.device_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+    }
+
     /// The brightness file itself.
     // amdgpu drivers set the actual_brightness in a different scale than [0, max_brightness],
     // so we have to use the 'brightness' file instead. This may be fixed in the new 5.7 kernel?
@@ -178,12 +224,44 @@ impl BacklitDevice {
     }
 }
 
+/// The brightness (in percents) after scrolling once from `brightness`, kept between 0 and 100
+fn scrolled_brightness(brightness: u64, step_width: u64, direction: LogicalDirection) -> u64 {
+    match direction {
+        LogicalDirection::Up => min(brightness + step_width, 100),
+        LogicalDirection::Down => brightness.saturating_sub(step_width),
+    }
+}
+
+/// A change of the brightness (in percents) spread over the fade duration
+#[derive(Debug, Clone, Copy)]
+struct Fade {
+    from: u64,
+    to: u64,
+    start: Instant,
+}
+
+/// The brightness `elapsed` into a fade of `duration` from `from` to `to`, moving in `steps`
+/// equal steps
+fn faded_brightness(from: u64, to: u64, elapsed: Duration, duration: Duration, steps: u32) -> u64 {
+    if elapsed >= duration || steps <= 1 {
+        return to;
+    }
+    let step = (elapsed.as_secs_f64() / duration.as_secs_f64() * steps as f64).floor();
+    let progress = step / steps as f64;
+    (from as f64 + (to as f64 - from as f64) * progress).round() as u64
+}
+
 /// A block for displaying the brightness of a backlit device.
 pub struct Backlight {
     id: usize,
     output: TextWidget,
+    bar: Option<ProgressBarWidget>,
     device: BacklitDevice,
     step_width: u64,
+    fade_duration: Duration,
+    fade_steps: u32,
+    fade: Option<Fade>,
+    format: FormatTemplate,
     scrolling: Scrolling,
     invert_icons: bool,
 }
@@ -192,9 +270,12 @@ pub struct Backlight {
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct BacklightConfig {
-    /// The backlight device in `/sys/class/backlight/` to read brightness from.
+    /// The backlight device in `/sys/class/backlight/` to read brightness from, or "auto".
     pub device: Option<String>,
 
+    /// Format string, with the placeholders `{brightness}` and `{device}`.
+    pub format: String,
+
     /// The steps brightness is in/decreased for the selected screen (When greater than 50 it gets limited to 50)
     pub step_width: u64,
 
@@ -208,15 +289,29 @@ pub struct BacklightConfig {
     pub root_scaling: f64,
 
     pub invert_icons: bool,
+
+    /// Show the brightness as a bar of this width next to the percentage
+    pub bar_width: Option<usize>,
+
+    /// How long a scroll step takes to fade to the new brightness. Zero changes it at once.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub fade_duration: Duration,
+
+    /// The number of intermediate brightness levels written during a fade
+    pub fade_steps: u32,
 }
 
 impl Default for BacklightConfig {
     fn default() -> Self {
         Self {
             device: None,
+            format: "{brightness}".to_string(),
             step_width: 5,
             root_scaling: 1f64,
             invert_icons: false,
+            bar_width: None,
+            fade_duration: Duration::from_secs(0),
+            fade_steps: 10,
         }
     }
 }
@@ -231,8 +326,10 @@ impl ConfigBlock for Backlight {
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let device = match block_config.device {
-            Some(path) => BacklitDevice::from_device(path, block_config.root_scaling),
-            None => BacklitDevice::default(block_config.root_scaling),
+            Some(path) if path != "auto" => {
+                BacklitDevice::from_device(path, block_config.root_scaling)
+            }
+            _ => BacklitDevice::default(block_config.root_scaling),
         }?;
 
         let brightness_file = device.brightness_file();
@@ -241,7 +338,14 @@ impl ConfigBlock for Backlight {
             id,
             device,
             step_width: block_config.step_width,
+            fade_duration: block_config.fade_duration,
+            fade_steps: block_config.fade_steps,
+            fade: None,
+            format: FormatTemplate::from_string(&block_config.format)?,
             scrolling: shared_config.scrolling,
+            bar: block_config.bar_width.map(|width| {
+                ProgressBarWidget::new(id, 1, shared_config.clone()).with_width(width)
+            }),
             output: TextWidget::new(id, 0, shared_config),
             invert_icons: block_config.invert_icons,
         };
@@ -283,8 +387,40 @@ impl ConfigBlock for Backlight {
 
 impl Block for Backlight {
     fn update(&mut self) -> Result<Option<Update>> {
+        // Updates also come from the brightness file changing during the fade, so the fade
+        // follows the time instead of counting updates
+        let next_update = match self.fade {
+            Some(fade) => {
+                let elapsed = fade.start.elapsed();
+                let brightness = faded_brightness(
+                    fade.from,
+                    fade.to,
+                    elapsed,
+                    self.fade_duration,
+                    self.fade_steps,
+                );
+                if brightness != self.device.brightness()? {
+                    self.device.set_brightness(brightness)?;
+                }
+                if elapsed >= self.fade_duration {
+                    self.fade = None;
+                    None
+                } else {
+                    Some(Update::Every(self.fade_duration / self.fade_steps.max(1)))
+                }
+            }
+            None => None,
+        };
+
         let mut brightness = self.device.brightness()?;
-        self.output.set_text(format!("{}%", brightness));
+        let values = map!(
+            "brightness" => Value::from_integer(brightness as i64).percents(),
+            "device" => Value::from_string(self.device.name().to_string()),
+        );
+        self.output.set_text(self.format.render(&values)?);
+        if let Some(ref mut bar) = self.bar {
+            bar.set_value(brightness as f64);
+        }
         if self.invert_icons {
             brightness = 100 - brightness;
         }
@@ -306,34 +442,104 @@ impl Block for Backlight {
             _ => "backlight_full",
         })?;
 
-        Ok(None)
+        Ok(next_update)
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.output]
+        match self.bar {
+            Some(ref bar) => vec![&self.output, bar],
+            None => vec![&self.output],
+        }
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
-        let brightness = self.device.brightness()?;
-        use LogicalDirection::*;
-        match self.scrolling.to_logical_direction(event.button) {
-            Some(Up) => {
-                if brightness < 100 {
-                    self.device.set_brightness(brightness + self.step_width)?;
-                }
-            }
-            Some(Down) => {
-                if brightness > self.step_width {
-                    self.device.set_brightness(brightness - self.step_width)?;
+        if let Some(direction) = self.scrolling.to_logical_direction(event.button) {
+            let brightness = self.device.brightness()?;
+            // Scrolling again during a fade goes on from where the fade is headed
+            let target = self.fade.map_or(brightness, |fade| fade.to);
+            let new_brightness = scrolled_brightness(target, self.step_width, direction);
+            if self.fade_duration.is_zero() || self.fade_steps <= 1 {
+                if new_brightness != brightness {
+                    self.device.set_brightness(new_brightness)?;
                 }
+            } else if new_brightness != target {
+                self.fade = Some(Fade {
+                    from: brightness,
+                    to: new_brightness,
+                    start: Instant::now(),
+                });
             }
-            None => {}
         }
 
         Ok(())
     }
 
+    fn update_after_click(&self) -> bool {
+        self.fade.is_some()
+    }
+
     fn id(&self) -> usize {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scrolled_brightness_is_clamped() {
+        use LogicalDirection::*;
+        assert_eq!(scrolled_brightness(50, 5, Up), 55);
+        assert_eq!(scrolled_brightness(50, 5, Down), 45);
+        assert_eq!(scrolled_brightness(98, 5, Up), 100);
+        assert_eq!(scrolled_brightness(100, 5, Up), 100);
+        assert_eq!(scrolled_brightness(3, 5, Down), 0);
+        assert_eq!(scrolled_brightness(0, 5, Down), 0);
+    }
+
+    #[test]
+    fn device_preference() {
+        fn best(devices: &[(&'static str, &str)]) -> Option<&'static str> {
+            devices
+                .iter()
+                .min_by_key(|(name, kind)| device_rank(name, kind))
+                .map(|(name, _)| *name)
+        }
+        assert_eq!(
+            best(&[("acpi_video0", "firmware"), ("intel_backlight", "raw")]),
+            Some("intel_backlight")
+        );
+        assert_eq!(
+            best(&[("ddcci5", "raw"), ("nvidia_wmi_ec_backlight", "firmware")]),
+            Some("nvidia_wmi_ec_backlight")
+        );
+        assert_eq!(
+            best(&[("ddcci5", "raw"), ("dell_backlight", "platform")]),
+            Some("dell_backlight")
+        );
+        assert_eq!(best(&[]), None);
+    }
+
+    #[test]
+    fn fade_steps() {
+        let duration = Duration::from_millis(200);
+        let at = |ms| faded_brightness(40, 60, Duration::from_millis(ms), duration, 4);
+        assert_eq!(at(0), 40);
+        assert_eq!(at(49), 40);
+        assert_eq!(at(50), 45);
+        assert_eq!(at(120), 50);
+        assert_eq!(at(199), 55);
+        assert_eq!(at(200), 60);
+        assert_eq!(at(500), 60);
+        // Down, and without steps
+        assert_eq!(
+            faded_brightness(60, 40, Duration::from_millis(100), duration, 4),
+            50
+        );
+        assert_eq!(
+            faded_brightness(60, 40, Duration::from_millis(0), duration, 1),
+            40
+        );
+    }
+}
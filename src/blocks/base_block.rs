@@ -1,18 +1,111 @@
 //! A Base block for common behavior for all blocks
 
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
+use crate::config::SharedConfig;
+use crate::de::deserialize_opt_duration;
 use crate::errors::*;
+use crate::icons::Icon;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
-use crate::{blocks::Update, subprocess::spawn_child_async, widgets::I3BarWidget, Block};
+use crate::widgets::{text::TextWidget, State};
+use crate::{blocks::Update, subprocess::spawn_shell_async, widgets::I3BarWidget, Block};
 
+use log::error;
 use serde_derive::Deserialize;
 use toml::{value::Table, Value};
 
 pub(super) struct BaseBlock<T: Block> {
     pub name: String,
     pub inner: T,
+    /// Commands run when the block is clicked with the left, middle or right button
     pub on_click: Option<String>,
+    pub on_middle_click: Option<String>,
+    pub on_right_click: Option<String>,
+    pub spacing: Option<usize>,
+    pub separator: Option<String>,
+    /// The real time signal that updates the block
+    pub signal: Option<i32>,
+    pub shared_config: SharedConfig,
+    /// Shown instead of the block while its last update failed
+    pub error: Option<ErrorWidget>,
+    /// The interval the block asked for with its last successful update
+    pub interval: Option<Update>,
+    /// Set when the `if_command` failed, the block is then never shown nor updated
+    pub hidden: bool,
+    pub click_debouncer: ClickDebouncer,
+}
+
+/// How often a block is updated while it fails, unless it asked for an interval before
+const ERROR_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long repeated clicks are ignored for, unless set with `click_debounce`
+pub(super) const DEFAULT_CLICK_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Ignores clicks repeating the previous one within a time window, so that an accidental
+/// double click doesn't run the click action twice. Scrolling is never ignored.
+pub(super) struct ClickDebouncer {
+    window: Duration,
+    last_click: Option<(MouseButton, Instant)>,
+}
+
+impl ClickDebouncer {
+    pub fn new(window: Duration) -> Self {
+        ClickDebouncer {
+            window,
+            last_click: None,
+        }
+    }
+
+    /// Whether a click with `button` at `now` should be handled
+    fn accept(&mut self, button: MouseButton, now: Instant) -> bool {
+        if let MouseButton::WheelUp | MouseButton::WheelDown = button {
+            return true;
+        }
+        let repeated = matches!(
+            self.last_click,
+            Some((last_button, last_time)) if last_button == button && now.duration_since(last_time) < self.window
+        );
+        if !repeated {
+            self.last_click = Some((button, now));
+        }
+        !repeated
+    }
+}
+
+/// Shows the `error` icon in the critical state, and the error message once clicked
+pub(super) struct ErrorWidget {
+    widget: TextWidget,
+    message: String,
+    show_message: bool,
+}
+
+impl ErrorWidget {
+    fn new(id: usize, shared_config: SharedConfig, message: String, show_message: bool) -> Self {
+        let mut error = ErrorWidget {
+            widget: TextWidget::new(id, 0, shared_config)
+                .with_state(State::Critical)
+                .with_icon("error")
+                .unwrap(), // `get_icon` falls back to the `fallback` icon instead of failing
+            message,
+            show_message,
+        };
+        error.set_text();
+        error
+    }
+
+    fn toggle_message(&mut self) {
+        self.show_message = !self.show_message;
+        self.set_text();
+    }
+
+    fn set_text(&mut self) {
+        self.widget.set_text(if self.show_message {
+            self.message.clone()
+        } else {
+            String::new()
+        });
+    }
 }
 
 impl<T: Block> Block for BaseBlock<T> {
@@ -21,28 +114,113 @@ impl<T: Block> Block for BaseBlock<T> {
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        self.inner.view()
+        if self.hidden {
+            return Vec::new();
+        }
+        match &self.error {
+            Some(error) => vec![&error.widget],
+            None => self.inner.view(),
+        }
     }
 
+    /// A failing update doesn't stop the bar: the error is shown in place of the block, which is
+    /// retried with its last interval
     fn update(&mut self) -> Result<Option<Update>> {
-        self.inner.update()
+        if self.hidden {
+            return Ok(None);
+        }
+        match self.inner.update().in_block(&self.name, self.id()) {
+            Ok(interval) => {
+                self.error = None;
+                if interval.is_some() {
+                    self.interval = interval.clone();
+                }
+                Ok(interval)
+            }
+            Err(e) => {
+                error!("{:?}", e);
+                let show_message = matches!(
+                    self.error,
+                    Some(ErrorWidget {
+                        show_message: true,
+                        ..
+                    })
+                );
+                self.error = Some(ErrorWidget::new(
+                    self.id(),
+                    self.shared_config.clone(),
+                    e.to_string(),
+                    show_message,
+                ));
+                Ok(Some(
+                    self.interval
+                        .clone()
+                        .unwrap_or_else(|| ERROR_RETRY_INTERVAL.into()),
+                ))
+            }
+        }
     }
 
     fn signal(&mut self, signal: i32) -> Result<()> {
-        self.inner.signal(signal)
+        if self.hidden {
+            return Ok(());
+        }
+        if self.signal == Some(signal) {
+            self.update()?;
+        }
+        self.inner.signal(signal).in_block(&self.name, self.id())
+    }
+
+    fn refresh_on_middle_click(&self) -> bool {
+        self.on_middle_click.is_none() && self.inner.refresh_on_middle_click()
+    }
+
+    fn update_after_click(&self) -> bool {
+        self.inner.update_after_click()
+    }
+
+    fn spacing(&self) -> Option<usize> {
+        self.spacing
+    }
+
+    fn separator(&self) -> Option<&str> {
+        self.separator.as_deref()
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
-        match &self.on_click {
-            Some(cmd) => {
-                if let MouseButton::Left = e.button {
-                    spawn_child_async("sh", &["-c", &cmd])
-                        .block_error(&self.name, "could not spawn child")?;
-                }
-                Ok(())
-            }
-            None => self.inner.click(e),
+        if !self.click_debouncer.accept(e.button, Instant::now()) {
+            return Ok(());
         }
+        if let Some(error) = &mut self.error {
+            error.toggle_message();
+            return Ok(());
+        }
+        let command = match e.button {
+            MouseButton::Left => &self.on_click,
+            MouseButton::Middle => &self.on_middle_click,
+            MouseButton::Right => &self.on_right_click,
+            _ => &None,
+        };
+        match command {
+            Some(cmd) => spawn_shell_async(cmd, &[(CLICK_TEXT_ENV, &self.text())])
+                .block_error(&self.name, "could not spawn child"),
+            None => self.inner.click(e).in_block(&self.name, self.id()),
+        }
+    }
+}
+
+/// Environment variable holding the text of the block for the click commands
+const CLICK_TEXT_ENV: &str = "I3RS_BLOCK_TEXT";
+
+impl<T: Block> BaseBlock<T> {
+    /// The text currently shown by the block, of all its widgets
+    fn text(&self) -> String {
+        self.view()
+            .iter()
+            .map(|widget| widget.get_data().full_text.trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
     }
 }
 
@@ -51,12 +229,50 @@ pub(super) struct BaseBlockConfig {
     /// Command to execute when the button is clicked
     pub on_click: Option<String>,
 
+    /// Command to execute when the block is clicked with the middle button
+    pub on_middle_click: Option<String>,
+
+    /// Command to execute when the block is clicked with the right button
+    pub on_right_click: Option<String>,
+
     pub theme_overrides: Option<HashMap<String, String>>,
     pub icons_format: Option<String>,
+
+    /// Icons to use in this block instead of the ones of the icon set
+    pub icons_overrides: Option<HashMap<String, Icon>>,
+
+    /// Width of the gap after the block, in pixels
+    pub spacing: Option<usize>,
+
+    /// Separator drawn in front of the block instead of the theme's one. Not named `separator`,
+    /// which is an option of the music block.
+    pub block_separator: Option<String>,
+
+    /// Offset from SIGRTMIN of the signal that updates the block
+    pub signal: Option<i32>,
+
+    /// Shell command run when the block is created, the block is only shown if it succeeds
+    pub if_command: Option<String>,
+
+    /// Repeated clicks within this time are ignored
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub click_debounce: Option<Duration>,
 }
 
 impl BaseBlockConfig {
-    const FIELDS: &'static [&'static str] = &["on_click", "theme_overrides", "icons_format"];
+    const FIELDS: &'static [&'static str] = &[
+        "on_click",
+        "on_middle_click",
+        "on_right_click",
+        "theme_overrides",
+        "icons_format",
+        "icons_overrides",
+        "spacing",
+        "block_separator",
+        "signal",
+        "if_command",
+        "click_debounce",
+    ];
 
     // FIXME: this function is to paper over https://github.com/serde-rs/serde/issues/1957
     pub(super) fn extract(config: &mut Value) -> Value {
@@ -71,3 +287,42 @@ impl BaseBlockConfig {
         common_table.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[test]
+    fn click_debouncer() {
+        let mut debouncer = ClickDebouncer::new(Duration::from_millis(200));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        assert!(debouncer.accept(MouseButton::Left, at(0)));
+        assert!(!debouncer.accept(MouseButton::Left, at(100)));
+        assert!(debouncer.accept(MouseButton::Right, at(150)));
+        assert!(debouncer.accept(MouseButton::Left, at(200)));
+        assert!(!debouncer.accept(MouseButton::Left, at(399)));
+        assert!(debouncer.accept(MouseButton::Left, at(400)));
+
+        assert!(debouncer.accept(MouseButton::WheelUp, at(500)));
+        assert!(debouncer.accept(MouseButton::WheelUp, at(501)));
+
+        let mut debouncer = ClickDebouncer::new(Duration::from_secs(0));
+        assert!(debouncer.accept(MouseButton::Left, at(0)));
+        assert!(debouncer.accept(MouseButton::Left, at(0)));
+    }
+
+    #[test]
+    fn extract_keeps_block_options() {
+        let mut config: Value =
+            toml::from_str("block_separator = \"|\"\nseparator = \" / \"\nspacing = 0\n").unwrap();
+        let common = BaseBlockConfig::deserialize(BaseBlockConfig::extract(&mut config)).unwrap();
+        assert_eq!(common.block_separator.as_deref(), Some("|"));
+        assert_eq!(common.spacing, Some(0));
+
+        let music = crate::blocks::music::MusicConfig::deserialize(config).unwrap();
+        assert_eq!(music.separator, " / ");
+    }
+}
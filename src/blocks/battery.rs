@@ -15,12 +15,15 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{
+    deserialize_duration, deserialize_one_or_many, deserialize_opt_duration, deserialize_percent,
+};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
 use crate::util::{battery_level_to_icon, read_file};
+use crate::widgets::progressbar::{self, bar_glyphs, render_bar};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, Spacing, State};
 
@@ -78,6 +81,53 @@ impl PowerSupplyDevice {
 
         Ok(device)
     }
+
+    /// The energy (µWh) or charge (µAh) of the full battery
+    fn full(&self) -> Option<f64> {
+        self.energy_full
+            .or(self.charge_full)
+            .map(|full| full as f64)
+    }
+
+    /// The energy (µWh) or charge (µAh) left in the battery
+    fn fill(&self) -> Result<f64> {
+        let energy_path = self.device_path.join("energy_now");
+        let charge_path = self.device_path.join("charge_now");
+        if energy_path.exists() {
+            read_file("battery", &energy_path)?
+                .parse::<f64>()
+                .block_error("battery", "failed to parse energy_now")
+        } else if charge_path.exists() {
+            read_file("battery", &charge_path)?
+                .parse::<f64>()
+                .block_error("battery", "failed to parse charge_now")
+        } else {
+            Err(BlockError(
+                "battery".to_string(),
+                "Device does not support reading energy".to_string(),
+            ))
+        }
+    }
+
+    /// The rate (µW or µA) the battery is (dis)charged at
+    fn usage(&self) -> Result<f64> {
+        let power_path = self.device_path.join("power_now");
+        let current_path = self.device_path.join("current_now");
+        if power_path.exists() {
+            read_file("battery", &power_path)?
+                .parse::<f64>()
+                .block_error("battery", "failed to parse power_now")
+        } else if current_path.exists() {
+            read_file("battery", &current_path)?
+                .parse::<f64>()
+                .block_error("battery", "failed to parse current_now")
+        } else {
+            Err(BlockError(
+                "battery".to_string(),
+                "Device does not support reading power".to_string(),
+            ))
+        }
+    }
 }
 
 impl BatteryDevice for PowerSupplyDevice {
@@ -190,49 +240,9 @@ impl BatteryDevice for PowerSupplyDevice {
             ))
         };
 
-        // Units are µWh
-        let full = if self.energy_full.is_some() {
-            self.energy_full
-        } else if self.charge_full.is_some() {
-            self.charge_full
-        } else {
-            None
-        };
-
-        // Units are µWh/µAh
-        let energy_path = self.device_path.join("energy_now");
-        let charge_path = self.device_path.join("charge_now");
-        let fill = if energy_path.exists() {
-            read_file("battery", &energy_path)?
-                .parse::<f64>()
-                .block_error("battery", "failed to parse energy_now")
-        } else if charge_path.exists() {
-            read_file("battery", &charge_path)?
-                .parse::<f64>()
-                .block_error("battery", "failed to parse charge_now")
-        } else {
-            Err(BlockError(
-                "battery".to_string(),
-                "Device does not support reading energy".to_string(),
-            ))
-        };
-
-        let power_path = self.device_path.join("power_now");
-        let current_path = self.device_path.join("current_now");
-        let usage = if power_path.exists() {
-            read_file("battery", &power_path)?
-                .parse::<f64>()
-                .block_error("battery", "failed to parse power_now")
-        } else if current_path.exists() {
-            read_file("battery", &current_path)?
-                .parse::<f64>()
-                .block_error("battery", "failed to parse current_now")
-        } else {
-            Err(BlockError(
-                "battery".to_string(),
-                "Device does not support reading power".to_string(),
-            ))
-        };
+        let full = self.full();
+        let fill = self.fill();
+        let usage = self.usage();
 
         // If the device driver uses the combination of energy_full, energy_now and power_now,
         // all values (full, fill, and usage) are in Watts, while if it uses charge_full, charge_now
@@ -243,8 +253,8 @@ impl BatteryDevice for PowerSupplyDevice {
             "Discharging" => {
                 if time_to_empty.is_ok() {
                     time_to_empty
-                } else if fill.is_ok() && usage.is_ok() {
-                    Ok(((fill.unwrap() / usage.unwrap()) * 60.0) as u64)
+                } else if let (Ok(fill), Ok(usage)) = (fill, usage) {
+                    Ok(minutes_until(fill, usage))
                 } else {
                     Err(BlockError(
                         "battery".to_string(),
@@ -256,8 +266,8 @@ impl BatteryDevice for PowerSupplyDevice {
             "Charging" => {
                 if time_to_full.is_ok() {
                     time_to_full
-                } else if full.is_some() && fill.is_ok() && usage.is_ok() {
-                    Ok((((full.unwrap() as f64 - fill.unwrap()) / usage.unwrap()) * 60.0) as u64)
+                } else if let (Some(full), Ok(fill), Ok(usage)) = (full, fill, usage) {
+                    Ok(minutes_until(full - fill, usage))
                 } else {
                     Err(BlockError(
                         "battery".to_string(),
@@ -305,6 +315,165 @@ impl BatteryDevice for PowerSupplyDevice {
     }
 }
 
+/// Several power supply devices combined into one battery, e.g. the internal and the
+/// removable battery of a laptop. The devices that are missing are left out.
+pub struct PowerSupplyGroup {
+    /// All batteries found in `/sys/class/power_supply`, instead of `batteries`
+    all: bool,
+    batteries: Vec<PowerSupplyDevice>,
+    allow_missing: bool,
+}
+
+impl PowerSupplyGroup {
+    /// Combines the power supply devices `devices`, or (re)discovers all batteries on every
+    /// refresh if `devices` is `["all"]`
+    pub fn from_devices(devices: &[String], allow_missing: bool) -> Result<Self> {
+        let all = devices.iter().any(|device| device == "all");
+        let batteries = devices
+            .iter()
+            .filter(|_| !all)
+            .map(|device| PowerSupplyDevice::from_device(device, true))
+            .collect::<Result<_>>()?;
+        Ok(PowerSupplyGroup {
+            all,
+            batteries,
+            allow_missing,
+        })
+    }
+
+    fn available(&self) -> impl Iterator<Item = &PowerSupplyDevice> {
+        self.batteries
+            .iter()
+            .filter(|battery| battery.is_available())
+    }
+
+    /// The batteries in `/sys/class/power_supply`
+    fn discover() -> Result<Vec<PowerSupplyDevice>> {
+        let mut names = std::fs::read_dir("/sys/class/power_supply")
+            .block_error("battery", "failed to list the power supply devices")?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                read_file("battery", &entry.path().join("type"))
+                    .map(|kind| kind == "Battery")
+                    .unwrap_or(false)
+            })
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+            .iter()
+            .map(|name| PowerSupplyDevice::from_device(name, true))
+            .collect()
+    }
+}
+
+impl BatteryDevice for PowerSupplyGroup {
+    fn is_available(&self) -> bool {
+        self.available().next().is_some()
+    }
+
+    fn refresh_device_info(&mut self) -> Result<()> {
+        if self.all {
+            self.batteries = Self::discover()?;
+        }
+        for battery in &mut self.batteries {
+            battery.refresh_device_info()?;
+        }
+        if !self.is_available() && !self.allow_missing {
+            return Err(BlockError(
+                "battery".into(),
+                "None of the power supply devices exist".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<String> {
+        let statuses = self
+            .available()
+            .map(|battery| battery.status())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(combined_status(&statuses))
+    }
+
+    fn capacity(&self) -> Result<u64> {
+        let mut full = 0.;
+        let mut fill = 0.;
+        for battery in self.available() {
+            match (battery.full(), battery.fill()) {
+                (Some(battery_full), Ok(battery_fill)) => {
+                    full += battery_full;
+                    fill += battery_fill;
+                }
+                // Without the energies, all batteries count the same
+                _ => {
+                    let capacities = self
+                        .available()
+                        .map(|battery| battery.capacity())
+                        .collect::<Result<Vec<_>>>()?;
+                    return Ok(capacities.iter().sum::<u64>() / capacities.len() as u64);
+                }
+            }
+        }
+        if full > 0. {
+            Ok(((fill / full * 100.) as u64).min(100))
+        } else {
+            Err(BlockError(
+                "battery".to_string(),
+                "Devices do not support reading capacity, charge, or energy".to_string(),
+            ))
+        }
+    }
+
+    fn time_remaining(&self) -> Result<u64> {
+        let mut full = 0.;
+        let mut fill = 0.;
+        let mut usage = 0.;
+        for battery in self.available() {
+            full += battery.full().unwrap_or(0.);
+            fill += battery.fill()?;
+            usage += battery.usage()?;
+        }
+        match self.status()?.as_str() {
+            "Discharging" => Ok(minutes_until(fill, usage)),
+            "Charging" => Ok(minutes_until(full - fill, usage)),
+            _ => Ok(0),
+        }
+    }
+
+    fn power_consumption(&self) -> Result<u64> {
+        self.available()
+            .map(|battery| battery.power_consumption())
+            .sum()
+    }
+}
+
+/// The status of several batteries: charging if any of them is charging, full if all of them
+/// are full
+fn combined_status(statuses: &[String]) -> String {
+    let any = |status: &str| statuses.iter().any(|s| s == status);
+    if any("Charging") {
+        "Charging"
+    } else if any("Discharging") {
+        "Discharging"
+    } else if !statuses.is_empty() && statuses.iter().all(|s| s == "Full" || s == "Not charging") {
+        "Full"
+    } else {
+        "Unknown"
+    }
+    .to_string()
+}
+
+/// The minutes it takes to (dis)charge `energy` at the rate `usage` (in per hour units of
+/// `energy`), or 0 if the battery is idle and no estimate is possible.
+fn minutes_until(energy: f64, usage: f64) -> u64 {
+    if usage > 0. && energy > 0. {
+        (energy / usage * 60.0) as u64
+    } else {
+        0
+    }
+}
+
 /// Represents a battery known to UPower.
 pub struct UpowerDevice {
     device_path: String,
@@ -489,10 +658,12 @@ pub struct Battery {
     allow_missing: bool,
     hide_missing: bool,
     driver: BatteryDriver,
-    good: u64,
-    info: u64,
-    warning: u64,
-    critical: u64,
+    good: f64,
+    info: f64,
+    warning: f64,
+    critical: f64,
+    blink_interval: Option<Duration>,
+    bar_glyphs: (String, String),
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -516,8 +687,10 @@ pub struct BatteryConfig {
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
 
-    /// The internal power supply device in `/sys/class/power_supply/` to read from.
-    pub device: String,
+    /// The internal power supply device in `/sys/class/power_supply/` to read from. A list of
+    /// devices, or "all", combines the batteries (sysfs only).
+    #[serde(deserialize_with = "deserialize_one_or_many")]
+    pub device: Vec<String>,
 
     /// Format string for displaying battery information.
     /// placeholders: {percentage}, {bar}, {time} and {power}
@@ -535,39 +708,48 @@ pub struct BatteryConfig {
     pub driver: BatteryDriver,
 
     /// The threshold above which the remaining capacity is shown as good
-    pub good: u64,
+    #[serde(deserialize_with = "deserialize_percent")]
+    pub good: f64,
 
     /// The threshold below which the remaining capacity is shown as info
-    pub info: u64,
+    #[serde(deserialize_with = "deserialize_percent")]
+    pub info: f64,
 
     /// The threshold below which the remaining capacity is shown as warning
-    pub warning: u64,
+    #[serde(deserialize_with = "deserialize_percent")]
+    pub warning: f64,
 
     /// The threshold below which the remaining capacity is shown as critical
-    pub critical: u64,
+    #[serde(deserialize_with = "deserialize_percent")]
+    pub critical: f64,
 
     /// If the battery device cannot be found, do not fail and show the block anyway (sysfs only).
     pub allow_missing: bool,
 
     /// If the battery device cannot be found, completely hide this block.
     pub hide_missing: bool,
+
+    /// Blink with this interval while the capacity is critical
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub blink_interval: Option<Duration>,
 }
 
 impl Default for BatteryConfig {
     fn default() -> Self {
         Self {
             interval: Duration::from_secs(10),
-            device: "BAT0".to_string(),
+            device: vec!["BAT0".to_string()],
             format: "{percentage}".to_string(),
             full_format: "".to_string(),
             missing_format: "{percentage}".to_string(),
             driver: BatteryDriver::Sysfs,
-            good: 60,
-            info: 60,
-            warning: 30,
-            critical: 15,
+            good: 60.,
+            info: 60.,
+            warning: 30.,
+            critical: 15.,
             allow_missing: false,
             hide_missing: false,
+            blink_interval: None,
         }
     }
 }
@@ -583,20 +765,38 @@ impl ConfigBlock for Battery {
     ) -> Result<Self> {
         let device: Box<dyn BatteryDevice> = match block_config.driver {
             BatteryDriver::Upower => {
-                let out = UpowerDevice::from_device(&block_config.device)?;
+                let device = match block_config.device.as_slice() {
+                    [device] if device == "all" => "DisplayDevice",
+                    [device] => device,
+                    _ => {
+                        return Err(ConfigurationError(
+                            "battery".to_string(),
+                            "the upower driver reads a single device, use \"DisplayDevice\" to combine all batteries".to_string(),
+                        ))
+                    }
+                };
+                let out = UpowerDevice::from_device(device)?;
                 out.monitor(id, update_request);
                 Box::new(out)
             }
-            BatteryDriver::Sysfs => Box::new(PowerSupplyDevice::from_device(
-                &block_config.device,
-                block_config.allow_missing,
-            )?),
+            BatteryDriver::Sysfs => match block_config.device.as_slice() {
+                [device] if device != "all" => Box::new(PowerSupplyDevice::from_device(
+                    device,
+                    block_config.allow_missing,
+                )?),
+                devices => Box::new(PowerSupplyGroup::from_devices(
+                    devices,
+                    block_config.allow_missing,
+                )?),
+            },
         };
 
         Ok(Battery {
             id,
             update_interval: block_config.interval,
-            output: TextWidget::new(id, 0, shared_config),
+            bar_glyphs: bar_glyphs(&shared_config.theme),
+            output: TextWidget::new(id, 0, shared_config)
+                .with_blink(block_config.blink_interval.is_some()),
             device,
             format: FormatTemplate::from_string(&block_config.format)?,
             full_format: FormatTemplate::from_string(&block_config.full_format)?,
@@ -608,6 +808,7 @@ impl ConfigBlock for Battery {
             info: block_config.info,
             warning: block_config.warning,
             critical: block_config.critical,
+            blink_interval: block_config.blink_interval,
         })
     }
 }
@@ -623,6 +824,7 @@ impl Block for Battery {
             // cannot be found right now.
             let values = map!(
                 "percentage" => Value::from_string("X".to_string()),
+                "bar" => Value::from_string(render_bar(0., progressbar::DEFAULT_WIDTH, &self.bar_glyphs.0, &self.bar_glyphs.1)),
                 "time" => Value::from_string("xx:xx".to_string()),
                 "power" => Value::from_string("N/A".to_string()),
             );
@@ -648,8 +850,14 @@ impl Block for Battery {
                 Ok(capacity) => Value::from_integer(capacity as i64).percents(),
                 _ => Value::from_string("×".into()),
             },
+            "bar" => Value::from_string(render_bar(
+                *capacity.as_ref().unwrap_or(&0) as f64,
+                progressbar::DEFAULT_WIDTH,
+                &self.bar_glyphs.0,
+                &self.bar_glyphs.1,
+            )),
             "time" => match self.device.time_remaining() {
-                Ok(0) => Value::from_string("".into()),
+                Ok(0) => Value::from_string("--:--".into()),
                 Ok(time) => Value::from_string(format!("{}:{:02}", std::cmp::min(time / 60, 99), time % 60)),
                 _ => Value::from_string("×".into()),
             },
@@ -677,6 +885,7 @@ impl Block for Battery {
                 _ => {
                     self.output.set_state(match capacity {
                         Ok(capacity) => {
+                            let capacity = capacity as f64;
                             if capacity <= self.critical {
                                 State::Critical
                             } else if capacity <= self.warning {
@@ -694,14 +903,25 @@ impl Block for Battery {
                 }
             }
 
-            self.output.set_icon(match status.as_str() {
-                "Discharging" => battery_level_to_icon(capacity),
-                "Charging" => "bat_charging",
-                _ => battery_level_to_icon(capacity),
-            })?;
+            // Use the `bat` and `bat_charging` icon ramps if the icon set has them
+            let level = *capacity.as_ref().unwrap_or(&100) as f64;
+            match status.as_str() {
+                "Charging" => self
+                    .output
+                    .set_ramp_icon("bat_charging", level, "bat_charging")?,
+                _ => self
+                    .output
+                    .set_ramp_icon("bat", level, battery_level_to_icon(capacity))?,
+            }
             self.output.set_spacing(Spacing::Normal);
         }
 
+        if let Some(blink_interval) = self.blink_interval {
+            if self.output.blink() {
+                return Ok(Some(blink_interval.into()));
+            }
+        }
+
         match self.driver {
             BatteryDriver::Sysfs => Ok(Some(self.update_interval.into())),
             BatteryDriver::Upower => Ok(None),
@@ -721,3 +941,31 @@ impl Block for Battery {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{combined_status, minutes_until};
+
+    #[test]
+    fn time_remaining_estimate() {
+        // 30 Wh left at 15 W
+        assert_eq!(minutes_until(30e6, 15e6), 120);
+        assert_eq!(minutes_until(1e6, 7e6), 8);
+        // Idle or bogus readings don't divide by zero
+        assert_eq!(minutes_until(30e6, 0.), 0);
+        assert_eq!(minutes_until(30e6, -1.), 0);
+        assert_eq!(minutes_until(-2e6, 15e6), 0);
+    }
+
+    #[test]
+    fn combine_statuses() {
+        let status = |statuses: &[&str]| {
+            combined_status(&statuses.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+        };
+        assert_eq!(status(&["Discharging", "Charging"]), "Charging");
+        assert_eq!(status(&["Full", "Discharging"]), "Discharging");
+        assert_eq!(status(&["Full", "Not charging"]), "Full");
+        assert_eq!(status(&["Full", "Unknown"]), "Unknown");
+        assert_eq!(status(&[]), "Unknown");
+    }
+}
@@ -215,11 +215,19 @@ impl BluetoothDevice {
     }
 }
 
+/// How long a click waits for the device to (dis)connect before it is given up
+const PENDING_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct Bluetooth {
     id: usize,
     output: TextWidget,
     device: BluetoothDevice,
+    /// The connection state requested by a click, and when, until the device gets there
+    pending: Option<(bool, Instant)>,
+    send: Sender<Task>,
     hide_disconnected: bool,
+    battery_warning: u8,
+    battery_critical: u8,
     format: FormatTemplate,
     format_unavailable: FormatTemplate,
 }
@@ -233,6 +241,12 @@ pub struct BluetoothConfig {
     pub label: Option<String>,
     #[serde(default = "BluetoothConfig::default_hide_disconnected")]
     pub hide_disconnected: bool,
+    /// The battery level (in percents) below which the block turns warning
+    #[serde(default = "BluetoothConfig::default_battery_warning")]
+    pub battery_warning: u8,
+    /// The battery level (in percents) below which the block turns critical
+    #[serde(default = "BluetoothConfig::default_battery_critical")]
+    pub battery_critical: u8,
     #[serde(default = "BluetoothConfig::default_format")]
     pub format: String,
     #[serde(default = "BluetoothConfig::default_format_unavailable")]
@@ -244,6 +258,14 @@ impl BluetoothConfig {
         false
     }
 
+    fn default_battery_warning() -> u8 {
+        30
+    }
+
+    fn default_battery_critical() -> u8 {
+        15
+    }
+
     fn default_format() -> String {
         "{label} {percentage}".into()
     }
@@ -263,7 +285,7 @@ impl ConfigBlock for Bluetooth {
         send: Sender<Task>,
     ) -> Result<Self> {
         let device = BluetoothDevice::new(block_config.mac, block_config.label)?;
-        device.monitor(id, send);
+        device.monitor(id, send.clone());
 
         Ok(Bluetooth {
             id,
@@ -275,7 +297,11 @@ impl ConfigBlock for Bluetooth {
                 _ => "bluetooth",
             })?,
             device,
+            pending: None,
+            send,
             hide_disconnected: block_config.hide_disconnected,
+            battery_warning: block_config.battery_warning,
+            battery_critical: block_config.battery_critical,
             format: FormatTemplate::from_string(&block_config.format)?,
             format_unavailable: FormatTemplate::from_string(&block_config.format_unavailable)?,
         })
@@ -289,11 +315,23 @@ impl Block for Bluetooth {
 
     fn update(&mut self) -> Result<Option<Update>> {
         if self.device.available()? {
+            let battery = self.device.battery();
+            // Empty for devices that don't report their battery level
+            let battery_value = match battery {
+                Some(value) => Value::from_integer(value as i64).percents(),
+                None => Value::from_string("".into()),
+            };
             let values = map!(
                 "label" => Value::from_string(self.device.label.clone()),
-                "percentage" => Value::from_integer(self.device.battery().unwrap_or(0) as i64).percents(),
+                "battery" => battery_value.clone(),
+                "percentage" => battery_value,
             );
             let connected = self.device.connected();
+            if let Some((target, since)) = self.pending {
+                if connected == target || since.elapsed() >= PENDING_TIMEOUT {
+                    self.pending = None;
+                }
+            }
             self.output.set_text(self.device.label.to_string());
             self.output
                 .set_state(if connected { State::Good } else { State::Idle });
@@ -307,41 +345,94 @@ impl Block for Bluetooth {
             })?;
 
             // Use battery info, when available.
-            if let Some(value) = self.device.battery() {
-                self.output.set_state(match value {
-                    0..=15 => State::Critical,
-                    16..=30 => State::Warning,
-                    31..=60 => State::Info,
-                    61..=100 => State::Good,
-                    _ => State::Warning,
-                });
+            if let Some(value) = battery {
+                self.output.set_state(battery_state(
+                    value,
+                    self.battery_warning,
+                    self.battery_critical,
+                ));
+            }
+            // Connecting or disconnecting after a click
+            if self.pending.is_some() {
+                self.output.set_state(State::Info);
             }
             self.output.set_text(self.format.render(&values)?);
         } else {
             let values = map!(
                 "label" => Value::from_string(self.device.label.clone()),
+                "battery" => Value::from_string("".into()),
                 "percentage" => Value::from_string("".into()),
             );
             self.output.set_state(State::Idle);
             self.output
                 .set_text(self.format_unavailable.render(&values)?);
+            self.pending = None;
         }
 
         Ok(None)
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
-        if let MouseButton::Right = event.button {
-            self.device.toggle()?;
+        if let MouseButton::Left | MouseButton::Right = event.button {
+            if self.device.available()? {
+                let target = !self.device.connected();
+                self.device.toggle()?;
+                self.pending = Some((target, Instant::now()));
+                self.update()?;
+
+                // The device signals the change of its connection, but not a failure to connect
+                let (id, send) = (self.id, self.send.clone());
+                thread::Builder::new()
+                    .name("bluetooth".into())
+                    .spawn(move || {
+                        thread::sleep(PENDING_TIMEOUT);
+                        // The block may be gone when the bar exits
+                        let _ = send.send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        });
+                    })
+                    .block_error("bluetooth", "failed to spawn thread")?;
+            }
         }
         Ok(())
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        if !self.device.connected() && self.hide_disconnected {
+        if !self.device.connected() && self.hide_disconnected && self.pending.is_none() {
             vec![]
         } else {
             vec![&self.output]
         }
     }
 }
+
+/// The state of the block for a device with `percentage` battery left
+fn battery_state(percentage: u8, warning: u8, critical: u8) -> State {
+    if percentage > 100 {
+        State::Warning
+    } else if percentage <= critical {
+        State::Critical
+    } else if percentage <= warning {
+        State::Warning
+    } else if percentage <= 60 {
+        State::Info
+    } else {
+        State::Good
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_states() {
+        assert!(matches!(battery_state(15, 30, 15), State::Critical));
+        assert!(matches!(battery_state(16, 30, 15), State::Warning));
+        assert!(matches!(battery_state(45, 30, 15), State::Info));
+        assert!(matches!(battery_state(80, 30, 15), State::Good));
+        assert!(matches!(battery_state(45, 50, 20), State::Warning));
+        assert!(matches!(battery_state(255, 30, 15), State::Warning));
+    }
+}
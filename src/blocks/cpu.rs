@@ -1,4 +1,4 @@
-use std::fs::File;
+use std::fs::{read_to_string, File};
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::time::Duration;
@@ -8,23 +8,28 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_percent};
 use crate::errors::*;
+use crate::formatting::gradient::Gradient;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
+use crate::util::{format_vec_to_bar_graph, graph_ramp};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
 pub struct Cpu {
     id: usize,
     output: TextWidget,
-    prev_util: Vec<(u64, u64)>,
+    /// The previous (idle, non idle) times of all CPUs, then of the cores by their number
+    prev_util: Vec<Option<(u64, u64)>>,
     update_interval: Duration,
-    minimum_info: u64,
-    minimum_warning: u64,
-    minimum_critical: u64,
+    minimum_info: f64,
+    minimum_warning: f64,
+    minimum_critical: f64,
     format: FormatTemplate,
+    gradient: Option<Gradient>,
+    graph_ramp: Vec<char>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -35,26 +40,33 @@ pub struct CpuConfig {
     pub interval: Duration,
 
     /// Minimum usage, where state is set to info
-    pub info: u64,
+    #[serde(deserialize_with = "deserialize_percent")]
+    pub info: f64,
 
     /// Minimum usage, where state is set to warning
-    pub warning: u64,
+    #[serde(deserialize_with = "deserialize_percent")]
+    pub warning: f64,
 
     /// Minimum usage, where state is set to critical
-    pub critical: u64,
+    #[serde(deserialize_with = "deserialize_percent")]
+    pub critical: f64,
 
     /// Format override
     pub format: String,
+
+    /// Color the utilization placeholders according to this gradient
+    pub gradient: Option<Gradient>,
 }
 
 impl Default for CpuConfig {
     fn default() -> Self {
         Self {
             interval: Duration::from_secs(1),
-            info: 30,
-            warning: 60,
-            critical: 90,
+            info: 30.,
+            warning: 60.,
+            critical: 90.,
             format: "{utilization}".to_string(),
+            gradient: None,
         }
     }
 }
@@ -71,6 +83,7 @@ impl ConfigBlock for Cpu {
         Ok(Cpu {
             id,
             update_interval: block_config.interval,
+            graph_ramp: graph_ramp(&shared_config.theme),
             output: TextWidget::new(id, 0, shared_config).with_icon("cpu")?,
             prev_util: Vec::with_capacity(32),
             minimum_info: block_config.info,
@@ -78,102 +91,160 @@ impl ConfigBlock for Cpu {
             minimum_critical: block_config.critical,
             format: FormatTemplate::from_string(&block_config.format)
                 .block_error("cpu", "Invalid format specified for cpu")?,
+            gradient: block_config.gradient,
         })
     }
 }
 
+impl Cpu {
+    fn colored(&self, value: Value) -> Value {
+        match self.gradient {
+            Some(ref gradient) => value.gradient(gradient),
+            None => value,
+        }
+    }
+}
+
+/// The current frequencies of the cores (in Hz), as set by cpufreq. Falls back to the ones in
+/// /proc/cpuinfo if cpufreq isn't available, and to none if neither is.
+fn read_frequencies() -> Vec<f64> {
+    let mut cores: Vec<(usize, f64)> = std::fs::read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let core = entry
+                .file_name()
+                .to_str()?
+                .strip_prefix("cpu")?
+                .parse::<usize>()
+                .ok()?;
+            // In kHz
+            let freq = read_to_string(entry.path().join("cpufreq/scaling_cur_freq")).ok()?;
+            Some((core, freq.trim().parse::<f64>().ok()? * 1e3))
+        })
+        .collect();
+    if !cores.is_empty() {
+        cores.sort_by_key(|(core, _)| *core);
+        return cores.into_iter().map(|(_, freq)| freq).collect();
+    }
+
+    read_to_string("/proc/cpuinfo")
+        .map(|cpuinfo| parse_cpuinfo_frequencies(&cpuinfo))
+        .unwrap_or_default()
+}
+
+/// The frequencies (in Hz) of the `cpu MHz` lines of /proc/cpuinfo
+fn parse_cpuinfo_frequencies(cpuinfo: &str) -> Vec<f64> {
+    cpuinfo
+        .lines()
+        .filter(|line| line.starts_with("cpu MHz"))
+        .filter_map(|line| line.split(':').nth(1)?.trim().parse::<f64>().ok())
+        .map(|mhz| mhz * 1e6)
+        .collect()
+}
+
+/// Parses a line of /proc/stat into the index of the CPU (0 for the line with the times of all
+/// CPUs, n + 1 for core n) and its idle and non idle times
+fn parse_stat_line(line: &str) -> Option<(usize, u64, u64)> {
+    let mut words = line.split_whitespace();
+    let name = words.next()?.strip_prefix("cpu")?;
+    let index = if name.is_empty() {
+        0
+    } else {
+        name.parse::<usize>().ok()? + 1
+    };
+    let data: Vec<u64> = words.filter_map(|x| x.parse::<u64>().ok()).collect();
+    if data.len() < 8 {
+        return None;
+    }
+
+    // idle = idle + iowait
+    let idle = data[3] + data[4];
+    let non_idle = data[0] + // user
+                    data[1] + // nice
+                    data[2] + // system
+                    data[5] + // irq
+                    data[6] + // softirq
+                    data[7]; // steal
+    Some((index, idle, non_idle))
+}
+
 impl Block for Cpu {
     fn update(&mut self) -> Result<Option<Update>> {
-        // Read frequencies (read in MHz, store in Hz)
-        let mut freqs = Vec::with_capacity(32);
-        let mut freqs_avg = 0.0;
-        let freqs_f =
-            File::open("/proc/cpuinfo").block_error("cpu", "failed to read /proc/cpuinfo")?;
-        for line in BufReader::new(freqs_f).lines().scan((), |_, x| x.ok()) {
-            if line.starts_with("cpu MHz") {
-                let words = line.split(' ');
-                let last = words
-                    .last()
-                    .expect("failed to get last word of line while getting cpu frequency");
-                let numb = last
-                    .parse::<f64>()
-                    .expect("failed to parse String to f64 while getting cpu frequency")
-                    * 1e6; // convert to Hz
-                freqs.push(numb);
-                freqs_avg += numb;
-            }
-        }
-        freqs_avg /= freqs.len() as f64;
+        let freqs = read_frequencies();
+        let freqs_avg = if freqs.is_empty() {
+            0.
+        } else {
+            freqs.iter().sum::<f64>() / freqs.len() as f64
+        };
+        let freqs_max = freqs.iter().copied().fold(0., f64::max);
 
-        // Read utilizations
-        let mut utilizations = Vec::with_capacity(32);
+        // Read utilizations. Cores can go offline (and come back), so the cores are identified by
+        // their number instead of their position in /proc/stat.
+        let mut utilizations: Vec<Option<f64>> = Vec::with_capacity(32);
         let utilizations_f = File::open("/proc/stat")
             .block_error("cpu", "Your system doesn't support /proc/stat")?;
-        for (i, line) in BufReader::new(utilizations_f)
+        for line in BufReader::new(utilizations_f)
             .lines()
             .scan((), |_, x| x.ok())
-            .enumerate()
         {
-            if line.starts_with("cpu") {
-                let data: Vec<u64> = line
-                    .split_whitespace()
-                    .filter_map(|x| x.parse::<u64>().ok())
-                    .collect();
-
-                // idle = idle + iowait
-                let idle = data[3] + data[4];
-                let non_idle = data[0] + // user
-                                data[1] + // nice
-                                data[2] + // system
-                                data[5] + // irq
-                                data[6] + // softirq
-                                data[7]; // steal
-
-                let (prev_idles, prev_non_idles) = if self.prev_util.len() <= i {
-                    self.prev_util.push((0, 0));
-                    (0, 0)
-                } else {
-                    self.prev_util[i]
-                };
-
-                let prev_total = prev_idles + prev_non_idles;
-                let total = idle + non_idle;
-
-                // This check is needed because the new values may be reset, for
-                // example after hibernation.
-                let (total_delta, idle_delta) = if prev_total < total && prev_idles <= idle {
-                    (total - prev_total, idle - prev_idles)
-                } else {
-                    (1, 1)
-                };
-
-                utilizations
-                    .push(((total_delta - idle_delta) as f64 / total_delta as f64).clamp(0., 1.));
-
-                self.prev_util[i] = (idle, non_idle);
+            let (i, idle, non_idle) = match parse_stat_line(&line) {
+                Some(times) => times,
+                None => continue,
+            };
+            if self.prev_util.len() <= i {
+                self.prev_util.resize(i + 1, None);
+            }
+            if utilizations.len() <= i {
+                utilizations.resize(i + 1, None);
+            }
+
+            let (prev_idles, prev_non_idles) = self.prev_util[i].unwrap_or((0, 0));
+            let prev_total = prev_idles + prev_non_idles;
+            let total = idle + non_idle;
+
+            // This check is needed because the new values may be reset, for
+            // example after hibernation.
+            let (total_delta, idle_delta) = if prev_total < total && prev_idles <= idle {
+                (total - prev_total, idle - prev_idles)
+            } else {
+                (1, 1)
+            };
+
+            utilizations[i] =
+                Some(((total_delta - idle_delta) as f64 / total_delta as f64).clamp(0., 1.));
+            self.prev_util[i] = Some((idle, non_idle));
+        }
+        // Forget the cores that went offline, so they start over when they come back
+        for (i, prev) in self.prev_util.iter_mut().enumerate() {
+            if utilizations.get(i).copied().flatten().is_none() {
+                *prev = None;
             }
         }
+        // Offline cores count as idle. Their placeholders stay available.
+        let utilizations: Vec<f64> = utilizations.iter().map(|u| u.unwrap_or(0.)).collect();
 
-        let (avg, utilizations) = utilizations.split_first().unwrap();
+        let (avg, utilizations) = utilizations
+            .split_first()
+            .block_error("cpu", "no cpu in /proc/stat")?;
         let avg_utilization = avg * 100.;
 
-        self.output.set_state(match avg_utilization as u64 {
+        self.output.set_state(match avg_utilization {
             x if x > self.minimum_critical => State::Critical,
             x if x > self.minimum_warning => State::Warning,
             x if x > self.minimum_info => State::Info,
             _ => State::Idle,
         });
 
-        let mut barchart = String::new();
-        const BOXCHARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
-        for utilization in utilizations {
-            barchart.push(BOXCHARS[(7.5 * utilization) as usize]);
-        }
+        let barchart =
+            format_vec_to_bar_graph(utilizations, Some(0.), Some(1.), &self.graph_ramp, false);
 
         let mut values = map!(
             "frequency" => Value::from_float(freqs_avg).hertz(),
+            "max_frequency" => Value::from_float(freqs_max).hertz(),
             "barchart" => Value::from_string(barchart),
-            "utilization" => Value::from_integer(avg_utilization as i64).percents(),
+            "utilization" => self.colored(Value::from_integer(avg_utilization as i64).percents()),
         );
         let mut frequency_keys = vec![]; // There should be a better way to dynamically crate keys?
         for i in 0..freqs.len() {
@@ -189,7 +260,7 @@ impl Block for Cpu {
         for (i, utilization) in utilizations.iter().enumerate() {
             values.insert(
                 &utilization_keys[i],
-                Value::from_integer((utilization * 100.) as i64).percents(),
+                self.colored(Value::from_integer((utilization * 100.) as i64).percents()),
             );
         }
 
@@ -206,3 +277,29 @@ impl Block for Cpu {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cpuinfo_frequencies, parse_stat_line};
+
+    #[test]
+    fn cpuinfo_frequencies() {
+        let cpuinfo = "processor\t: 0\ncpu MHz\t\t: 2400.000\nprocessor\t: 1\ncpu MHz\t\t: 800.5\n";
+        assert_eq!(parse_cpuinfo_frequencies(cpuinfo), vec![2.4e9, 800.5e6]);
+        assert!(parse_cpuinfo_frequencies("processor\t: 0\nBogoMIPS\t: 48.00\n").is_empty());
+    }
+
+    #[test]
+    fn stat_lines() {
+        assert_eq!(
+            parse_stat_line("cpu  10 1 5 100 4 2 3 1 0 0"),
+            Some((0, 104, 22))
+        );
+        assert_eq!(
+            parse_stat_line("cpu3 10 1 5 100 4 2 3 1 0 0"),
+            Some((4, 104, 22))
+        );
+        assert_eq!(parse_stat_line("intr 1234 0 0"), None);
+        assert_eq!(parse_stat_line("cpu1 10 1"), None);
+    }
+}
@@ -7,10 +7,15 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{
+    deserialize_duration, deserialize_one_or_many_expanded, deserialize_opt_percent, Threshold,
+};
 use crate::errors::*;
 use crate::formatting::FormatTemplate;
-use crate::formatting::{prefix::Prefix, value::Value};
+use crate::formatting::{
+    prefix::{BytePrefix, Prefix},
+    value::Value,
+};
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -27,14 +32,18 @@ pub struct DiskSpace {
     id: usize,
     disk_space: TextWidget,
     update_interval: Duration,
-    path: String,
+    paths: Vec<String>,
+    path_separator: String,
     unit: Prefix,
     info_type: InfoType,
-    warning: f64,
-    alert: f64,
+    warning: Threshold,
+    alert: Threshold,
     alert_absolute: bool,
+    inodes_warning: Option<f64>,
+    inodes_alert: Option<f64>,
     format: FormatTemplate,
     icon: String,
+    byte_prefix: BytePrefix,
 
     // DEPRECATED
     // TODO remove
@@ -44,8 +53,12 @@ pub struct DiskSpace {
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct DiskSpaceConfig {
-    /// Path to collect information from
-    pub path: String,
+    /// Path to collect information from, or a list of paths to show one after another
+    #[serde(deserialize_with = "deserialize_one_or_many_expanded")]
+    pub path: Vec<String>,
+
+    /// Text between the paths, if there are several
+    pub path_separator: String,
 
     /// Currently supported options are available, free, total and used
     /// Sets value used for {percentage} calculation
@@ -62,15 +75,26 @@ pub struct DiskSpaceConfig {
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
 
-    /// Diskspace warning (yellow)
-    pub warning: f64,
+    /// Diskspace warning (yellow), or a size like "20GiB"
+    pub warning: Threshold,
 
-    /// Diskspace alert (red)
-    pub alert: f64,
+    /// Diskspace alert (red), or a size like "10GiB"
+    pub alert: Threshold,
 
     /// use absolute (unit) values for disk space alerts
     pub alert_absolute: bool,
 
+    /// Percentage of used inodes above which the state is warning
+    #[serde(deserialize_with = "deserialize_opt_percent")]
+    pub inodes_warning: Option<f64>,
+
+    /// Percentage of used inodes above which the state is critical
+    #[serde(deserialize_with = "deserialize_opt_percent")]
+    pub inodes_alert: Option<f64>,
+
+    /// How byte values are scaled and labelled. Options are binary, si and iec
+    pub byte_prefix: BytePrefix,
+
     /// Alias that is displayed for path
     // DEPRECATED
     // TODO remove
@@ -80,14 +104,18 @@ pub struct DiskSpaceConfig {
 impl Default for DiskSpaceConfig {
     fn default() -> Self {
         Self {
-            path: "/".to_string(),
+            path: vec!["/".to_string()],
+            path_separator: " ".to_string(),
             info_type: InfoType::Available,
             format: "{available}".to_string(),
             unit: "GB".to_string(),
             interval: Duration::from_secs(20),
-            warning: 20.,
-            alert: 10.,
+            warning: Threshold::Value(20.),
+            alert: Threshold::Value(10.),
             alert_absolute: false,
+            inodes_warning: None,
+            inodes_alert: None,
+            byte_prefix: BytePrefix::default(),
             alias: "/".to_string(),
         }
     }
@@ -138,7 +166,8 @@ impl ConfigBlock for DiskSpace {
             id,
             update_interval: block_config.interval,
             disk_space: TextWidget::new(id, 0, shared_config),
-            path: block_config.path,
+            paths: block_config.path,
+            path_separator: block_config.path_separator,
             format: FormatTemplate::from_string(&block_config.format)?,
             info_type: block_config.info_type,
             unit: match block_config.unit.as_str() {
@@ -157,22 +186,37 @@ impl ConfigBlock for DiskSpace {
             warning: block_config.warning,
             alert: block_config.alert,
             alert_absolute: block_config.alert_absolute,
+            inodes_warning: block_config.inodes_warning,
+            inodes_alert: block_config.inodes_alert,
+            byte_prefix: block_config.byte_prefix,
             icon: icon.trim().to_string(),
             alias: block_config.alias,
         })
     }
 }
 
-impl Block for DiskSpace {
-    fn update(&mut self) -> Result<Option<Update>> {
-        let statvfs = statvfs(Path::new(self.path.as_str()))
-            .block_error("disk_space", "failed to retrieve statvfs")?;
+impl DiskSpace {
+    /// Renders `format` for `path`, and the state of its disk space
+    fn render_path(&self, path: &str) -> Result<(String, State)> {
+        let statvfs = statvfs(Path::new(path)).block_error(
+            "disk_space",
+            &format!("failed to retrieve statvfs of '{}'", path),
+        )?;
 
         let total = (statvfs.blocks() as u64) * (statvfs.fragment_size() as u64);
         let used = ((statvfs.blocks() as u64) - (statvfs.blocks_free() as u64))
             * (statvfs.fragment_size() as u64);
         let available = (statvfs.blocks_available() as u64) * (statvfs.block_size() as u64);
         let free = (statvfs.blocks_free() as u64) * (statvfs.block_size() as u64);
+        let inodes = statvfs.files() as u64;
+        let inodes_free = statvfs.files_free() as u64;
+        let inodes_used = inodes.saturating_sub(inodes_free);
+        // Some filesystems (e.g. btrfs) don't have a fixed number of inodes
+        let inodes_used_percent = if inodes > 0 {
+            inodes_used as f64 / inodes as f64 * 100.
+        } else {
+            0.
+        };
 
         let result;
         let alert_type;
@@ -194,34 +238,75 @@ impl Block for DiskSpace {
         let percentage = result / (total as f64) * 100.;
         let values = map!(
             "percentage" => Value::from_float(percentage).percents(),
-            "path" => Value::from_string(self.path.clone()),
-            "total" => Value::from_float(total as f64).bytes(),
-            "used" => Value::from_float(used as f64).bytes(),
-            "available" => Value::from_float(available as f64).bytes(),
-            "free" => Value::from_float(free as f64).bytes(),
+            "path" => Value::from_string(path.to_string()),
+            "total" => Value::from_float(total as f64).bytes().byte_prefix(self.byte_prefix),
+            "used" => Value::from_float(used as f64).bytes().byte_prefix(self.byte_prefix),
+            "available" => Value::from_float(available as f64).bytes().byte_prefix(self.byte_prefix),
+            "free" => Value::from_float(free as f64).bytes().byte_prefix(self.byte_prefix),
+            "inodes_used" => Value::from_integer(inodes_used as i64),
+            "inodes_free" => Value::from_integer(inodes_free as i64),
+            "inodes_used_percent" => Value::from_float(inodes_used_percent).percents(),
             "icon" => Value::from_string(self.icon.to_string()),
             //TODO remove
             "alias" => Value::from_string(self.alias.clone()),
         );
-        self.disk_space.set_text(self.format.render(&values)?);
+        let text = self.format.render(&values)?;
 
         // Send percentage to alert check if we don't want absolute alerts
-        let alert_val = if self.alert_absolute {
-            result
-                / match self.unit {
-                    Prefix::Tera => 1u64 << 40,
-                    Prefix::Giga => 1u64 << 30,
-                    Prefix::Mega => 1u64 << 20,
-                    Prefix::Kilo => 1u64 << 10,
-                    Prefix::One => 1u64,
-                    _ => unreachable!(),
-                } as f64
+        let bytes_per_unit = if self.alert_absolute {
+            (match self.unit {
+                Prefix::Tera => 1u64 << 40,
+                Prefix::Giga => 1u64 << 30,
+                Prefix::Mega => 1u64 << 20,
+                Prefix::Kilo => 1u64 << 10,
+                Prefix::One => 1u64,
+                _ => unreachable!(),
+            }) as f64
         } else {
-            percentage
+            total as f64 / 100.
         };
+        let alert_val = result / bytes_per_unit;
+
+        let state = self.compute_state(
+            alert_val,
+            self.warning.value(bytes_per_unit),
+            self.alert.value(bytes_per_unit),
+            alert_type,
+        );
+        // Running out of inodes is as bad as running out of space
+        let inodes_state = self.compute_state(
+            inodes_used_percent,
+            self.inodes_warning.unwrap_or(f64::INFINITY),
+            self.inodes_alert.unwrap_or(f64::INFINITY),
+            AlertType::Above,
+        );
+        Ok((text, worst_state(&[state, inodes_state])))
+    }
+}
+
+/// The state of the fullest disk
+fn worst_state(states: &[State]) -> State {
+    if states.iter().any(|s| matches!(s, State::Critical)) {
+        State::Critical
+    } else if states.iter().any(|s| matches!(s, State::Warning)) {
+        State::Warning
+    } else {
+        State::Idle
+    }
+}
+
+impl Block for DiskSpace {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let (texts, states): (Vec<_>, Vec<_>) = self
+            .paths
+            .iter()
+            .map(|path| self.render_path(path))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
 
-        let state = self.compute_state(alert_val, self.warning, self.alert, alert_type);
-        self.disk_space.set_state(state);
+        self.disk_space.set_text(texts.join(&self.path_separator));
+        self.disk_space.set_state(worst_state(&states));
 
         Ok(Some(self.update_interval.into()))
     }
@@ -234,3 +319,17 @@ impl Block for DiskSpace {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn worst_mount_sets_state() {
+        use State::*;
+        assert!(matches!(worst_state(&[Idle, Idle]), Idle));
+        assert!(matches!(worst_state(&[Idle, Warning, Idle]), Warning));
+        assert!(matches!(worst_state(&[Warning, Critical]), Critical));
+        assert!(matches!(worst_state(&[]), Idle));
+    }
+}
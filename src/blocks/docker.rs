@@ -1,24 +1,32 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
+use regex::Regex;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_one_or_many};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::http;
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
-use crate::widgets::I3BarWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const SOCKET_PATH: &str = "/var/run/docker.sock";
 
 pub struct Docker {
     id: usize,
     text: TextWidget,
     format: FormatTemplate,
     update_interval: Duration,
+    name_filter: Option<Regex>,
+    label_filter: Vec<LabelFilter>,
+    /// The last CPU usage sample of each running container, by id
+    cpu_samples: HashMap<String, CpuSample>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -39,6 +47,153 @@ struct Status {
     images: i64,
 }
 
+/// An entry of the container list of the Docker API
+#[derive(Deserialize, Debug, Clone)]
+struct Container {
+    #[serde(rename = "Id")]
+    id: String,
+
+    #[serde(rename = "Names", default)]
+    names: Vec<String>,
+
+    /// `created`, `restarting`, `running`, `removing`, `paused`, `exited` or `dead`
+    #[serde(rename = "State", default)]
+    state: String,
+
+    #[serde(rename = "Labels", default)]
+    labels: Option<HashMap<String, String>>,
+}
+
+/// The parts of the container stats of the Docker API the block uses
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct ContainerStats {
+    cpu_stats: CpuStats,
+    memory_stats: MemoryStats,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct CpuStats {
+    cpu_usage: CpuUsage,
+    /// Nanoseconds of CPU time of the host, absent on Windows
+    system_cpu_usage: Option<u64>,
+    online_cpus: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct CpuUsage {
+    /// Nanoseconds of CPU time of the container
+    total_usage: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+struct MemoryStats {
+    usage: Option<u64>,
+    stats: HashMap<String, u64>,
+}
+
+impl MemoryStats {
+    /// The used memory without the page cache, as `docker stats` shows it. The cache is
+    /// `inactive_file` with cgroup v2 and `cache` with cgroup v1.
+    fn used(&self) -> Option<u64> {
+        let cache = self
+            .stats
+            .get("inactive_file")
+            .or_else(|| self.stats.get("cache"))
+            .copied()
+            .unwrap_or(0);
+        self.usage.map(|usage| usage.saturating_sub(cache))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CpuSample {
+    container: u64,
+    system: u64,
+    online_cpus: u64,
+}
+
+impl CpuSample {
+    fn of(stats: &CpuStats) -> Option<Self> {
+        Some(CpuSample {
+            container: stats.cpu_usage.total_usage,
+            system: stats.system_cpu_usage?,
+            online_cpus: stats.online_cpus.unwrap_or(1),
+        })
+    }
+
+    /// The CPU usage in percents between the `previous` sample and this one, where 100% is one
+    /// fully used core
+    fn percents_since(&self, previous: &CpuSample) -> f64 {
+        let container = self.container.saturating_sub(previous.container) as f64;
+        let system = self.system.saturating_sub(previous.system) as f64;
+        if system == 0. {
+            0.
+        } else {
+            container / system * self.online_cpus as f64 * 100.
+        }
+    }
+}
+
+/// A `label_filter` entry: `key` matches containers with the label, `key=value` those where
+/// it has that value.
+#[derive(Debug, Clone, PartialEq)]
+struct LabelFilter {
+    key: String,
+    value: Option<String>,
+}
+
+impl LabelFilter {
+    fn parse(filter: &str) -> Self {
+        match filter.split_once('=') {
+            Some((key, value)) => LabelFilter {
+                key: key.to_string(),
+                value: Some(value.to_string()),
+            },
+            None => LabelFilter {
+                key: filter.to_string(),
+                value: None,
+            },
+        }
+    }
+
+    fn matches(&self, container: &Container) -> bool {
+        match container.labels.as_ref().and_then(|l| l.get(&self.key)) {
+            Some(value) => self.value.as_ref().is_none_or(|v| v == value),
+            None => false,
+        }
+    }
+}
+
+/// The number of containers in each state
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Counts {
+    total: i64,
+    running: i64,
+    paused: i64,
+    exited: i64,
+}
+
+impl Counts {
+    fn of(containers: &[&Container]) -> Self {
+        let count = |state: &str| containers.iter().filter(|c| c.state == state).count() as i64;
+        Counts {
+            total: containers.len() as i64,
+            running: count("running"),
+            paused: count("paused"),
+            exited: count("exited"),
+        }
+    }
+
+    /// All containers which are neither running nor paused, as in `docker info`
+    fn stopped(&self) -> i64 {
+        self.total - self.running - self.paused
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct DockerConfig {
@@ -48,6 +203,13 @@ pub struct DockerConfig {
 
     /// Format override
     pub format: String,
+
+    /// Only count the containers with a name matching this regex
+    pub name_filter: Option<String>,
+
+    /// Only count the containers with all of these labels, given as `key` or `key=value`
+    #[serde(deserialize_with = "deserialize_one_or_many")]
+    pub label_filter: Vec<String>,
 }
 
 impl Default for DockerConfig {
@@ -55,6 +217,8 @@ impl Default for DockerConfig {
         Self {
             interval: Duration::from_secs(5),
             format: "{running}".to_string(),
+            name_filter: None,
+            label_filter: Vec::new(),
         }
     }
 }
@@ -77,32 +241,141 @@ impl ConfigBlock for Docker {
             format: FormatTemplate::from_string(&block_config.format)
                 .block_error("docker", "Invalid format specified")?,
             update_interval: block_config.interval,
+            name_filter: match block_config.name_filter {
+                None => None,
+                Some(regex_str) => Some(Regex::new(&regex_str).map_err(|e| {
+                    ConfigurationError(
+                        "docker".to_string(),
+                        format!("invalid name_filter regex: {}", e),
+                    )
+                })?),
+            },
+            label_filter: block_config
+                .label_filter
+                .iter()
+                .map(|filter| LabelFilter::parse(filter))
+                .collect(),
+            cpu_samples: HashMap::new(),
         })
     }
 }
 
+impl Docker {
+    fn is_filtered(&self) -> bool {
+        self.name_filter.is_some() || !self.label_filter.is_empty()
+    }
+
+    /// Whether `container` passes `name_filter` and `label_filter`. Docker prefixes the names
+    /// with a slash, which is stripped before matching.
+    fn matches(&self, container: &Container) -> bool {
+        let name_matches = match &self.name_filter {
+            Some(regex) => container
+                .names
+                .iter()
+                .any(|name| regex.is_match(name.trim_start_matches('/'))),
+            None => true,
+        };
+        name_matches && self.label_filter.iter().all(|f| f.matches(container))
+    }
+
+    /// Lists the containers passing the filters
+    fn matching_containers(&self) -> Result<Vec<Container>> {
+        let output =
+            http::http_get_socket_json(SOCKET_PATH.into(), "http:/api/containers/json?all=1")
+                .block_error("docker", "Failed to list the containers.")?;
+        let containers: Vec<Container> = serde_json::from_value(output.content)
+            .block_error("docker", "Failed to parse JSON response.")?;
+        Ok(containers.into_iter().filter(|c| self.matches(c)).collect())
+    }
+
+    fn shows_stats(&self) -> bool {
+        self.format.contains("cpu") || self.format.contains("mem")
+    }
+
+    /// The summed CPU (in percents) and memory (in bytes) usage of the running `containers`.
+    /// The CPU usage is measured since the last update, so containers only count once they
+    /// have been running for a poll. Containers which stopped in the meantime are skipped.
+    fn stats(&mut self, containers: &[Container]) -> (f64, u64) {
+        let mut cpu = 0.;
+        let mut mem = 0;
+        let mut samples = HashMap::new();
+        for container in containers.iter().filter(|c| c.state == "running") {
+            let url = format!(
+                "http:/api/containers/{}/stats?stream=false&one-shot=true",
+                container.id
+            );
+            let stats: ContainerStats = match http::http_get_socket_json(SOCKET_PATH.into(), &url) {
+                Ok(output) if output.status == 200 => {
+                    match serde_json::from_value(output.content) {
+                        Ok(stats) => stats,
+                        Err(_) => continue,
+                    }
+                }
+                _ => continue,
+            };
+            mem += stats.memory_stats.used().unwrap_or(0);
+            if let Some(sample) = CpuSample::of(&stats.cpu_stats) {
+                if let Some(previous) = self.cpu_samples.get(&container.id) {
+                    cpu += sample.percents_since(previous);
+                }
+                samples.insert(container.id.clone(), sample);
+            }
+        }
+        self.cpu_samples = samples;
+        (cpu, mem)
+    }
+}
+
 impl Block for Docker {
     fn update(&mut self) -> Result<Option<Update>> {
-        let socket_path = std::path::PathBuf::from("/var/run/docker.sock");
-        let output = http::http_get_socket_json(socket_path, "http:/api/info");
+        let output = http::http_get_socket_json(SOCKET_PATH.into(), "http:/api/info");
 
+        // The daemon is not running, or the socket is not accessible
         if output.is_err() {
             self.text.set_text("N/A".to_string());
+            self.text.set_state(State::Warning);
             return Ok(Some(self.update_interval.into()));
         }
 
         let status: Status = serde_json::from_value(output.unwrap().content)
             .block_error("docker", "Failed to parse JSON response.")?;
 
+        // `docker info` has no count of the exited containers and no stats, so they need the
+        // container list as well
+        let shows_stats = self.shows_stats();
+        let mut usage = (0., 0);
+        let (counts, stopped) =
+            if self.is_filtered() || self.format.contains("exited") || shows_stats {
+                let containers = self.matching_containers()?;
+                if shows_stats {
+                    usage = self.stats(&containers);
+                }
+                let counts = Counts::of(&containers.iter().collect::<Vec<_>>());
+                let stopped = counts.stopped();
+                (counts, stopped)
+            } else {
+                let counts = Counts {
+                    total: status.total,
+                    running: status.running,
+                    paused: status.paused,
+                    exited: 0,
+                };
+                (counts, status.stopped)
+            };
+
         let values = map!(
-            "total" =>   Value::from_integer(status.total),
-            "running" => Value::from_integer(status.running),
-            "paused" =>  Value::from_integer(status.paused),
-            "stopped" => Value::from_integer(status.stopped),
+            "total" =>   Value::from_integer(counts.total),
+            "running" => Value::from_integer(counts.running),
+            "paused" =>  Value::from_integer(counts.paused),
+            "stopped" => Value::from_integer(stopped),
+            "exited" =>  Value::from_integer(counts.exited),
             "images" =>  Value::from_integer(status.images),
+            "cpu" =>     Value::from_float(usage.0).percents(),
+            "mem" =>     Value::from_float(usage.1 as f64).bytes(),
         );
 
         self.text.set_text(self.format.render(&values)?);
+        self.text.set_state(State::Idle);
 
         Ok(Some(self.update_interval.into()))
     }
@@ -115,3 +388,101 @@ impl Block for Docker {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn container(name: &str, state: &str, labels: &[(&str, &str)]) -> Container {
+        Container {
+            id: name.to_string(),
+            names: vec![format!("/{}", name)],
+            state: state.to_string(),
+            labels: Some(
+                labels
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+
+    #[test]
+    fn filter_containers() {
+        let containers = [
+            container(
+                "shop_web_1",
+                "running",
+                &[("com.docker.compose.project", "shop")],
+            ),
+            container(
+                "shop_db_1",
+                "exited",
+                &[("com.docker.compose.project", "shop")],
+            ),
+            container("blog", "paused", &[("com.docker.compose.project", "blog")]),
+            container("scratch", "created", &[]),
+        ];
+        let counts = |docker: &Docker| {
+            let matching: Vec<&Container> =
+                containers.iter().filter(|c| docker.matches(c)).collect();
+            Counts::of(&matching)
+        };
+        let mut docker = Docker {
+            id: 0,
+            text: TextWidget::new(0, 0, SharedConfig::default()),
+            format: FormatTemplate::from_string("{running}").unwrap(),
+            update_interval: Duration::from_secs(5),
+            name_filter: None,
+            label_filter: Vec::new(),
+            cpu_samples: HashMap::new(),
+        };
+        let all = counts(&docker);
+        assert_eq!(
+            (all.total, all.running, all.paused, all.exited),
+            (4, 1, 1, 1)
+        );
+        assert_eq!(all.stopped(), 2);
+
+        docker.name_filter = Some(Regex::new("^shop_").unwrap());
+        assert_eq!(counts(&docker).total, 2);
+
+        docker.name_filter = None;
+        docker.label_filter = vec![LabelFilter::parse("com.docker.compose.project=shop")];
+        let shop = counts(&docker);
+        assert_eq!((shop.total, shop.running, shop.exited), (2, 1, 1));
+
+        docker.label_filter = vec![LabelFilter::parse("com.docker.compose.project")];
+        assert_eq!(counts(&docker).total, 3);
+    }
+
+    #[test]
+    fn container_stats() {
+        let stats: ContainerStats = serde_json::from_str(
+            r#"{
+                "cpu_stats": {
+                    "cpu_usage": {"total_usage": 3000000000},
+                    "system_cpu_usage": 120000000000,
+                    "online_cpus": 4
+                },
+                "memory_stats": {"usage": 52428800, "stats": {"inactive_file": 10485760}}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(stats.memory_stats.used(), Some(41943040));
+
+        let sample = CpuSample::of(&stats.cpu_stats).unwrap();
+        let previous = CpuSample {
+            container: 1000000000,
+            system: 100000000000,
+            online_cpus: 4,
+        };
+        // 2s of the 20s the 4 CPUs ran for in total, i.e. 40% of one CPU
+        assert!((sample.percents_since(&previous) - 40.).abs() < 1e-9);
+        assert_eq!(sample.percents_since(&sample), 0.);
+
+        let empty: ContainerStats = serde_json::from_str("{}").unwrap();
+        assert_eq!(empty.memory_stats.used(), None);
+        assert_eq!(CpuSample::of(&empty.cpu_stats), None);
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
@@ -9,9 +9,10 @@ use swayipc::{Connection, EventType};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::scheduler::Task;
-use crate::widgets::text::TextWidget;
+use crate::widgets::rotatingtext::RotatingTextWidget;
 use crate::widgets::I3BarWidget;
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -24,11 +25,11 @@ pub enum MarksType {
 
 pub struct FocusedWindow {
     id: usize,
-    text: TextWidget,
+    text: RotatingTextWidget,
     title: Arc<Mutex<String>>,
     marks: Arc<Mutex<String>>,
     show_marks: MarksType,
-    max_width: usize,
+    marquee: bool,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -39,6 +40,20 @@ pub struct FocusedWindowConfig {
 
     /// Show marks in place of title (if exist)
     pub show_marks: MarksType,
+
+    /// Scroll titles longer than max-width instead of truncating them
+    pub marquee: bool,
+
+    /// Delay between two rotations, in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub marquee_interval: Duration,
+
+    /// Scrolling time per character, in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub marquee_speed: Duration,
+
+    /// Text shown between the end and the start of the title while it rotates
+    pub marquee_gap: String,
 }
 
 impl Default for FocusedWindowConfig {
@@ -46,6 +61,10 @@ impl Default for FocusedWindowConfig {
         Self {
             max_width: 21,
             show_marks: MarksType::None,
+            marquee: false,
+            marquee_interval: Duration::from_secs(10),
+            marquee_speed: Duration::from_millis(500),
+            marquee_gap: "|".to_string(),
         }
     }
 }
@@ -176,12 +195,21 @@ impl ConfigBlock for FocusedWindow {
             })
             .expect("failed to start watching thread for `window` block");
 
-        let text = TextWidget::new(id, 0, shared_config);
+        let text = RotatingTextWidget::new(
+            id,
+            0,
+            block_config.marquee_interval,
+            block_config.marquee_speed,
+            block_config.max_width,
+            true,
+            shared_config,
+        )
+        .with_gap(&block_config.marquee_gap);
         Ok(FocusedWindow {
             id,
             text,
-            max_width: block_config.max_width,
             show_marks: block_config.show_marks,
+            marquee: block_config.marquee,
             title,
             marks,
         })
@@ -190,18 +218,16 @@ impl ConfigBlock for FocusedWindow {
 
 impl Block for FocusedWindow {
     fn update(&mut self) -> Result<Option<Update>> {
-        let mut marks_string = (*self
+        let marks_string = (*self
             .marks
             .lock()
             .block_error("focused_window", "failed to acquire lock")?)
         .clone();
-        marks_string = marks_string.chars().take(self.max_width).collect();
-        let mut title_string = (*self
+        let title_string = (*self
             .title
             .lock()
             .block_error("focused_window", "failed to acquire lock")?)
         .clone();
-        title_string = title_string.chars().take(self.max_width).collect();
         let out_str = match self.show_marks {
             MarksType::None => title_string,
             _ => {
@@ -212,8 +238,14 @@ impl Block for FocusedWindow {
                 }
             }
         };
+        // The widget truncates (or rotates) the text to max_width and escapes it
         self.text.set_text(out_str);
 
+        if self.marquee {
+            let (_, next_rotation) = self.text.next()?;
+            return Ok(next_rotation.map(Update::Every));
+        }
+
         Ok(None)
     }
 
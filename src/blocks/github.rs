@@ -1,33 +1,77 @@
 use std::collections::HashMap;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
+use log::{info, warn};
 use regex::Regex;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_opt_expanded};
 use crate::errors::*;
 use crate::formatting::value::Value;
-use crate::formatting::FormatTemplate;
-use crate::http;
+use crate::formatting::FormatPair;
+use crate::http::{self, HttpResult};
+use crate::protocol::i3bar_event::{I3BarEvent, Modifier, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
 use crate::widgets::text::TextWidget;
-use crate::widgets::I3BarWidget;
+use crate::widgets::{I3BarWidget, State};
 
 const GITHUB_TOKEN_ENV: &str = "I3RS_GITHUB_TOKEN";
 
+/// Delay before the first retry of a failed request, doubled on every further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper limit of `retries`, the last retry waits over four minutes
+const MAX_RETRIES: u32 = 10;
+
+/// Whether a request that failed with `error` is retried. Only network errors and timeouts are,
+/// not unexpected responses.
+fn is_retried(error: &HttpError) -> bool {
+    matches!(error, HttpError::Timeout | HttpError::Request(_))
+}
+
+/// The delay before the retry following the `attempt`th one (counting from 0)
+fn retry_delay(attempt: u32) -> Duration {
+    RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt))
+}
+
+/// Notification reasons that have a dedicated `{<reason>_icon}` placeholder
+const REASONS_WITH_ICONS: &[&str] = &["mention", "review_requested", "security_alert"];
+
 pub struct Github {
     id: usize,
     text: TextWidget,
     update_interval: Duration,
-    api_server: String,
-    token: String,
-    format: FormatTemplate,
+    request: FetchRequest,
+    fetched: Arc<Mutex<Option<Fetched>>>,
+    fetching: bool,
+    update_request: Sender<Task>,
+    format: FormatPair,
+    format_alt: Option<FormatPair>,
+    /// Whether `format_alt` is shown instead of `format`, toggled by left-clicking
+    show_alt: bool,
+    /// Whether the block shows counts, rather than the `x` shown before the first fetch and
+    /// after errors
+    has_counts: bool,
     total_notifications: u64,
     hide_if_total_is_zero: bool,
+    on_click_url: String,
+    on_ctrl_click_url: String,
+    warning_count: Option<u64>,
+    critical_count: Option<u64>,
+    validators: CacheValidators,
+    reason_icons: HashMap<String, String>,
+    aggregations: HashMap<String, u64>,
+    rate_limit: Option<RateLimit>,
+    format_auth_error: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -39,10 +83,56 @@ pub struct GithubConfig {
 
     pub api_server: String,
 
+    /// Timeout for each API request
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Duration,
+
+    /// Number of times a failed request is retried, with exponential backoff
+    pub retries: u32,
+
     /// Format override
     pub format: String,
 
+    /// Shorter format the bar may switch to when it runs out of space
+    pub format_short: Option<String>,
+
+    /// Format shown instead of `format` after a left click, until the next one
+    pub format_alt: Option<String>,
+
+    /// Text shown when GitHub rejects the token
+    pub format_auth_error: String,
+
     pub hide_if_total_is_zero: bool,
+
+    /// Only count notifications from these repositories (`owner/name`)
+    pub repos: Vec<String>,
+
+    /// Ignore notifications from these repositories (`owner/name`)
+    pub exclude_repos: Vec<String>,
+
+    /// URL to open when the block is clicked. Derived from `api_server` if not set
+    pub on_click_url: Option<String>,
+
+    /// URL to open when the block is clicked while holding Ctrl. The pull requests page derived
+    /// from `api_server` if not set
+    pub on_ctrl_click_url: Option<String>,
+
+    /// Set the block state to Warning once the total reaches this count
+    pub warning_count: Option<u64>,
+
+    /// Set the block state to Critical once the total reaches this count
+    pub critical_count: Option<u64>,
+
+    /// Read the token from this file instead of the environment
+    #[serde(deserialize_with = "deserialize_opt_expanded")]
+    pub token_file: Option<PathBuf>,
+
+    /// Run this command and use its output as the token instead of the environment
+    pub token_command: Option<String>,
+
+    /// CA certificate file (or directory) to trust in addition to the system's certificates
+    #[serde(deserialize_with = "deserialize_opt_expanded")]
+    pub tls_ca: Option<PathBuf>,
 }
 
 impl Default for GithubConfig {
@@ -50,8 +140,22 @@ impl Default for GithubConfig {
         Self {
             interval: Duration::from_secs(30),
             api_server: "https://api.github.com".to_string(),
+            timeout: Duration::from_secs(3),
+            retries: 0,
             format: "{total}".to_string(),
+            format_short: None,
+            format_alt: None,
+            format_auth_error: "auth".to_string(),
             hide_if_total_is_zero: false,
+            repos: Vec::new(),
+            exclude_repos: Vec::new(),
+            on_click_url: None,
+            on_ctrl_click_url: None,
+            warning_count: None,
+            critical_count: None,
+            token_file: None,
+            token_command: None,
+            tls_ca: None,
         }
     }
 }
@@ -63,10 +167,72 @@ impl ConfigBlock for Github {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _: Sender<Task>,
+        update_request: Sender<Task>,
     ) -> Result<Self> {
-        let token = std::env::var(GITHUB_TOKEN_ENV)
-            .block_error("github", "missing I3RS_GITHUB_TOKEN environment variable")?;
+        let token = match (&block_config.token_file, &block_config.token_command) {
+            (Some(_), Some(_)) => {
+                return Err(ConfigurationError(
+                    "github".to_string(),
+                    "only one of `token_file` and `token_command` can be set".to_string(),
+                ))
+            }
+            (Some(path), None) => std::fs::read_to_string(path)
+                .block_error(
+                    "github",
+                    &format!("failed to read token file {}", path.to_string_lossy()),
+                )?
+                .trim_end()
+                .to_string(),
+            (None, Some(command)) => {
+                let output = Command::new("sh")
+                    .args(["-c", command])
+                    .output()
+                    .block_error("github", "failed to run token_command")?;
+                if !output.status.success() {
+                    return Err(BlockError(
+                        "github".to_string(),
+                        format!("token_command exited with {}", output.status),
+                    ));
+                }
+                String::from_utf8_lossy(&output.stdout)
+                    .trim_end()
+                    .to_string()
+            }
+            (None, None) => std::env::var(GITHUB_TOKEN_ENV)
+                .block_error("github", "missing I3RS_GITHUB_TOKEN environment variable")?,
+        };
+
+        let api_server = block_config.api_server;
+        let on_click_url = block_config
+            .on_click_url
+            .unwrap_or_else(|| web_url(&api_server, "notifications"));
+        let on_ctrl_click_url = block_config
+            .on_ctrl_click_url
+            .unwrap_or_else(|| web_url(&api_server, "pulls"));
+
+        // Use `github_<reason>` icons if the icon set has them, the generic icon otherwise
+        let reason_icons = REASONS_WITH_ICONS
+            .iter()
+            .map(|reason| {
+                let name = format!("github_{}", reason);
+                let icon = if shared_config.has_icon(&name) {
+                    shared_config.get_icon(&name)?
+                } else {
+                    shared_config.get_icon("github")?
+                };
+                Ok((format!("{}_icon", reason), icon))
+            })
+            .collect::<Result<_>>()?;
+
+        let format_short = block_config.format_short.as_deref();
+        let format = FormatPair::new(&block_config.format, format_short)
+            .block_error("github", "Invalid format specified")?;
+        let format_alt = block_config
+            .format_alt
+            .as_deref()
+            .map(|format| FormatPair::new(format, format_short))
+            .transpose()
+            .block_error("github", "Invalid format_alt specified")?;
 
         let text = TextWidget::new(id, 0, shared_config)
             .with_text("x")
@@ -75,40 +241,83 @@ impl ConfigBlock for Github {
             id,
             update_interval: block_config.interval,
             text,
-            api_server: block_config.api_server,
-            token,
-            format: FormatTemplate::from_string(&block_config.format)
-                .block_error("github", "Invalid format specified")?,
+            request: FetchRequest {
+                api_server,
+                token,
+                timeout: block_config.timeout,
+                retries: block_config.retries.min(MAX_RETRIES),
+                tls_ca: block_config.tls_ca,
+                repos: block_config.repos,
+                exclude_repos: block_config.exclude_repos,
+            },
+            fetched: Arc::new(Mutex::new(None)),
+            fetching: false,
+            update_request,
+            format,
+            format_alt,
+            show_alt: false,
+            has_counts: false,
             total_notifications: 0,
             hide_if_total_is_zero: block_config.hide_if_total_is_zero,
+            on_click_url,
+            on_ctrl_click_url,
+            warning_count: block_config.warning_count,
+            critical_count: block_config.critical_count,
+            validators: CacheValidators::default(),
+            reason_icons,
+            aggregations: HashMap::new(),
+            rate_limit: None,
+            format_auth_error: block_config.format_auth_error,
         })
     }
 }
 
-impl Block for Github {
-    fn update(&mut self) -> Result<Option<Update>> {
-        let aggregations = match Notifications::new(&self.api_server, &self.token).try_fold(
-            map!("total".to_owned() => 0),
-            |mut acc,
-             notif|
-             -> std::result::Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
-                let n = notif?;
-                acc.entry(n.reason).and_modify(|v| *v += 1).or_insert(1);
-                acc.entry("total".to_owned()).and_modify(|v| *v += 1);
-                Ok(acc)
-            },
-        ) {
-            Ok(v) => v,
-            Err(_) => {
-                // If there is a error reported, set the value to x
-                self.text.set_text("x".to_owned());
-                return Ok(Some(self.update_interval.into()));
-            }
-        };
+impl Github {
+    /// Fetches the notifications on a worker thread, which requests an update of the block
+    /// once the result is available, so slow responses don't hold up the other blocks
+    fn start_fetch(&mut self) -> Result<()> {
+        let request = self.request.clone();
+        let validators = self.validators.clone();
+        let fetched = self.fetched.clone();
+        let update_request = self.update_request.clone();
+        let id = self.id;
+
+        thread::Builder::new()
+            .name("github".into())
+            .spawn(move || {
+                let result = request.fetch(validators);
+                *fetched
+                    .lock()
+                    .expect("lock has been poisoned in `github` block") = Some(result);
+                // The bar is gone if nobody receives the request anymore
+                let _ = update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .block_error("github", "failed to spawn the fetching thread")?;
+
+        self.fetching = true;
+        Ok(())
+    }
 
+    fn state(&self) -> State {
+        let reached = |threshold| matches!(threshold, Some(t) if self.total_notifications >= t);
+        if reached(self.critical_count) {
+            State::Critical
+        } else if reached(self.warning_count) {
+            State::Warning
+        } else {
+            State::Idle
+        }
+    }
+
+    /// Shows the last fetched counts with the current format
+    fn render(&mut self) -> Result<()> {
+        let aggregations = &self.aggregations;
         let default: u64 = 0;
         self.total_notifications = *aggregations.get("total").unwrap_or(&default);
-        let values = map!(
+        let mut values = map!(
             "total" => Value::from_integer(self.total_notifications as i64),
             // As specified by:
             // https://developer.github.com/v3/activity/notifications/#notification-reasons
@@ -124,8 +333,88 @@ impl Block for Github {
             "subscribed" =>       Value::from_integer(*aggregations.get("subscribed").unwrap_or(&default) as i64),
             "team_mention" =>     Value::from_integer(*aggregations.get("team_mention").unwrap_or(&default) as i64),
         );
+        for (key, icon) in &self.reason_icons {
+            values.insert(key, Value::from_string(icon.clone()));
+        }
+        match self.rate_limit {
+            Some(ref rate_limit) => {
+                values.insert(
+                    "remaining",
+                    Value::from_integer(rate_limit.remaining as i64),
+                );
+                values.insert("reset", Value::from_duration(rate_limit.reset_in()));
+            }
+            None => {
+                values.insert("remaining", Value::from_string("?".to_string()));
+                values.insert("reset", Value::from_string("?".to_string()));
+            }
+        }
+
+        self.text.set_state(self.state());
+        let format = match &self.format_alt {
+            Some(format_alt) if self.show_alt => format_alt,
+            _ => &self.format,
+        };
+        self.text.set_texts(format.render(&values)?);
+        Ok(())
+    }
+}
+
+impl Block for Github {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let fetched = self
+            .fetched
+            .lock()
+            .block_error("github", "failed to acquire lock")?
+            .take();
+        let fetched = match fetched {
+            Some(fetched) => fetched,
+            None => {
+                if !self.fetching {
+                    self.start_fetch()?;
+                }
+                // The fetching thread requests an update to show the result, which doesn't
+                // reschedule the block: keep the cadence here
+                return Ok(Some(self.update_interval.into()));
+            }
+        };
+        self.fetching = false;
+
+        match fetched {
+            Fetched::Notifications {
+                aggregations,
+                not_modified,
+                validators,
+                rate_limit,
+            } => {
+                if rate_limit.is_some() {
+                    self.rate_limit = rate_limit;
+                }
+                // If nothing changed since the last update, keep the previous counts
+                if !not_modified {
+                    self.validators = validators;
+                    self.aggregations = aggregations;
+                }
+            }
+            fetched => {
+                if let Fetched::AuthError = fetched {
+                    // Retrying won't help until the user fixes the token
+                    self.text.set_state(State::Critical);
+                    self.text.set_text(self.format_auth_error.clone());
+                } else {
+                    // If there is a error reported, set the value to x
+                    self.text.set_state(State::Idle);
+                    self.text.set_text("x".to_owned());
+                }
+                self.has_counts = false;
+                // Make sure the next successful request is not answered with a 304
+                self.validators = CacheValidators::default();
+                return Ok(Some(self.update_interval.into()));
+            }
+        }
 
-        self.text.set_text(self.format.render(&values)?);
+        self.has_counts = true;
+        self.render()?;
 
         Ok(Some(self.update_interval.into()))
     }
@@ -138,24 +427,190 @@ impl Block for Github {
         }
     }
 
+    /// With a `format_alt`, left-clicking switches the format and right-clicking opens the
+    /// notifications page, which left-clicking does otherwise. Ctrl-clicking opens the pull
+    /// requests page.
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match (event.button, self.format_alt.is_some()) {
+            (MouseButton::Left, _) if event.has_modifier(Modifier::Control) => {
+                spawn_child_async("xdg-open", &[&self.on_ctrl_click_url])
+                    .block_error("github", "could not spawn xdg-open")?;
+            }
+            (MouseButton::Left, true) => {
+                self.show_alt = !self.show_alt;
+                if self.has_counts {
+                    self.render()?;
+                }
+            }
+            (MouseButton::Left, false) | (MouseButton::Right, true) => {
+                spawn_child_async("xdg-open", &[&self.on_click_url])
+                    .block_error("github", "could not spawn xdg-open")?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn refresh_on_middle_click(&self) -> bool {
+        true
+    }
+
     fn id(&self) -> usize {
         self.id
     }
 }
 
+/// Derives a web page (such as `notifications`) from the API server URL.
+///
+/// `https://api.github.com` maps to `https://github.com/<page>`, while Enterprise
+/// servers (`https://<host>/api/v3`) map to `https://<host>/<page>`.
+fn web_url(api_server: &str, page: &str) -> String {
+    let base = api_server.trim_end_matches('/');
+    let base = base.trim_end_matches("/api/v3");
+    let base = base.replacen("://api.github.com", "://github.com", 1);
+    format!("{}/{}", base, page)
+}
+
+/// Everything needed to fetch the notifications, moved to the fetching thread
+#[derive(Clone)]
+struct FetchRequest {
+    api_server: String,
+    token: String,
+    timeout: Duration,
+    retries: u32,
+    tls_ca: Option<PathBuf>,
+    repos: Vec<String>,
+    exclude_repos: Vec<String>,
+}
+
+/// The outcome of fetching the notifications
+enum Fetched {
+    Notifications {
+        aggregations: HashMap<String, u64>,
+        not_modified: bool,
+        validators: CacheValidators,
+        rate_limit: Option<RateLimit>,
+    },
+    AuthError,
+    Error,
+}
+
+impl FetchRequest {
+    fn is_repo_watched(&self, full_name: &str) -> bool {
+        (self.repos.is_empty() || self.repos.iter().any(|r| r == full_name))
+            && !self.exclude_repos.iter().any(|r| r == full_name)
+    }
+
+    fn fetch(&self, validators: CacheValidators) -> Fetched {
+        let mut notifications = Notifications::new(
+            &self.api_server,
+            &self.token,
+            self.timeout,
+            self.retries,
+            self.tls_ca.as_deref(),
+            validators,
+        );
+        let aggregations = notifications.try_fold(
+            map!("total".to_owned() => 0),
+            |mut acc, notif| -> HttpResult<HashMap<String, u64>> {
+                let n = notif?;
+                if !self.is_repo_watched(&n.repository.full_name) {
+                    return Ok(acc);
+                }
+                acc.entry(n.reason).and_modify(|v| *v += 1).or_insert(1);
+                acc.entry("total".to_owned()).and_modify(|v| *v += 1);
+                Ok(acc)
+            },
+        );
+        match aggregations {
+            Ok(aggregations) => Fetched::Notifications {
+                aggregations,
+                not_modified: notifications.not_modified,
+                validators: notifications.validators,
+                rate_limit: notifications.rate_limit,
+            },
+            // GitHub also answers with 403 once the rate limit is exhausted
+            Err(HttpError::Status(401)) => {
+                warn!("github: the token was rejected (HTTP status 401)");
+                Fetched::AuthError
+            }
+            Err(HttpError::Status(403))
+                if !matches!(
+                    notifications.rate_limit,
+                    Some(RateLimit { remaining: 0, .. })
+                ) =>
+            {
+                warn!("github: the token was rejected (HTTP status 403)");
+                Fetched::AuthError
+            }
+            Err(e) => {
+                warn!("github: failed to fetch the notifications: {}", e);
+                Fetched::Error
+            }
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct Notification {
     reason: String,
+    repository: Repository,
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    full_name: String,
+}
+
+/// `ETag` and `Last-Modified` values of the first notifications page, sent back with the next
+/// request so that GitHub can answer with `304 Not Modified` (which does not count towards the
+/// rate limit).
+#[derive(Clone, Default)]
+struct CacheValidators {
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+/// Rate limit status reported by the `X-RateLimit-*` response headers.
+struct RateLimit {
+    remaining: u64,
+    /// Time at which the quota resets, in seconds since the epoch
+    reset: u64,
+}
+
+impl RateLimit {
+    fn from_response<T>(response: &http::HttpResponse<T>) -> Option<Self> {
+        Some(RateLimit {
+            remaining: response.header("X-RateLimit-Remaining")?.parse().ok()?,
+            reset: response.header("X-RateLimit-Reset")?.parse().ok()?,
+        })
+    }
+
+    /// Time left until the quota resets
+    fn reset_in(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Duration::from_secs(self.reset.saturating_sub(now))
+    }
 }
 
 struct Notifications<'a> {
     notifications: <Vec<Notification> as IntoIterator>::IntoIter,
     token: &'a str,
+    timeout: Duration,
+    retries: u32,
+    tls_ca: Option<&'a Path>,
     next_page_url: String,
+    first_page: bool,
+    validators: CacheValidators,
+    not_modified: bool,
+    rate_limit: Option<RateLimit>,
 }
 
 impl<'a> Iterator for Notifications<'a> {
-    type Item = std::result::Result<Notification, Box<dyn std::error::Error>>;
+    type Item = HttpResult<Notification>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.try_next() {
@@ -167,17 +622,29 @@ impl<'a> Iterator for Notifications<'a> {
 }
 
 impl<'a> Notifications<'a> {
-    fn new(api_server: &'a str, token: &'a str) -> Notifications<'a> {
+    fn new(
+        api_server: &'a str,
+        token: &'a str,
+        timeout: Duration,
+        retries: u32,
+        tls_ca: Option<&'a Path>,
+        validators: CacheValidators,
+    ) -> Notifications<'a> {
         Notifications {
             next_page_url: format!("{}/notifications", api_server),
             token,
+            timeout,
+            retries,
+            tls_ca,
             notifications: vec![].into_iter(),
+            first_page: true,
+            validators,
+            not_modified: false,
+            rate_limit: None,
         }
     }
 
-    fn try_next(
-        &mut self,
-    ) -> std::result::Result<Option<Notification>, Box<dyn std::error::Error>> {
+    fn try_next(&mut self) -> HttpResult<Option<Notification>> {
         if let Some(notif) = self.notifications.next() {
             return Ok(Some(notif));
         }
@@ -186,10 +653,73 @@ impl<'a> Notifications<'a> {
             return Ok(None);
         }
 
-        let header_value = format!("Bearer {}", self.token);
-        let headers = vec![("Authorization", header_value.as_str())];
-        let result =
-            http::http_get_json(&self.next_page_url, Some(Duration::from_secs(3)), headers)?;
+        let authorization = format!("Bearer {}", self.token);
+        let headers = self.request_headers(&authorization);
+        // Only transport errors are retried: HTTP errors such as 401 are successful responses,
+        // and a body that isn't JSON won't become one when sent again
+        let mut attempt = 0;
+        let result = loop {
+            match http::http_request_json(
+                "GET",
+                &self.next_page_url,
+                None,
+                Some(self.timeout),
+                headers.clone(),
+                None,
+                self.tls_ca,
+            ) {
+                Err(e) if attempt < self.retries && is_retried(&e) => {
+                    info!("github: retrying the request after error: {}", e);
+                    std::thread::sleep(retry_delay(attempt));
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+
+        self.handle_response(result)
+    }
+
+    /// The headers of the request for the next page, the validators of the last fetch are only
+    /// sent for the first one
+    fn request_headers<'b>(&'b self, authorization: &'b str) -> Vec<(&'static str, &'b str)> {
+        let mut headers = vec![("Authorization", authorization)];
+        if self.first_page {
+            if let Some(ref etag) = self.validators.etag {
+                headers.push(("If-None-Match", etag));
+            }
+            if let Some(ref last_modified) = self.validators.last_modified {
+                headers.push(("If-Modified-Since", last_modified));
+            }
+        }
+        headers
+    }
+
+    /// Takes the response for the next page, returns its first notification
+    fn handle_response(
+        &mut self,
+        result: http::HttpResponse<serde_json::Value>,
+    ) -> HttpResult<Option<Notification>> {
+        if let Some(rate_limit) = RateLimit::from_response(&result) {
+            self.rate_limit = Some(rate_limit);
+        }
+
+        if result.status >= 400 {
+            return Err(HttpError::Status(result.status));
+        }
+
+        if self.first_page {
+            self.first_page = false;
+            if result.status == 304 {
+                self.not_modified = true;
+                self.next_page_url.clear();
+                return Ok(None);
+            }
+            self.validators = CacheValidators {
+                etag: result.header("ETag").map(str::to_owned),
+                last_modified: result.header("Last-Modified").map(str::to_owned),
+            };
+        }
 
         self.next_page_url = result
             .headers
@@ -204,7 +734,8 @@ impl<'a> Notifications<'a> {
             .unwrap_or(&"")
             .to_string();
 
-        let notifications: Vec<Notification> = serde_json::from_value(result.content)?;
+        let notifications: Vec<Notification> =
+            serde_json::from_value(result.content).map_err(|e| HttpError::Parse(e.to_string()))?;
         self.notifications = notifications.into_iter();
 
         Ok(self.notifications.next())
@@ -235,6 +766,125 @@ fn parse_links_header(raw_links: &str) -> HashMap<&str, &str> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn retried_errors() {
+        assert!(is_retried(&HttpError::Timeout));
+        assert!(is_retried(&HttpError::Request(
+            "Couldn't resolve host name".to_string()
+        )));
+        assert!(!is_retried(&HttpError::Parse(
+            "expected value at line 1 column 1".to_string()
+        )));
+        assert!(!is_retried(&HttpError::Status(502)));
+
+        assert_eq!(retry_delay(0), Duration::from_millis(500));
+        assert_eq!(retry_delay(3), Duration::from_secs(4));
+        assert_eq!(retry_delay(MAX_RETRIES - 1), Duration::from_secs(256));
+        // Doesn't overflow even for configs that weren't clamped
+        assert!(retry_delay(40) >= retry_delay(31));
+    }
+
+    fn response(
+        status: u16,
+        headers: &[&str],
+        content: serde_json::Value,
+    ) -> http::HttpResponse<serde_json::Value> {
+        http::HttpResponse {
+            status,
+            content,
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+        }
+    }
+
+    fn notifications(validators: CacheValidators) -> Notifications<'static> {
+        Notifications::new(
+            "https://api.github.com",
+            "token",
+            Duration::from_secs(3),
+            0,
+            None,
+            validators,
+        )
+    }
+
+    #[test]
+    fn validators_are_sent_with_the_next_request() {
+        let mut first = notifications(CacheValidators::default());
+        assert_eq!(
+            first.request_headers("Bearer token"),
+            [("Authorization", "Bearer token")]
+        );
+        let notification = first
+            .handle_response(response(
+                200,
+                &[
+                    "ETag: W/\"1234\"",
+                    "Last-Modified: Thu, 01 Oct 2020 10:00:00 GMT",
+                ],
+                serde_json::json!([
+                    {"reason": "mention", "repository": {"full_name": "a/b"}}
+                ]),
+            ))
+            .unwrap();
+        assert_eq!(notification.unwrap().reason, "mention");
+        assert!(!first.not_modified);
+
+        let next = notifications(first.validators);
+        assert_eq!(
+            next.request_headers("Bearer token"),
+            [
+                ("Authorization", "Bearer token"),
+                ("If-None-Match", "W/\"1234\""),
+                ("If-Modified-Since", "Thu, 01 Oct 2020 10:00:00 GMT"),
+            ]
+        );
+    }
+
+    #[test]
+    fn not_modified_keeps_the_previous_count() {
+        let mut request = notifications(CacheValidators {
+            etag: Some("\"1234\"".to_string()),
+            last_modified: None,
+        });
+        let notification = request
+            .handle_response(response(304, &[], serde_json::Value::Null))
+            .unwrap();
+        assert!(notification.is_none());
+        assert!(request.not_modified);
+        assert!(request.next().is_none());
+
+        let mut github = Github::new(
+            0,
+            GithubConfig {
+                token_command: Some("echo token".to_string()),
+                ..GithubConfig::default()
+            },
+            SharedConfig::default(),
+            crossbeam_channel::unbounded().0,
+        )
+        .unwrap();
+        let fetched = |not_modified, total, etag: &str| Fetched::Notifications {
+            aggregations: map!("total".to_owned() => total),
+            not_modified,
+            validators: CacheValidators {
+                etag: Some(etag.to_string()),
+                last_modified: None,
+            },
+            rate_limit: None,
+        };
+
+        *github.fetched.lock().unwrap() = Some(fetched(false, 3, "\"1234\""));
+        github.update().unwrap();
+        assert_eq!(github.total_notifications, 3);
+
+        // The aggregations of a 304 are empty, the block keeps its count and validators
+        *github.fetched.lock().unwrap() = Some(fetched(true, 0, "\"5678\""));
+        github.update().unwrap();
+        assert_eq!(github.total_notifications, 3);
+        assert!(github.text.get_data().full_text.contains('3'));
+        assert_eq!(github.validators.etag.as_deref(), Some("\"1234\""));
+    }
+
     #[test]
     fn it_parses_links_header() {
         assert_eq!(
@@ -249,4 +899,20 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn it_derives_web_urls() {
+        assert_eq!(
+            web_url("https://api.github.com", "notifications"),
+            "https://github.com/notifications"
+        );
+        assert_eq!(
+            web_url("https://github.example.com/api/v3/", "notifications"),
+            "https://github.example.com/notifications"
+        );
+        assert_eq!(
+            web_url("https://api.github.com", "pulls"),
+            "https://github.com/pulls"
+        );
+    }
 }
@@ -21,6 +21,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
@@ -48,6 +49,18 @@ pub trait KeyboardLayoutMonitor {
     /// Monitor layout changes and send updates via the `update_request`
     /// channel. By default, this method does nothing.
     fn monitor(&self, _id: usize, _update_request: Sender<Task>) {}
+
+    /// Switch to `layout`, with `variant` if set.
+    fn set_layout(&self, layout: &str, variant: Option<&str>) -> Result<()>;
+
+    /// Switch to the next of the layouts the backend itself knows about, for
+    /// the drivers that have such a list. By default, this is an error.
+    fn next_layout(&self) -> Result<()> {
+        Err(BlockError(
+            "keyboard_layout".to_string(),
+            "this driver can only cycle through the `layouts` of the block".to_string(),
+        ))
+    }
 }
 
 pub struct SetXkbMap;
@@ -59,45 +72,75 @@ impl SetXkbMap {
     }
 }
 
-fn setxkbmap_layouts() -> Result<String> {
-    let output = Command::new("setxkbmap")
+fn setxkbmap_query() -> Result<String> {
+    Command::new("setxkbmap")
         .args(&["-query"])
         .output()
         .block_error("keyboard_layout", "Failed to execute setxkbmap.")
         .and_then(|raw| {
             String::from_utf8(raw.stdout).block_error("keyboard_layout", "Non-UTF8 input.")
-        })?;
+        })
+}
 
-    // Find the "layout:    xxxx" entry.
-    let layout = output
+/// The value of the `key:    xxxx` entry of the output of `setxkbmap -query`
+fn setxkbmap_entry(output: &str, key: &str) -> Option<String> {
+    output
         .split('\n')
-        .find(|line| line.starts_with("layout"))
-        .block_error(
-            "keyboard_layout",
-            "Could not find the layout entry from setxkbmap.",
-        )?
+        .find(|line| line.starts_with(key))?
         .split(char::is_whitespace)
-        .last();
+        .last()
+        .map(|s| s.to_string())
+}
 
-    layout.map(|s| s.to_string()).block_error(
+fn setxkbmap_layouts() -> Result<String> {
+    setxkbmap_entry(&setxkbmap_query()?, "layout").block_error(
         "keyboard_layout",
         "Could not read the layout entry from setxkbmap.",
     )
 }
 
+/// The variants of the layouts, "N/A" when none is set
+fn setxkbmap_variants() -> Result<String> {
+    Ok(setxkbmap_entry(&setxkbmap_query()?, "variant")
+        .filter(|variant| !variant.trim_matches(',').is_empty())
+        .unwrap_or_else(|| "N/A".to_string()))
+}
+
 impl KeyboardLayoutMonitor for SetXkbMap {
     fn keyboard_layout(&self) -> Result<String> {
         setxkbmap_layouts()
     }
 
-    // Not implemented (TODO?)
     fn keyboard_variant(&self) -> Result<String> {
-        Ok("N/A".to_string())
+        setxkbmap_variants()
     }
 
     fn must_poll(&self) -> bool {
         true
     }
+
+    fn set_layout(&self, layout: &str, variant: Option<&str>) -> Result<()> {
+        let mut args = vec!["-layout", layout];
+        if let Some(variant) = variant {
+            args.extend(&["-variant", variant]);
+        }
+        run_setxkbmap(&args)
+    }
+}
+
+fn run_setxkbmap(args: &[&str]) -> Result<()> {
+    let status = Command::new("setxkbmap")
+        .args(args)
+        .status()
+        .block_error("keyboard_layout", "Failed to execute setxkbmap.")?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(BlockError(
+            "keyboard_layout".to_string(),
+            format!("setxkbmap {} failed", args.join(" ")),
+        ))
+    }
 }
 
 pub struct LocaleBus {
@@ -132,6 +175,33 @@ impl KeyboardLayoutMonitor for LocaleBus {
         false
     }
 
+    fn set_layout(&self, layout: &str, variant: Option<&str>) -> Result<()> {
+        let model: String = self
+            .con
+            .with_path("org.freedesktop.locale1", "/org/freedesktop/locale1", 1000)
+            .get("org.freedesktop.locale1", "X11Model")
+            .unwrap_or_default();
+        let options: String = self
+            .con
+            .with_path("org.freedesktop.locale1", "/org/freedesktop/locale1", 1000)
+            .get("org.freedesktop.locale1", "X11Options")
+            .unwrap_or_default();
+        // Don't convert the layout to a console keymap, and don't ask for a password
+        let msg = Message::new_method_call(
+            "org.freedesktop.locale1",
+            "/org/freedesktop/locale1",
+            "org.freedesktop.locale1",
+            "SetX11Keyboard",
+        )
+        .block_error("locale", "Failed to build D-Bus method.")?
+        .append3(layout, model, variant.unwrap_or(""))
+        .append3(options, false, false);
+        self.con
+            .send_with_reply_and_block(msg, 5000)
+            .block_error("locale", "Failed to set the X11 keyboard layout.")?;
+        Ok(())
+    }
+
     /// Monitor Locale property changes in a separate thread and send updates
     /// via the `update_request` channel.
     // TODO: pull the new value from the PropertiesChanged message instead of making another method call
@@ -215,6 +285,25 @@ impl KbdDaemonBus {
 
         Ok(current_layout_id)
     }
+
+    fn call(method: &str, args: Option<u32>) -> Result<()> {
+        let c = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::Session)
+            .block_error("kbddaemonbus", "can't connect to dbus")?;
+        let msg = Message::new_method_call(
+            "ru.gentoo.KbddService",
+            "/ru/gentoo/KbddService",
+            "ru.gentoo.kbdd",
+            method,
+        )
+        .block_error("kbddaemonbus", "Create message failure")?;
+        let msg = match args {
+            Some(id) => msg.append1(id),
+            None => msg,
+        };
+        c.send(msg)
+            .map(|_| ())
+            .block_error("kbddaemonbus", "Is kbdd running?")
+    }
 }
 
 impl KeyboardLayoutMonitor for KbdDaemonBus {
@@ -241,14 +330,37 @@ impl KeyboardLayoutMonitor for KbdDaemonBus {
     }
 
     fn keyboard_variant(&self) -> Result<String> {
-        // Not implemented (TODO?)
-        Ok("N/A".to_string())
+        let variants = setxkbmap_variants()?;
+        let idx = *self.kbdd_layout_id.lock().unwrap();
+        Ok(variants
+            .split(',')
+            .nth(idx as usize)
+            .filter(|variant| !variant.is_empty())
+            .unwrap_or("N/A")
+            .to_string())
     }
 
     fn must_poll(&self) -> bool {
         false
     }
 
+    /// kbdd switches between the layouts set with setxkbmap, `layout` has to be one of them
+    fn set_layout(&self, layout: &str, _variant: Option<&str>) -> Result<()> {
+        let layouts = setxkbmap_layouts()?;
+        let idx = layouts
+            .split(',')
+            .position(|l| l.split(':').next() == Some(layout))
+            .block_error(
+                "kbddaemonbus",
+                &format!("layout '{}' is not one of the setxkbmap layouts", layout),
+            )?;
+        KbdDaemonBus::call("set_layout", Some(idx as u32))
+    }
+
+    fn next_layout(&self) -> Result<()> {
+        KbdDaemonBus::call("next_layout", None)
+    }
+
     // Monitor KbdDaemon 'layoutChanged' property in a separate thread and send updates
     // via the `update_request` channel.
     fn monitor(&self, id: usize, update_request: Sender<Task>) {
@@ -310,6 +422,8 @@ impl dbus::ffidisp::MsgHandler for KbddMessageHandler {
 
 pub struct Sway {
     sway_kb_layout: Arc<Mutex<String>>,
+    /// The input the commands apply to
+    sway_kb_identifier: String,
 }
 
 impl Sway {
@@ -331,8 +445,25 @@ impl Sway {
 
         Ok(Sway {
             sway_kb_layout: Arc::new(Mutex::new(layout)),
+            sway_kb_identifier: sway_kb_identifier.unwrap_or_else(|| "type:keyboard".to_string()),
         })
     }
+
+    fn run_input_command(&self, command: &str) -> Result<()> {
+        let outcomes = swayipc::Connection::new()
+            .block_error("sway", "Failed to connect to sway.")?
+            .run_command(format!("input \"{}\" {}", self.sway_kb_identifier, command))
+            .block_error("sway", "Failed to run the sway command.")?;
+        match outcomes.into_iter().find(|o| !o.success) {
+            Some(failure) => Err(BlockError(
+                "sway".to_string(),
+                failure
+                    .error
+                    .unwrap_or_else(|| format!("'{}' failed", command)),
+            )),
+            None => Ok(()),
+        }
+    }
 }
 
 impl KeyboardLayoutMonitor for Sway {
@@ -365,6 +496,18 @@ impl KeyboardLayoutMonitor for Sway {
         false
     }
 
+    fn set_layout(&self, layout: &str, variant: Option<&str>) -> Result<()> {
+        self.run_input_command(&format!("xkb_layout \"{}\"", layout))?;
+        if let Some(variant) = variant {
+            self.run_input_command(&format!("xkb_variant \"{}\"", variant))?;
+        }
+        Ok(())
+    }
+
+    fn next_layout(&self) -> Result<()> {
+        self.run_input_command("xkb_switch_layout next")
+    }
+
     /// Monitor layout changes in a separate thread and send updates
     /// via the `update_request` channel.
     fn monitor(&self, id: usize, update_request: Sender<Task>) {
@@ -426,6 +569,9 @@ pub struct KeyboardLayoutConfig {
 
     // Used to ovrreride long layout names: "German (dead acute)" => "DE"
     mappings: Option<HashMap<String, String>>,
+
+    /// The layouts a click cycles through, as `layout` or `layout (variant)`
+    layouts: Vec<String>,
 }
 
 impl Default for KeyboardLayoutConfig {
@@ -436,6 +582,7 @@ impl Default for KeyboardLayoutConfig {
             interval: Duration::from_secs(60),
             sway_kb_identifier: None,
             mappings: None,
+            layouts: Vec::new(),
         }
     }
 }
@@ -447,6 +594,11 @@ pub struct KeyboardLayout {
     update_interval: Option<Duration>,
     format: FormatTemplate,
     mappings: Option<HashMap<String, String>>,
+    layouts: Vec<String>,
+    /// The position in `layouts` of the layout set by the last click
+    layout_index: Option<usize>,
+    /// Only loaded if `format` shows the description
+    descriptions: Option<XkbDescriptions>,
 }
 
 impl ConfigBlock for KeyboardLayout {
@@ -492,6 +644,13 @@ impl ConfigBlock for KeyboardLayout {
                 "Invalid format specified for keyboard_layout",
             )?,
             mappings: block_config.mappings,
+            layouts: block_config.layouts,
+            layout_index: None,
+            descriptions: if block_config.format.contains("{description") {
+                Some(XkbDescriptions::load())
+            } else {
+                None
+            },
         })
     }
 }
@@ -502,8 +661,44 @@ impl Block for KeyboardLayout {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let mut layout = self.monitor.keyboard_layout()?;
+        let layout = self.monitor.keyboard_layout()?;
         let variant = self.monitor.keyboard_variant()?;
+        self.show(layout, variant)?;
+        Ok(self.update_interval.map(|d| d.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        if self.layouts.is_empty() {
+            // The new layout is only known once the backend reports it
+            return self.monitor.next_layout();
+        }
+
+        let current = self.monitor.keyboard_layout()?;
+        let variant = self.monitor.keyboard_variant()?;
+        let index = next_layout_index(&self.layouts, &current, &variant, self.layout_index);
+        let (layout, variant) = parse_layout(&self.layouts[index]);
+        self.monitor.set_layout(layout, variant)?;
+        self.layout_index = Some(index);
+
+        // Show the new layout right away, the backends take a while to report it
+        self.show(layout.to_string(), variant.unwrap_or("N/A").to_string())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+}
+
+impl KeyboardLayout {
+    fn show(&mut self, mut layout: String, variant: String) -> Result<()> {
+        let description = self
+            .descriptions
+            .as_ref()
+            .map(|d| d.describe(&layout, &variant))
+            .unwrap_or_default();
         if let Some(ref mappings) = self.mappings {
             if let Some(mapped) = mappings.get(&format!("{} ({})", layout, variant)) {
                 layout = mapped.to_string();
@@ -511,14 +706,163 @@ impl Block for KeyboardLayout {
         }
         let values = map!(
             "layout" => Value::from_string(layout),
-            "variant" => Value::from_string(variant)
+            "variant" => Value::from_string(variant),
+            "description" => Value::from_string(description)
         );
 
         self.output.set_text(self.format.render(&values)?);
-        Ok(self.update_interval.map(|d| d.into()))
+        Ok(())
     }
+}
 
-    fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.output]
+/// The descriptions of the layouts and variants from the XKB rules database, e.g.
+/// "English (Dvorak)" for the `dvorak` variant of `us`
+#[derive(Debug, Default)]
+struct XkbDescriptions {
+    layouts: HashMap<String, String>,
+    /// Keyed by layout and variant
+    variants: HashMap<(String, String), String>,
+}
+
+impl XkbDescriptions {
+    const RULES: &'static [&'static str] = &[
+        "/usr/share/X11/xkb/rules/evdev.lst",
+        "/usr/share/X11/xkb/rules/base.lst",
+    ];
+
+    /// Loads the first rules file found, without any descriptions if there is none
+    fn load() -> Self {
+        Self::RULES
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .map(|rules| Self::parse(&rules))
+            .unwrap_or_default()
+    }
+
+    /// Parses the `! layout` and `! variant` sections of a rules `.lst` file
+    fn parse(rules: &str) -> Self {
+        let mut descriptions = Self::default();
+        let mut section = "";
+        for line in rules.lines() {
+            if let Some(name) = line.strip_prefix('!') {
+                section = name.trim();
+                continue;
+            }
+            let (name, description) = match line.trim().split_once(char::is_whitespace) {
+                Some((name, description)) => (name, description.trim()),
+                None => continue,
+            };
+            match section {
+                "layout" => {
+                    descriptions
+                        .layouts
+                        .insert(name.to_string(), description.to_string());
+                }
+                "variant" => {
+                    if let Some((layout, description)) = description.split_once(": ") {
+                        descriptions.variants.insert(
+                            (layout.to_string(), name.to_string()),
+                            description.to_string(),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        descriptions
+    }
+
+    /// The description of `layout` with `variant`, or the codes if they aren't in the
+    /// database (as for the sway driver, which already reports descriptions)
+    fn describe(&self, layout: &str, variant: &str) -> String {
+        let has_variant = !variant.is_empty() && variant != "N/A";
+        let description = if has_variant {
+            self.variants
+                .get(&(layout.to_string(), variant.to_string()))
+        } else {
+            self.layouts.get(layout)
+        };
+        match description {
+            Some(description) => description.clone(),
+            None if has_variant => format!("{} ({})", layout, variant),
+            None => layout.to_string(),
+        }
+    }
+}
+
+/// Splits a layout of the `layouts` option into the layout and the variant
+fn parse_layout(entry: &str) -> (&str, Option<&str>) {
+    match entry.find('(') {
+        Some(i) => (
+            entry[..i].trim(),
+            Some(entry[i + 1..].trim_end_matches(')').trim()),
+        ),
+        None => (entry.trim(), None),
+    }
+}
+
+/// The position in `layouts` of the layout after the current one. The current layout is found
+/// by name, or is the one of the last click (`last`) if it isn't in `layouts` by name, as
+/// the drivers may report descriptions.
+fn next_layout_index(
+    layouts: &[String],
+    layout: &str,
+    variant: &str,
+    last: Option<usize>,
+) -> usize {
+    let current = layouts
+        .iter()
+        .position(|entry| match parse_layout(entry) {
+            (l, Some(v)) => l == layout && v == variant,
+            (l, None) => l == layout,
+        })
+        .or(last);
+    current.map_or(0, |i| (i + 1) % layouts.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setxkbmap_output() {
+        let output =
+            "rules:      evdev\nmodel:      pc105\nlayout:     us,de\nvariant:    dvorak,\n";
+        assert_eq!(setxkbmap_entry(output, "layout").as_deref(), Some("us,de"));
+        assert_eq!(
+            setxkbmap_entry(output, "variant").as_deref(),
+            Some("dvorak,")
+        );
+        assert_eq!(setxkbmap_entry(output, "options"), None);
+    }
+
+    #[test]
+    fn descriptions() {
+        let descriptions = XkbDescriptions::parse(
+            "! model\n  pc105           Generic 105-key PC\n\
+             ! layout\n  us              English (US)\n  de              German\n\
+             ! variant\n  dvorak          us: English (Dvorak)\n  us              de: German (US)\n",
+        );
+        assert_eq!(descriptions.describe("us", "N/A"), "English (US)");
+        assert_eq!(descriptions.describe("us", ""), "English (US)");
+        assert_eq!(descriptions.describe("us", "dvorak"), "English (Dvorak)");
+        assert_eq!(descriptions.describe("de", "us"), "German (US)");
+        assert_eq!(descriptions.describe("pc105", "N/A"), "pc105");
+        assert_eq!(descriptions.describe("fr", "bepo"), "fr (bepo)");
+    }
+
+    #[test]
+    fn cycle_layouts() {
+        assert_eq!(parse_layout("us"), ("us", None));
+        assert_eq!(parse_layout("de (nodeadkeys)"), ("de", Some("nodeadkeys")));
+        assert_eq!(parse_layout("fr(bepo)"), ("fr", Some("bepo")));
+
+        let layouts: Vec<String> = vec!["us".into(), "de (nodeadkeys)".into(), "fr".into()];
+        assert_eq!(next_layout_index(&layouts, "us", "N/A", None), 1);
+        assert_eq!(next_layout_index(&layouts, "de", "nodeadkeys", None), 2);
+        assert_eq!(next_layout_index(&layouts, "fr", "", Some(0)), 0);
+        // Not in the list by name
+        assert_eq!(next_layout_index(&layouts, "English (US)", "", None), 0);
+        assert_eq!(next_layout_index(&layouts, "English (US)", "", Some(1)), 2);
     }
 }
@@ -24,6 +24,29 @@ pub struct Load {
     minimum_info: f64,
     minimum_warning: f64,
     minimum_critical: f64,
+    average: LoadAverage,
+}
+
+/// One of the load averages of /proc/loadavg
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadAverage {
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+}
+
+impl LoadAverage {
+    /// The position of the average in /proc/loadavg
+    fn index(self) -> usize {
+        match self {
+            LoadAverage::OneMinute => 0,
+            LoadAverage::FiveMinutes => 1,
+            LoadAverage::FifteenMinutes => 2,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -41,6 +64,9 @@ pub struct LoadConfig {
 
     /// Minimum load, where state is set to critical
     pub critical: f64,
+
+    /// The load average (per core) the state is set from
+    pub average: LoadAverage,
 }
 
 impl Default for LoadConfig {
@@ -51,6 +77,7 @@ impl Default for LoadConfig {
             info: 0.3,
             warning: 0.6,
             critical: 0.9,
+            average: LoadAverage::OneMinute,
         }
     }
 }
@@ -83,6 +110,7 @@ impl ConfigBlock for Load {
             minimum_info: block_config.info,
             minimum_warning: block_config.warning,
             minimum_critical: block_config.critical,
+            average: block_config.average,
             format: FormatTemplate::from_string(&block_config.format)
                 .block_error("load", "Invalid format specified for load")?,
             text,
@@ -90,6 +118,12 @@ impl ConfigBlock for Load {
     }
 }
 
+/// The 1, 5 and 15 minutes load averages at the start of /proc/loadavg
+fn parse_loadavg(loadavg: &str) -> Option<[f64; 3]> {
+    let mut averages = loadavg.split_whitespace().map(|x| x.parse::<f64>().ok());
+    Some([averages.next()??, averages.next()??, averages.next()??])
+}
+
 impl Block for Load {
     fn update(&mut self) -> Result<Option<Update>> {
         let mut f = OpenOptions::new()
@@ -103,19 +137,21 @@ impl Block for Load {
         f.read_to_string(&mut loadavg)
             .block_error("load", "Failed to read the load average of your system!")?;
 
-        let split: Vec<f64> = (&loadavg)
-            .split(' ')
-            .take(3)
-            .map(|x| x.parse().unwrap())
-            .collect();
+        let averages = parse_loadavg(&loadavg)
+            .block_error("load", "Failed to parse the load average of your system!")?;
+        let cores = self.logical_cores.max(1) as f64;
 
         let values = map!(
-            "1m" => Value::from_float(split[0]),
-            "5m" => Value::from_float(split[1]),
-            "15m" => Value::from_float(split[2]),
+            "1m" => Value::from_float(averages[0]),
+            "5m" => Value::from_float(averages[1]),
+            "15m" => Value::from_float(averages[2]),
+            "1m_per_core" => Value::from_float(averages[0] / cores),
+            "5m_per_core" => Value::from_float(averages[1] / cores),
+            "15m_per_core" => Value::from_float(averages[2] / cores),
+            "cores" => Value::from_integer(self.logical_cores as i64),
         );
 
-        let used_perc = split[0] / (self.logical_cores as f64);
+        let used_perc = averages[self.average.index()] / cores;
 
         self.text.set_state(match used_perc {
             x if x > self.minimum_critical => State::Critical,
@@ -137,3 +173,18 @@ impl Block for Load {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_loadavg;
+
+    #[test]
+    fn loadavg() {
+        assert_eq!(
+            parse_loadavg("0.52 0.58 0.59 1/467 12345\n"),
+            Some([0.52, 0.58, 0.59])
+        );
+        assert_eq!(parse_loadavg("0.52 0.58"), None);
+        assert_eq!(parse_loadavg("0.52 x 0.59"), None);
+    }
+}
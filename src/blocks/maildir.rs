@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -6,8 +7,10 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_vec_expanded};
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -21,11 +24,30 @@ pub enum MailType {
 }
 
 impl MailType {
-    fn count_mail(&self, maildir: &ExtMaildir) -> usize {
+    fn count_mail(&self, counts: &MailCounts) -> usize {
         match self {
-            MailType::New => maildir.count_new(),
-            MailType::Cur => maildir.count_cur(),
-            MailType::All => maildir.count_new() + maildir.count_cur(),
+            MailType::New => counts.new,
+            MailType::Cur => counts.cur,
+            MailType::All => counts.new + counts.cur,
+        }
+    }
+}
+
+/// The number of mails in the `new` and `cur` directories of a maildir
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct MailCounts {
+    new: usize,
+    cur: usize,
+}
+
+impl MailCounts {
+    /// Counts the mails of the maildir at `path`. A missing maildir (or a missing directory of
+    /// it) has no mails.
+    fn of(path: &str) -> Self {
+        let maildir = ExtMaildir::from(path);
+        MailCounts {
+            new: maildir.count_new(),
+            cur: maildir.count_cur(),
         }
     }
 }
@@ -38,20 +60,23 @@ pub struct Maildir {
     threshold_warning: usize,
     threshold_critical: usize,
     display_type: MailType,
+    format: FormatTemplate,
 }
 
-//TODO add `format`
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct MaildirConfig {
     /// Update interval in seconds
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
+    #[serde(deserialize_with = "deserialize_vec_expanded")]
     pub inboxes: Vec<String>,
     pub threshold_warning: usize,
     pub threshold_critical: usize,
     pub display_type: MailType,
     pub icon: bool,
+    /// Format override
+    pub format: String,
 }
 
 impl Default for MaildirConfig {
@@ -63,6 +88,7 @@ impl Default for MaildirConfig {
             threshold_critical: 10,
             display_type: MailType::New,
             icon: true,
+            format: "{count}".to_string(),
         }
     }
 }
@@ -89,18 +115,46 @@ impl ConfigBlock for Maildir {
             threshold_warning: block_config.threshold_warning,
             threshold_critical: block_config.threshold_critical,
             display_type: block_config.display_type,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("maildir", "Invalid format specified")?,
         })
     }
 }
 
 impl Block for Maildir {
     fn update(&mut self) -> Result<Option<Update>> {
-        let mut newmails = 0;
-        for inbox in &self.inboxes {
-            let isl: &str = &inbox[..];
-            let maildir = ExtMaildir::from(isl);
-            newmails += self.display_type.count_mail(&maildir)
+        let counts: Vec<MailCounts> = self
+            .inboxes
+            .iter()
+            .map(|inbox| MailCounts::of(inbox))
+            .collect();
+        let total = MailCounts {
+            new: counts.iter().map(|c| c.new).sum(),
+            cur: counts.iter().map(|c| c.cur).sum(),
+        };
+        let newmails = self.display_type.count_mail(&total);
+
+        // The counts of each inbox are suffixed with its index in `inboxes`
+        let mut values = HashMap::new();
+        for (i, inbox_counts) in std::iter::once((String::new(), &total))
+            .chain(counts.iter().enumerate().map(|(i, c)| (i.to_string(), c)))
+        {
+            let count = self.display_type.count_mail(inbox_counts);
+            values.insert(format!("count{}", i), Value::from_integer(count as i64));
+            values.insert(
+                format!("new{}", i),
+                Value::from_integer(inbox_counts.new as i64),
+            );
+            values.insert(
+                format!("cur{}", i),
+                Value::from_integer(inbox_counts.cur as i64),
+            );
         }
+        let values = values
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.clone()))
+            .collect();
+
         let mut state = State::Idle;
         if newmails >= self.threshold_critical {
             state = State::Critical;
@@ -108,7 +162,7 @@ impl Block for Maildir {
             state = State::Warning;
         }
         self.text.set_state(state);
-        self.text.set_text(format!("{}", newmails));
+        self.text.set_text(self.format.render(&values)?);
         Ok(Some(self.update_interval.into()))
     }
 
@@ -120,3 +174,27 @@ impl Block for Maildir {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_mails() {
+        let maildir = crate::util::test_path("count_mails");
+        for (dir, mails) in &[("new", 2), ("cur", 3), ("tmp", 1)] {
+            std::fs::create_dir_all(maildir.join(dir)).unwrap();
+            for i in 0..*mails {
+                std::fs::write(maildir.join(dir).join(format!("mail{}", i)), "").unwrap();
+            }
+        }
+        let counts = MailCounts::of(maildir.to_str().unwrap());
+        assert_eq!(counts, MailCounts { new: 2, cur: 3 });
+        assert_eq!(MailType::All.count_mail(&counts), 5);
+
+        let missing = MailCounts::of("/nonexistent/i3status-rs-maildir");
+        assert_eq!(missing, MailCounts::default());
+
+        std::fs::remove_dir_all(maildir).unwrap();
+    }
+}
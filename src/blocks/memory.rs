@@ -8,8 +8,9 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_percent_threshold, Threshold};
 use crate::errors::*;
+use crate::formatting::prefix::BytePrefix;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
@@ -29,6 +30,7 @@ pub enum Memtype {
 struct Memstate {
     mem_total: (u64, bool),
     mem_free: (u64, bool),
+    mem_available: (u64, bool),
     buffers: (u64, bool),
     cached: (u64, bool),
     s_reclaimable: (u64, bool),
@@ -46,6 +48,21 @@ impl Memstate {
         self.mem_free.0
     }
 
+    /// The memory available for starting new applications, in kB. Estimated from the free
+    /// memory and the caches on kernels without `MemAvailable` (before 3.14).
+    fn mem_available(&self) -> u64 {
+        if self.mem_available.1 {
+            self.mem_available.0
+        } else {
+            self.mem_free() + self.buffers() + self.cached_total()
+        }
+    }
+
+    /// The page cache and reclaimable slabs, without the shared memory (which can't be freed)
+    fn cached_total(&self) -> u64 {
+        (self.cached() + self.s_reclaimable()).saturating_sub(self.shmem())
+    }
+
     fn buffers(&self) -> u64 {
         self.buffers.0
     }
@@ -74,6 +91,7 @@ impl Memstate {
         Memstate {
             mem_total: (0, false),
             mem_free: (0, false),
+            mem_available: (0, false),
             buffers: (0, false),
             cached: (0, false),
             s_reclaimable: (0, false),
@@ -83,6 +101,8 @@ impl Memstate {
         }
     }
 
+    /// Whether all the values are read. `MemAvailable` is optional, it comes before the others
+    /// anyway.
     fn done(&self) -> bool {
         self.mem_total.1
             && self.mem_free.1
@@ -104,8 +124,10 @@ pub struct Memory {
     format: (FormatTemplate, FormatTemplate),
     update_interval: Duration,
     tx_update_request: Sender<Task>,
-    warning: (f64, f64),
-    critical: (f64, f64),
+    warning: (Threshold, Threshold),
+    critical: (Threshold, Threshold),
+    swap_sets_state: bool,
+    byte_prefix: BytePrefix,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -130,32 +152,44 @@ pub struct MemoryConfig {
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
 
-    /// Percentage of memory usage, where state is set to warning
-    pub warning_mem: f64,
+    /// Percentage (or size, like "4GiB") of memory usage, where state is set to warning
+    #[serde(deserialize_with = "deserialize_percent_threshold")]
+    pub warning_mem: Threshold,
+
+    /// Percentage (or size, like "4GiB") of swap usage, where state is set to warning
+    #[serde(deserialize_with = "deserialize_percent_threshold")]
+    pub warning_swap: Threshold,
+
+    /// Percentage (or size, like "4GiB") of memory usage, where state is set to critical
+    #[serde(deserialize_with = "deserialize_percent_threshold")]
+    pub critical_mem: Threshold,
 
-    /// Percentage of swap usage, where state is set to warning
-    pub warning_swap: f64,
+    /// Percentage (or size, like "4GiB") of swap usage, where state is set to critical
+    #[serde(deserialize_with = "deserialize_percent_threshold")]
+    pub critical_swap: Threshold,
 
-    /// Percentage of memory usage, where state is set to critical
-    pub critical_mem: f64,
+    /// Whether the swap usage can raise the state of the memory view too
+    pub swap_sets_state: bool,
 
-    /// Percentage of swap usage, where state is set to critical
-    pub critical_swap: f64,
+    /// How byte values are scaled and labelled. Options are binary, si and iec
+    pub byte_prefix: BytePrefix,
 }
 
 impl Default for MemoryConfig {
     fn default() -> Self {
         Self {
-            format_mem: "{mem_free;M}/{mem_total;M}({mem_total_used_percents})".to_string(),
+            format_mem: "{mem_avail;M}/{mem_total;M}({mem_used_percents})".to_string(),
             format_swap: "{swap_free;M}/{swap_total;M}({swap_used_percents})".to_string(),
             display_type: Memtype::Memory,
             icons: true,
             clickable: true,
             interval: Duration::from_secs(5),
-            warning_mem: 80.,
-            warning_swap: 80.,
-            critical_mem: 95.,
-            critical_swap: 95.,
+            warning_mem: Threshold::Value(80.),
+            warning_swap: Threshold::Value(80.),
+            critical_mem: Threshold::Value(95.),
+            critical_swap: Threshold::Value(95.),
+            swap_sets_state: false,
+            byte_prefix: BytePrefix::default(),
         }
     }
 }
@@ -169,47 +203,46 @@ impl Memory {
         let swap_used = swap_total - swap_free;
         let mem_total_used = mem_total - mem_free;
         let buffers = mem_state.buffers() as f64 * 1024.;
-        let cached =
-            // Why do we include shared memory to "cached"?
-            (mem_state.cached() + mem_state.s_reclaimable() - mem_state.shmem()) as f64 * 1024.;
-        let mem_used = mem_total_used - (buffers + cached);
-        let mem_avail = mem_total - mem_used;
+        let cached = mem_state.cached_total() as f64 * 1024.;
+        let mem_avail = mem_state.mem_available() as f64 * 1024.;
+        let mem_used = mem_total - mem_avail;
 
+        let bp = self.byte_prefix;
         let values = map!(
-            "mem_total" => Value::from_float(mem_total).bytes(),
-            "mem_free" => Value::from_float(mem_free).bytes(),
+            "mem_total" => Value::from_float(mem_total).bytes().byte_prefix(bp),
+            "mem_free" => Value::from_float(mem_free).bytes().byte_prefix(bp),
             "mem_free_percents" => Value::from_float(mem_free / mem_total * 100.).percents(),
-            "mem_total_used" => Value::from_float(mem_total_used).bytes(),
+            "mem_total_used" => Value::from_float(mem_total_used).bytes().byte_prefix(bp),
             "mem_total_used_percents" => Value::from_float(mem_total_used / mem_total * 100.).percents(),
-            "mem_used" => Value::from_float(mem_used).bytes(),
+            "mem_used" => Value::from_float(mem_used).bytes().byte_prefix(bp),
             "mem_used_percents" => Value::from_float(mem_used / mem_total * 100.).percents(),
-            "mem_avail" => Value::from_float(mem_avail).bytes(),
+            "mem_avail" => Value::from_float(mem_avail).bytes().byte_prefix(bp),
             "mem_avail_percents" => Value::from_float(mem_avail / mem_total * 100.).percents(),
-            "swap_total" => Value::from_float(swap_total).bytes(),
-            "swap_free" => Value::from_float(swap_free).bytes(),
-            "swap_free_percents" => Value::from_float(swap_free / swap_total * 100.).percents(),
-            "swap_used" => Value::from_float(swap_used).bytes(),
-            "swap_used_percents" => Value::from_float(swap_used / swap_total * 100.).percents(),
-            "buffers" => Value::from_float(buffers).bytes(),
+            "swap_total" => Value::from_float(swap_total).bytes().byte_prefix(bp),
+            "swap_free" => Value::from_float(swap_free).bytes().byte_prefix(bp),
+            "swap_free_percents" => Value::from_float(percents(swap_free, swap_total)).percents(),
+            "swap_used" => Value::from_float(swap_used).bytes().byte_prefix(bp),
+            "swap_used_percents" => Value::from_float(percents(swap_used, swap_total)).percents(),
+            "buffers" => Value::from_float(buffers).bytes().byte_prefix(bp),
             "buffers_percent" => Value::from_float(buffers / mem_total * 100.).percents(),
-            "cached" => Value::from_float(cached).bytes(),
+            "cached" => Value::from_float(cached).bytes().byte_prefix(bp),
             "cached_percent" => Value::from_float(cached / mem_total * 100.).percents(),
         );
 
+        let swap_state = usage_state(swap_used, swap_total, self.warning.1, self.critical.1);
         match self.memtype {
-            Memtype::Memory => self.output.0.set_state(match mem_used / mem_total * 100. {
-                x if x > self.critical.0 => State::Critical,
-                x if x > self.warning.0 => State::Warning,
-                _ => State::Idle,
-            }),
-            Memtype::Swap => self
-                .output
-                .1
-                .set_state(match swap_used / swap_total * 100. {
-                    x if x > self.critical.1 => State::Critical,
-                    x if x > self.warning.1 => State::Warning,
-                    _ => State::Idle,
-                }),
+            Memtype::Memory => {
+                let mem_state = usage_state(mem_used, mem_total, self.warning.0, self.critical.0);
+                self.output.0.set_state(match (mem_state, swap_state) {
+                    (_, State::Critical) | (State::Idle, State::Warning)
+                        if self.swap_sets_state =>
+                    {
+                        swap_state
+                    }
+                    _ => mem_state,
+                })
+            }
+            Memtype::Swap => self.output.1.set_state(swap_state),
         };
 
         Ok(match self.memtype {
@@ -227,6 +260,28 @@ impl Memory {
     }
 }
 
+/// `part` in percents of `total`, or 0 if there is nothing (e.g. no swap)
+fn percents(part: f64, total: f64) -> f64 {
+    if total > 0. {
+        part / total * 100.
+    } else {
+        0.
+    }
+}
+
+/// The state for `used` bytes out of `total`, which is idle if there is nothing (e.g. no swap)
+fn usage_state(used: f64, total: f64, warning: Threshold, critical: Threshold) -> State {
+    if total <= 0. {
+        return State::Idle;
+    }
+    let per_cent = total / 100.;
+    match used / per_cent {
+        x if x > critical.value(per_cent) => State::Critical,
+        x if x > warning.value(per_cent) => State::Warning,
+        _ => State::Idle,
+    }
+}
+
 impl ConfigBlock for Memory {
     type Config = MemoryConfig;
 
@@ -257,6 +312,8 @@ impl ConfigBlock for Memory {
             tx_update_request: tx,
             warning: (block_config.warning_mem, block_config.warning_swap),
             critical: (block_config.critical_mem, block_config.critical_swap),
+            swap_sets_state: block_config.swap_sets_state,
+            byte_prefix: block_config.byte_prefix,
         })
     }
 }
@@ -301,6 +358,14 @@ impl Block for Memory {
                     );
                     continue;
                 }
+                Some(&"MemAvailable:") => {
+                    mem_state.mem_available = (
+                        u64::from_str(line[1])
+                            .block_error("memory", "failed to parse mem_available")?,
+                        true,
+                    );
+                    continue;
+                }
                 Some(&"Buffers:") => {
                     mem_state.buffers = (
                         u64::from_str(line[1]).block_error("memory", "failed to parse buffers")?,
@@ -383,3 +448,32 @@ impl Block for Memory {
         }]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn available_memory() {
+        let mut state = Memstate::new();
+        state.mem_free = (1000, true);
+        state.buffers = (100, true);
+        state.cached = (500, true);
+        state.s_reclaimable = (50, true);
+        state.shmem = (150, true);
+        // Old kernels: estimated from the free memory and the caches
+        assert_eq!(state.mem_available(), 1500);
+        state.mem_available = (1200, true);
+        assert_eq!(state.mem_available(), 1200);
+    }
+
+    #[test]
+    fn without_swap() {
+        assert_eq!(percents(0., 0.), 0.);
+        assert_eq!(percents(1., 4.), 25.);
+        let state = usage_state(0., 0., Threshold::Value(80.), Threshold::Value(95.));
+        assert!(matches!(state, State::Idle));
+        let state = usage_state(90., 100., Threshold::Value(80.), Threshold::Value(95.));
+        assert!(matches!(state, State::Warning));
+    }
+}
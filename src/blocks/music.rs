@@ -17,18 +17,21 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::{LogicalDirection, Scrolling, SharedConfig};
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_one_or_many};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::subprocess::spawn_child_async;
+use crate::subprocess::spawn_shell_async;
 use crate::util::pseudo_uuid;
 use crate::widgets::{
     rotatingtext::RotatingTextWidget, text::TextWidget, I3BarWidget, Spacing, State,
 };
 
+/// How often the position in the track is refreshed while playing
+const POSITION_INTERVAL: Duration = Duration::from_secs(1);
+
 #[derive(Debug, Clone)]
 struct Player {
     bus_name: String,
@@ -36,6 +39,10 @@ struct Player {
     playback_status: PlaybackStatus,
     artist: Option<String>,
     title: Option<String>,
+    track_id: Option<String>,
+    /// Unknown for streams
+    length: Option<Duration>,
+    art: Option<AlbumArt>,
     //TODO
     //volume: u32,
 }
@@ -43,12 +50,12 @@ struct Player {
 impl Player {
     pub fn new(dbus_conn: &Connection, name: &str, bus_name: &str) -> Self {
         let path = dbus_conn.with_path(name, "/org/mpris/MediaPlayer2", 500);
-        let data = path
+        let metadata = path
             .get("org.mpris.MediaPlayer2.Player", "Metadata")
             .map(|d: Box<dyn RefArg>| extract_from_metadata(d.as_ref()));
-        let (title, artist) = match data {
+        let metadata = match metadata {
             Ok(Ok(res)) => res,
-            _ => (None, None),
+            _ => Metadata::default(),
         };
 
         // Get current playback status
@@ -61,12 +68,96 @@ impl Player {
             bus_name: bus_name.to_string(),
             interface_name: name.to_string(),
             playback_status: status,
-            artist,
-            title,
+            artist: metadata.artist,
+            title: metadata.title,
+            track_id: metadata.track_id,
+            length: metadata.length,
+            art: metadata.art,
         }
     }
+
+    /// Applies changed metadata, returns whether anything changed
+    fn set_metadata(&mut self, metadata: Metadata) -> bool {
+        let changed = self.title != metadata.title
+            || self.artist != metadata.artist
+            || self.track_id != metadata.track_id
+            || self.length != metadata.length
+            || self.art != metadata.art;
+        self.title = metadata.title;
+        self.artist = metadata.artist;
+        self.track_id = metadata.track_id;
+        self.length = metadata.length;
+        self.art = metadata.art;
+        changed
+    }
 }
 
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Metadata {
+    title: Option<String>,
+    artist: Option<String>,
+    track_id: Option<String>,
+    length: Option<Duration>,
+    art: Option<AlbumArt>,
+}
+
+/// Where the album art of a track is, from its `mpris:artUrl`
+#[derive(Debug, Clone, PartialEq)]
+enum AlbumArt {
+    /// A local file, from a `file://` URL
+    File(String),
+    /// Any other URL, which has to be downloaded
+    Remote(String),
+}
+
+impl AlbumArt {
+    fn from_url(url: &str) -> Option<Self> {
+        if url.is_empty() {
+            None
+        } else if let Some(path) = url.strip_prefix("file://") {
+            Some(AlbumArt::File(percent_decode(path)))
+        } else {
+            Some(AlbumArt::Remote(url.to_string()))
+        }
+    }
+
+    /// The path of the file or the URL
+    fn location(&self) -> &str {
+        match self {
+            AlbumArt::File(path) | AlbumArt::Remote(path) => path,
+        }
+    }
+}
+
+/// Decodes the `%XX` escapes of an URL, keeping invalid ones as they are
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Environment variables holding the album art for the click commands, the first one for local
+/// files and the second one for remote URLs
+const ART_FILE_ENV: &str = "I3RS_MUSIC_ART_FILE";
+const ART_URL_ENV: &str = "I3RS_MUSIC_ART_URL";
+
 #[derive(Debug, Clone, PartialEq)]
 enum PlaybackStatus {
     Playing,
@@ -102,14 +193,60 @@ pub struct Music {
     max_width: usize,
     separator: String,
     seek_step: i64,
+    seek_on_click: bool,
     players: Arc<Mutex<Vec<Player>>>,
     hide_when_empty: bool,
     send: Sender<Task>,
     format: FormatTemplate,
+    /// Whether `format` needs the position in the track, which has to be polled
+    shows_position: bool,
     scrolling: Scrolling,
 }
 
 impl Music {
+    /// The position of `player` in the current track, asked over D-Bus as players don't
+    /// signal its changes
+    fn position(&self, player: &Player) -> Option<Duration> {
+        let path = self.dbus_conn.with_path(
+            player.interface_name.clone(),
+            "/org/mpris/MediaPlayer2",
+            500,
+        );
+        path.get("org.mpris.MediaPlayer2.Player", "Position")
+            .ok()
+            .and_then(|d: Box<dyn RefArg>| d.as_i64())
+            .map(|us| Duration::from_micros(us.max(0) as u64))
+    }
+
+    /// Seeks `player` to where the block was clicked, taking the block as a progress bar
+    fn seek_to_click(&self, player: &Player, event: &I3BarEvent) -> Result<()> {
+        let (track_id, length, fraction) =
+            match (&player.track_id, player.length, event.relative_position()) {
+                (Some(track_id), Some(length), Some(fraction)) => (track_id, length, fraction),
+                // Streams can't be seeked, and old bars don't tell where the click was
+                _ => return Ok(()),
+            };
+        let track_id =
+            dbus::Path::new(track_id.clone()).block_error("music", "invalid MPRIS track id")?;
+        let position = (length.as_micros() as f64 * fraction) as i64;
+        let m = Message::new_method_call(
+            player.interface_name.clone(),
+            "/org/mpris/MediaPlayer2",
+            "org.mpris.MediaPlayer2.Player",
+            "SetPosition",
+        )
+        .block_error("music", "failed to create D-Bus method call")?
+        .append2(track_id, position);
+        self.dbus_conn
+            .send(m)
+            .block_error("music", "failed to call method via D-Bus")?;
+        self.send.send(Task {
+            id: self.id,
+            update_time: Instant::now(),
+        })?;
+        Ok(())
+    }
+
     fn smart_trim(&self, artist: String, title: String) -> String {
         // Below code is by https://github.com/jgbyrne
         let mut artist: String = artist;
@@ -181,10 +318,12 @@ impl Music {
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct MusicConfig {
-    /// Name of the music player. Must be the same name the player is
-    /// registered with the MediaPlayer2 Interface. If not specified then
-    /// the block will track all players found.
-    pub player: Option<String>,
+    /// Names of the music players, in order of preference. Must be the same
+    /// names the players are registered with on the MediaPlayer2 Interface.
+    /// The block shows the first one available. If not specified then the
+    /// block will track all players found.
+    #[serde(deserialize_with = "deserialize_one_or_many")]
+    pub player: Vec<String>,
 
     /// Max width of the block in characters, not including the buttons.
     pub max_width: usize,
@@ -205,6 +344,9 @@ pub struct MusicConfig {
     #[serde(deserialize_with = "deserialize_duration")]
     pub marquee_speed: Duration,
 
+    /// Text shown between the end and the start of the title while it rotates.
+    pub marquee_gap: String,
+
     /// Bool to specify whether smart trimming should be used when marquee
     /// rotation is disabled and the title + artist is longer than
     /// max-width. It will trim from both the artist and the title in proportion
@@ -223,6 +365,9 @@ pub struct MusicConfig {
     // Number of microseconds to seek forward/backward when scrolling on the bar.
     pub seek_step: i64,
 
+    /// Seek to the position clicked on the block (if `on_click` is not set).
+    pub seek_on_click: bool,
+
     /// MPRIS interface name regex patterns to ignore.
     pub interface_name_exclude: Vec<String>,
 
@@ -235,17 +380,19 @@ pub struct MusicConfig {
 impl Default for MusicConfig {
     fn default() -> Self {
         Self {
-            player: None,
+            player: Vec::new(),
             max_width: 21,
             dynamic_width: false,
             marquee: true,
             marquee_interval: Duration::from_secs(10),
             marquee_speed: Duration::from_millis(500),
+            marquee_gap: "|".to_string(),
             smart_trim: false,
             separator: " - ".to_string(),
             buttons: Vec::new(),
             on_collapsed_click: None,
             seek_step: 1000,
+            seek_on_click: false,
             interface_name_exclude: Vec::new(),
             hide_when_empty: false,
             format: "{combo}".to_string(),
@@ -289,11 +436,7 @@ impl ConfigBlock for Music {
             .unwrap();
         let names = list_names.get1::<Array<&str, _>>().unwrap().filter(|name| {
             // If an interface matches an exclude pattern, ignore it
-            !ignored_player(
-                name,
-                &interface_name_exclude_regexps,
-                block_config.player.clone(),
-            )
+            !ignored_player(name, &interface_name_exclude_regexps, &block_config.player)
         });
 
         let mut players = Vec::<Player>::new();
@@ -320,13 +463,17 @@ impl ConfigBlock for Music {
             }
 
             // Add player
-            players.push(Player::new(&dbus_conn, name, bus_name));
+            insert_player(
+                &mut players,
+                Player::new(&dbus_conn, name, bus_name),
+                &block_config.player,
+            );
         }
 
         let players = Arc::new(Mutex::new(players));
         let players_clone = players.clone();
         let send_clone = send.clone();
-        let preferred_player = block_config.player.clone();
+        let preferred_players = block_config.player.clone();
 
         thread::Builder::new()
             .name("music".into())
@@ -354,12 +501,8 @@ impl ConfigBlock for Music {
                                 let sender = sender.to_string();
                                 if let Some(player) = players.iter_mut().find(|p| p.bus_name == sender) {
                                     if let Some(data) = prop_changed.changed_properties.get("Metadata") {
-                                        let (title, artist) = extract_from_metadata(&data.0).unwrap_or((None,None));
-                                        if player.title != title || player.artist != artist {
-                                            player.title = title;
-                                            player.artist = artist;
-                                            updated = true;
-                                        }
+                                        let metadata = extract_from_metadata(&data.0).unwrap_or_default();
+                                        updated |= player.set_metadata(metadata);
                                     }
                                     if let Some(data) = prop_changed.changed_properties.get("PlaybackStatus") {
                                         let new_playback = extract_playback_status(&data.0);
@@ -393,8 +536,8 @@ impl ConfigBlock for Music {
                                 match (old_owner, new_owner) {
                                     ("", new_owner) => { // Add a new player
                                         // Skip if already presented (or ignored)
-                                        if !players.iter().any(|p| p.bus_name == new_owner) && !ignored_player(name, &interface_name_exclude_regexps, preferred_player.clone()) {
-                                            players.push(Player::new(&dbus_conn,name,new_owner));
+                                        if !players.iter().any(|p| p.bus_name == new_owner) && !ignored_player(name, &interface_name_exclude_regexps, &preferred_players) {
+                                            insert_player(&mut players, Player::new(&dbus_conn, name, new_owner), &preferred_players);
                                             updated = true;
                                         }
                                     }
@@ -459,6 +602,8 @@ impl ConfigBlock for Music {
             };
         }
 
+        let format = FormatTemplate::from_string(&block_config.format)?;
+
         fn compile_regexps(patterns: Vec<String>) -> result::Result<Vec<Regex>, regex::Error> {
             patterns.iter().map(|p| Regex::new(&p)).collect()
         }
@@ -478,6 +623,7 @@ impl ConfigBlock for Music {
                 block_config.dynamic_width,
                 shared_config.clone(),
             )
+            .with_gap(&block_config.marquee_gap)
             .with_icon("music")?
             .with_state(State::Info)
             .with_spacing(Spacing::Hidden),
@@ -498,10 +644,12 @@ impl ConfigBlock for Music {
             max_width: block_config.max_width,
             separator: block_config.separator,
             seek_step: block_config.seek_step,
+            seek_on_click: block_config.seek_on_click,
             players,
             hide_when_empty: block_config.hide_when_empty,
             send,
-            format: FormatTemplate::from_string(&block_config.format)?,
+            shows_position: format.contains("position") || format.contains("percent"),
+            format,
             scrolling: shared_config.scrolling,
         })
     }
@@ -540,6 +688,11 @@ impl Block for Music {
         let player_name = split[3].to_string();
         let artist = metadata.clone().artist.unwrap_or_else(|| String::from(""));
         let title = metadata.clone().title.unwrap_or_else(|| String::from(""));
+        let position = if self.shows_position {
+            self.position(metadata).unwrap_or_default()
+        } else {
+            Duration::default()
+        };
         let combo =
             if (title.chars().count() + self.separator.chars().count() + artist.chars().count())
                 < self.max_width
@@ -550,14 +703,26 @@ impl Block for Music {
                 self.smart_trim(artist.clone(), title.clone())
             };
 
+        // RotatingTextWidget escapes its content itself
         let values = map!(
-            "artist" => Value::from_string(artist.clone()),
-            "title" => Value::from_string(title.clone()),
-            "combo" => Value::from_string(combo),
+            "artist" => Value::from_string(artist.clone()).markup(),
+            "title" => Value::from_string(title.clone()).markup(),
+            "combo" => Value::from_string(combo).markup(),
             //TODO
             //"vol" => volume,
-            "player" => Value::from_string(player_name),
+            "player" => Value::from_string(player_name).markup(),
             "avail" => Value::from_string(players.len().to_string()),
+            "art" => Value::from_string(
+                metadata.art.as_ref().map_or("", AlbumArt::location).to_string()
+            ).markup(),
+            "position" => Value::from_string(format_track_time(position)),
+            "length" => Value::from_string(
+                metadata
+                    .length
+                    .map(format_track_time)
+                    .unwrap_or_else(|| "--:--".to_string())
+            ),
+            "percent" => Value::from_integer(track_percents(position, metadata.length).round() as i64).percents(),
         );
 
         if !(rotation_in_progress) {
@@ -592,15 +757,24 @@ impl Block for Music {
 
         // If `marquee` is enabled then we need to schedule an update for the text rotation.
         // (time_to_next_rotation is always None if marquee is disabled)
-        if let Some(t) = time_to_next_rotation {
-            Ok(Some(Update::Every(t)))
+        let next_update = if let Some(t) = time_to_next_rotation {
+            Some(t)
         // We just finished a rotation so we wait before starting again
         } else if self.marquee {
-            Ok(Some(self.marquee_interval.into()))
+            Some(self.marquee_interval)
         // Otherwise we do not need to schedule anything as the block will auto-update itself after
         // seeing a PropertiesChanged signal for the MPRIS interface it is monitoring.
         } else {
-            Ok(None)
+            None
+        };
+
+        // ...except for the position, which only changes by itself while playing
+        if self.shows_position && metadata.playback_status == PlaybackStatus::Playing {
+            Ok(Some(Update::Every(
+                next_update.map_or(POSITION_INTERVAL, |t| t.min(POSITION_INTERVAL)),
+            )))
+        } else {
+            Ok(next_update.map(Update::Every))
         }
     }
 
@@ -636,12 +810,16 @@ impl Block for Music {
                             .block_error("music", "failed to call method via D-Bus")?;
                     } else if event_id == self.collapsed_id && self.on_collapsed_click.is_some() {
                         let cmd = self.on_collapsed_click.as_ref().unwrap();
-                        spawn_child_async("sh", &["-c", cmd])
+                        spawn_shell_async(cmd, &art_env(players.first()))
                             .block_error("music", "could not spawn child")?;
                     } else if event_id == self.id {
                         if let Some(ref cmd) = self.on_click {
-                            spawn_child_async("sh", &["-c", cmd])
+                            spawn_shell_async(cmd, &art_env(players.first()))
                                 .block_error("music", "could not spawn child")?;
+                        } else if self.seek_on_click {
+                            if let Some(player) = players.first() {
+                                self.seek_to_click(player, event)?;
+                            }
                         }
                     }
                 }
@@ -749,9 +927,8 @@ fn extract_artist_from_value(value: &dyn RefArg) -> Result<&str> {
     }
 }
 
-fn extract_from_metadata(metadata: &dyn RefArg) -> Result<(Option<String>, Option<String>)> {
-    let mut title = None;
-    let mut artist = None;
+fn extract_from_metadata(metadata: &dyn RefArg) -> Result<Metadata> {
+    let mut res = Metadata::default();
 
     let mut iter = metadata
         .as_iter()
@@ -765,30 +942,86 @@ fn extract_from_metadata(metadata: &dyn RefArg) -> Result<(Option<String>, Optio
             .as_str()
             .block_error("music", "failed to extract metadata")?
         {
-            "xesam:artist" => artist = Some(String::from(extract_artist_from_value(value)?)),
+            "xesam:artist" => res.artist = Some(String::from(extract_artist_from_value(value)?)),
             "xesam:title" => {
-                title = Some(String::from(
+                res.title = Some(String::from(
                     value
                         .as_str()
                         .block_error("music", "failed to extract metadata")?,
                 ))
             }
+            "mpris:artUrl" => res.art = value.as_str().and_then(AlbumArt::from_url),
+            "mpris:trackid" => res.track_id = value.as_str().map(String::from),
+            // Players disagree on the type of the length (in microseconds), streams have none
+            "mpris:length" => {
+                res.length = value
+                    .as_i64()
+                    .or_else(|| value.as_u64().map(|us| us as i64))
+                    .filter(|&us| us > 0)
+                    .map(|us| Duration::from_micros(us as u64))
+            }
             _ => {}
         };
     }
-    Ok((title, artist))
+    Ok(res)
+}
+
+/// The environment variables with the album art of `player` for the click commands
+fn art_env(player: Option<&Player>) -> [(&str, &str); 2] {
+    let (file, url) = match player.and_then(|p| p.art.as_ref()) {
+        Some(AlbumArt::File(path)) => (path.as_str(), ""),
+        Some(AlbumArt::Remote(url)) => ("", url.as_str()),
+        None => ("", ""),
+    };
+    [(ART_FILE_ENV, file), (ART_URL_ENV, url)]
+}
+
+/// Formats a position in a track like `3:07`, or `1:02:09` past an hour
+fn format_track_time(time: Duration) -> String {
+    let secs = time.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+/// How far `position` is into a track of `length`, in percents. Zero when the length is unknown.
+fn track_percents(position: Duration, length: Option<Duration>) -> f64 {
+    match length {
+        Some(length) if !length.is_zero() => {
+            (position.as_secs_f64() / length.as_secs_f64() * 100.).min(100.)
+        }
+        _ => 0.,
+    }
+}
+
+/// The position of the MPRIS interface `name` in `preferred_players`, if it is one of them
+fn player_priority(name: &str, preferred_players: &[String]) -> Option<usize> {
+    preferred_players
+        .iter()
+        .position(|p| name.starts_with(&format!("org.mpris.MediaPlayer2.{}", p)))
+}
+
+/// Adds `player` after the players that are preferred over it or just as much, so that the
+/// first player is always the most preferred one available.
+fn insert_player(players: &mut Vec<Player>, player: Player, preferred_players: &[String]) {
+    let priority = player_priority(&player.interface_name, preferred_players);
+    let pos = players
+        .iter()
+        .position(|p| player_priority(&p.interface_name, preferred_players) > priority)
+        .unwrap_or(players.len());
+    players.insert(pos, player);
 }
 
 fn ignored_player(
     name: &str,
     interface_name_exclude_regexps: &[Regex],
-    preferred_player: Option<String>,
+    preferred_players: &[String],
 ) -> bool {
-    // If the player is specified in the config then we will ignore all others.
-    if let Some(p) = preferred_player {
-        if !name.starts_with(&format!("org.mpris.MediaPlayer2.{}", p)) {
-            return true;
-        }
+    // If players are specified in the config then we will ignore all others.
+    if !preferred_players.is_empty() && player_priority(name, preferred_players).is_none() {
+        return true;
     }
 
     if !name.starts_with("org.mpris.MediaPlayer2") {
@@ -804,3 +1037,95 @@ fn ignored_player(
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(interface_name: &str) -> Player {
+        Player {
+            bus_name: format!(":1.{}", interface_name.len()),
+            interface_name: interface_name.to_string(),
+            playback_status: PlaybackStatus::Unknown,
+            artist: None,
+            title: None,
+            track_id: None,
+            length: None,
+            art: None,
+        }
+    }
+
+    #[test]
+    fn album_art() {
+        assert_eq!(
+            AlbumArt::from_url("file:///home/me/My%20Music/cover%2Ejpg"),
+            Some(AlbumArt::File("/home/me/My Music/cover.jpg".to_string()))
+        );
+        assert_eq!(
+            AlbumArt::from_url("https://i.scdn.co/image/ab67616d"),
+            Some(AlbumArt::Remote(
+                "https://i.scdn.co/image/ab67616d".to_string()
+            ))
+        );
+        assert_eq!(AlbumArt::from_url(""), None);
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%C3%A9"), "%zzé");
+    }
+
+    #[test]
+    fn track_times() {
+        assert_eq!(format_track_time(Duration::from_secs(0)), "0:00");
+        assert_eq!(format_track_time(Duration::from_secs(187)), "3:07");
+        assert_eq!(format_track_time(Duration::from_secs(3729)), "1:02:09");
+
+        let length = Some(Duration::from_secs(200));
+        assert_eq!(track_percents(Duration::from_secs(50), length), 25.);
+        assert_eq!(track_percents(Duration::from_secs(250), length), 100.);
+        assert_eq!(track_percents(Duration::from_secs(50), None), 0.);
+        assert_eq!(
+            track_percents(Duration::from_secs(50), Some(Duration::default())),
+            0.
+        );
+    }
+
+    #[test]
+    fn preferred_players() {
+        let preferred = vec!["spotify".to_string(), "mpd".to_string()];
+        assert!(ignored_player(
+            "org.mpris.MediaPlayer2.vlc",
+            &[],
+            &preferred
+        ));
+        assert!(!ignored_player(
+            "org.mpris.MediaPlayer2.mpd",
+            &[],
+            &preferred
+        ));
+        assert!(!ignored_player("org.mpris.MediaPlayer2.vlc", &[], &[]));
+        assert!(ignored_player("org.freedesktop.Notifications", &[], &[]));
+
+        let mut players = Vec::new();
+        for name in &[
+            "org.mpris.MediaPlayer2.mpd",
+            "org.mpris.MediaPlayer2.spotify",
+            "org.mpris.MediaPlayer2.mpd.instance2",
+        ] {
+            insert_player(&mut players, player(name), &preferred);
+        }
+        let names: Vec<&str> = players.iter().map(|p| &*p.interface_name).collect();
+        assert_eq!(
+            names,
+            [
+                "org.mpris.MediaPlayer2.spotify",
+                "org.mpris.MediaPlayer2.mpd",
+                "org.mpris.MediaPlayer2.mpd.instance2"
+            ]
+        );
+
+        // Without preferences the players are kept in the order they appeared
+        let mut players = Vec::new();
+        insert_player(&mut players, player("org.mpris.MediaPlayer2.mpd"), &[]);
+        insert_player(&mut players, player("org.mpris.MediaPlayer2.vlc"), &[]);
+        assert_eq!(players[1].interface_name, "org.mpris.MediaPlayer2.vlc");
+    }
+}
@@ -5,20 +5,23 @@ use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::{Duration, Instant};
 
+use chrono::Local;
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
+use log::warn;
 use regex::bytes::Regex;
-use serde_derive::Deserialize;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_opt_expanded};
 use crate::errors::*;
+use crate::formatting::prefix::BytePrefix;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{escape_pango_text, format_vec_to_bar_graph};
+use crate::util::{format_vec_to_bar_graph, graph_ramp};
 use crate::widgets::{text::TextWidget, I3BarWidget, Spacing};
 
 lazy_static! {
@@ -98,6 +101,27 @@ impl NetworkDevice {
         .ok()
     }
 
+    /// The device backing the default route, or `fallback` if there is none
+    pub fn default_device_or(fallback: &str) -> String {
+        match Self::default_device() {
+            Some(ref s) if !s.is_empty() => s.to_string(),
+            _ => fallback.to_string(),
+        }
+    }
+
+    /// The name of the icon for this kind of device
+    pub fn icon(&self) -> &'static str {
+        if self.is_wireless() {
+            "net_wireless"
+        } else if self.is_vpn() {
+            "net_vpn"
+        } else if self.device == "lo" {
+            "net_loopback"
+        } else {
+            "net_wired"
+        }
+    }
+
     /// Check whether the device exists.
     pub fn exists(&self) -> Result<bool> {
         Ok(self.device_path.exists())
@@ -154,7 +178,8 @@ impl NetworkDevice {
         self.tun || self.wg || self.ppp
     }
 
-    /// Queries the wireless SSID of this device, if it is connected to one.
+    /// Queries the wireless SSID, frequency (in Hz) and signal strength (in dBm) of this device,
+    /// if it is connected to one.
     pub fn wifi_info(&self) -> Result<(Option<String>, Option<f64>, Option<i64>)> {
         if !self.is_up()? || !self.wireless {
             return Ok((None, None, None));
@@ -175,11 +200,11 @@ impl NetworkDevice {
                         continue;
                     }
 
-                    let ssid = Some(escape_pango_text(decode_escaped_unicode(&ssid)));
+                    let ssid = Some(decode_escaped_unicode(&ssid));
                     let freq = interface
                         .frequency
                         .map(|f| nl80211::parse_u32(&f) as f64 * 1e6);
-                    let signal = ap.signal.map(|s| signal_percents(nl80211::parse_i8(&s)));
+                    let signal = ap.signal.map(|s| nl80211::parse_i8(&s) as i64);
 
                     return Ok((ssid, freq, signal));
                 }
@@ -304,6 +329,78 @@ impl NetworkDevice {
     }
 }
 
+/// A count of bytes as a Float, which is scaled with a prefix unlike Integers
+fn byte_count(bytes: u64, byte_prefix: BytePrefix) -> Value {
+    Value::from_float(bytes as f64)
+        .bytes()
+        .byte_prefix(byte_prefix)
+}
+
+/// The bytes sent and received since the block started (or since midnight)
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct DataUsage {
+    /// The day (`%Y-%m-%d`) the counting started
+    date: String,
+    up: u64,
+    down: u64,
+}
+
+impl DataUsage {
+    fn today() -> String {
+        Local::now().format("%Y-%m-%d").to_string()
+    }
+
+    /// Loads the usage from the state file, starting over if there is none yet
+    fn load(state_file: Option<&Path>) -> Self {
+        let usage =
+            state_file
+                .filter(|path| path.exists())
+                .and_then(|path| match read_to_string(path) {
+                    Ok(contents) => serde_json::from_str(&contents)
+                        .map_err(|e| warn!("net: ignoring state file '{}': {}", path.display(), e))
+                        .ok(),
+                    Err(e) => {
+                        warn!("net: failed to read state file '{}': {}", path.display(), e);
+                        None
+                    }
+                });
+        usage.unwrap_or_else(|| DataUsage {
+            date: Self::today(),
+            ..DataUsage::default()
+        })
+    }
+
+    fn save(&self, state_file: &Path) -> Result<()> {
+        let contents = serde_json::to_string(self).internal_error("net", "failed to encode")?;
+        std::fs::write(state_file, contents).block_error(
+            "net",
+            &format!("failed to write state file '{}'", state_file.display()),
+        )
+    }
+
+    /// Starts over if the day changed. Returns whether it did.
+    fn reset_if_new_day(&mut self, today: String) -> bool {
+        if self.date == today {
+            return false;
+        }
+        *self = DataUsage {
+            date: today,
+            ..DataUsage::default()
+        };
+        true
+    }
+}
+
+/// The bytes counted by an interface since `previous` was read. A counter that went down was
+/// reset (e.g. because the interface went down and up), so it counted `current` bytes since.
+fn counter_delta(previous: u64, current: u64) -> u64 {
+    if current < previous {
+        current
+    } else {
+        current - previous
+    }
+}
+
 pub struct Net {
     id: usize,
     format: FormatTemplate,
@@ -319,6 +416,7 @@ pub struct Net {
     update_interval: Duration,
     device: NetworkDevice,
     auto_device: bool,
+    fallback_device: String,
     tx_buff: Vec<f64>,
     rx_buff: Vec<f64>,
     tx_bytes: u64,
@@ -329,6 +427,12 @@ pub struct Net {
     hide_missing: bool,
     last_update: Instant,
     shared_config: SharedConfig,
+    byte_prefix: BytePrefix,
+    logarithmic_graph: bool,
+    usage: DataUsage,
+    reset_at_midnight: bool,
+    state_file: Option<PathBuf>,
+    last_saved: Instant,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -363,14 +467,30 @@ pub struct NetConfig {
 
     pub format_alt: Option<String>,
 
-    /// Which interface in /sys/class/net/ to read from.
+    /// Which interface in /sys/class/net/ to read from, or "auto" to follow the default route.
     pub device: Option<String>,
 
+    /// The interface to use while the default route can't be determined (with "auto").
+    pub fallback_device: Option<String>,
+
     /// Whether to hide networks that are down/inactive completely.
     pub hide_inactive: bool,
 
     /// Whether to hide networks that are missing.
     pub hide_missing: bool,
+
+    /// How byte values are scaled and labelled. Options are binary, si and iec
+    pub byte_prefix: BytePrefix,
+
+    /// Scale the graphs logarithmically instead of linearly
+    pub logarithmic_graph: bool,
+
+    /// Start counting `{total_up}` and `{total_down}` over at midnight
+    pub reset_totals_at_midnight: bool,
+
+    /// Keep `{total_up}` and `{total_down}` in this file across restarts
+    #[serde(deserialize_with = "deserialize_opt_expanded")]
+    pub state_file: Option<PathBuf>,
 }
 
 impl Default for NetConfig {
@@ -380,8 +500,13 @@ impl Default for NetConfig {
             format: "{speed_up;K} {speed_down;K}".to_string(),
             format_alt: None,
             device: None,
+            fallback_device: None,
             hide_inactive: false,
             hide_missing: false,
+            byte_prefix: BytePrefix::default(),
+            logarithmic_graph: false,
+            reset_totals_at_midnight: false,
+            state_file: None,
         }
     }
 }
@@ -395,18 +520,17 @@ impl ConfigBlock for Net {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let default_device = match NetworkDevice::default_device() {
-            Some(ref s) if !s.is_empty() => s.to_string(),
-            _ => "lo".to_string(),
-        };
-        let device = match block_config.device.clone() {
-            Some(d) => NetworkDevice::from_device(d),
-            _ => NetworkDevice::from_device(default_device),
+        let fallback_device = block_config
+            .fallback_device
+            .unwrap_or_else(|| "lo".to_string());
+        let auto_device = matches!(block_config.device.as_deref(), None | Some("auto"));
+        let device = if auto_device {
+            NetworkDevice::from_device(NetworkDevice::default_device_or(&fallback_device))
+        } else {
+            NetworkDevice::from_device(block_config.device.unwrap())
         };
         let init_rx_bytes = device.rx_bytes().unwrap_or(0);
         let init_tx_bytes = device.tx_bytes().unwrap_or(0);
-        let wireless = device.is_wireless();
-        let vpn = device.is_vpn();
 
         let format = FormatTemplate::from_string(&block_config.format)?;
         let format_alt = if let Some(f) = block_config.format_alt {
@@ -419,15 +543,7 @@ impl ConfigBlock for Net {
             id,
             update_interval: block_config.interval,
             output: TextWidget::new(id, 0, shared_config.clone())
-                .with_icon(if wireless {
-                    "net_wireless"
-                } else if vpn {
-                    "net_vpn"
-                } else if device.device == "lo" {
-                    "net_loopback"
-                } else {
-                    "net_wired"
-                })?
+                .with_icon(device.icon())?
                 .with_text("")
                 .with_spacing(Spacing::Inline),
             // TODO: a better way to deal with this?
@@ -454,7 +570,8 @@ impl ConfigBlock for Net {
             graph_tx: String::new(),
             graph_rx: String::new(),
             device,
-            auto_device: block_config.device.is_none(),
+            auto_device,
+            fallback_device,
             rx_buff: vec![0.; 10],
             tx_buff: vec![0.; 10],
             rx_bytes: init_rx_bytes,
@@ -463,6 +580,12 @@ impl ConfigBlock for Net {
             exists: true,
             hide_inactive: block_config.hide_inactive,
             hide_missing: block_config.hide_missing,
+            byte_prefix: block_config.byte_prefix,
+            logarithmic_graph: block_config.logarithmic_graph,
+            usage: DataUsage::load(block_config.state_file.as_deref()),
+            reset_at_midnight: block_config.reset_totals_at_midnight,
+            state_file: block_config.state_file,
+            last_saved: Instant::now(),
             last_update: Instant::now() - Duration::from_secs(30),
             shared_config,
             format,
@@ -517,8 +640,13 @@ impl Net {
             + (self.update_interval.subsec_nanos() as f64 / 1_000_000_000.0);
 
         // Update the throughput/graph widgets if they are enabled
+        if self.reset_at_midnight && self.usage.reset_if_new_day(DataUsage::today()) {
+            self.save_usage()?;
+        }
+
         let current_tx = self.device.tx_bytes()?;
-        let diff = current_tx.saturating_sub(self.tx_bytes);
+        let diff = counter_delta(self.tx_bytes, current_tx);
+        self.usage.up += diff;
         let tx_bytes = (diff as f64 / update_interval) as u64;
         self.tx_bytes = current_tx;
 
@@ -526,10 +654,17 @@ impl Net {
 
         self.tx_buff.remove(0);
         self.tx_buff.push(tx_bytes as f64);
-        self.graph_tx = format_vec_to_bar_graph(&self.tx_buff, None, None);
+        self.graph_tx = format_vec_to_bar_graph(
+            &self.tx_buff,
+            None,
+            None,
+            &graph_ramp(&self.shared_config.theme),
+            self.logarithmic_graph,
+        );
 
         let current_rx = self.device.rx_bytes()?;
-        let diff = current_rx.saturating_sub(self.rx_bytes);
+        let diff = counter_delta(self.rx_bytes, current_rx);
+        self.usage.down += diff;
         let rx_bytes = (diff as f64 / update_interval) as u64;
         self.rx_bytes = current_rx;
 
@@ -537,32 +672,46 @@ impl Net {
 
         self.rx_buff.remove(0);
         self.rx_buff.push(rx_bytes as f64);
-        self.graph_rx = format_vec_to_bar_graph(&self.rx_buff, None, None);
+        self.graph_rx = format_vec_to_bar_graph(
+            &self.rx_buff,
+            None,
+            None,
+            &graph_ramp(&self.shared_config.theme),
+            self.logarithmic_graph,
+        );
+
+        // Don't write the state file on every update
+        if self.last_saved.elapsed() >= Duration::from_secs(60) {
+            self.save_usage()?;
+        }
 
         Ok(())
     }
+
+    fn save_usage(&mut self) -> Result<()> {
+        self.last_saved = Instant::now();
+        match &self.state_file {
+            Some(state_file) => self.usage.save(state_file),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Block for Net {
     fn update(&mut self) -> Result<Option<Update>> {
         // Update device
         if self.auto_device {
-            let dev = match NetworkDevice::default_device() {
-                Some(ref s) if !s.is_empty() => s.to_string(),
-                _ => "lo".to_string(),
-            };
+            let dev = NetworkDevice::default_device_or(&self.fallback_device);
 
             if self.device.device() != dev {
                 self.device = NetworkDevice::from_device(dev);
-                self.output.set_icon(if self.device.is_wireless() {
-                    "net_wireless"
-                } else if self.device.is_vpn() {
-                    "net_vpn"
-                } else if self.device.device == "lo" {
-                    "net_loopback"
-                } else {
-                    "net_wired"
-                })?;
+                self.output.set_icon(self.device.icon())?;
+                // Start over with the counters and addresses of the new device
+                self.rx_bytes = self.device.rx_bytes().unwrap_or(0);
+                self.tx_bytes = self.device.tx_bytes().unwrap_or(0);
+                self.ip_addr = self.ip_addr.as_ref().map(|_| String::new());
+                self.ipv6_addr = self.ipv6_addr.as_ref().map(|_| String::new());
+                self.bitrate = self.bitrate.as_ref().map(|_| String::new());
             }
         }
 
@@ -605,19 +754,30 @@ impl Block for Net {
         let (ssid, freq, signal) = self.device.wifi_info()?;
 
         let empty_string = "".to_string();
-        let na_string = "N/A".to_string();
+        // The wireless placeholders are empty for wired devices
+        let missing = || {
+            Value::from_string(if self.device.is_wireless() {
+                "N/A".to_string()
+            } else {
+                String::new()
+            })
+        };
 
         let values = map!(
-            "ssid" => Value::from_string(ssid.clone().unwrap_or(na_string)),
-            "signal_strength" => Value::from_integer(signal.unwrap_or(0)).percents(),
-            "frequency" => Value::from_float(freq.unwrap_or(0.)).hertz(),
+            "device" => Value::from_string(self.device.device()),
+            "ssid" => ssid.map(Value::from_string).unwrap_or_else(missing),
+            "signal_strength" => signal.map(|s| Value::from_integer(signal_percents(s as i8)).percents()).unwrap_or_else(missing),
+            "signal_dbm" => signal.map(Value::from_integer).unwrap_or_else(missing),
+            "frequency" => freq.map(|f| Value::from_float(f).hertz()).unwrap_or_else(missing),
             "bitrate" => Value::from_string(self.bitrate.clone().unwrap_or_else(|| empty_string.clone())), // TODO: not a String?
             "ip" => Value::from_string(self.ip_addr.clone().unwrap_or_else(|| empty_string.clone())),
             "ipv6" => Value::from_string(self.ipv6_addr.clone().unwrap_or(empty_string)),
-            "speed_up" => Value::from_float(self.speed_up).bytes().icon(self.shared_config.get_icon("net_up")?),
-            "speed_down" => Value::from_float(self.speed_down).bytes().icon(self.shared_config.get_icon("net_down")?),
+            "speed_up" => Value::from_float(self.speed_up).bytes().byte_prefix(self.byte_prefix).icon(self.shared_config.get_icon("net_up")?),
+            "speed_down" => Value::from_float(self.speed_down).bytes().byte_prefix(self.byte_prefix).icon(self.shared_config.get_icon("net_down")?),
             "graph_up" => Value::from_string(self.graph_tx.clone()),
             "graph_down" => Value::from_string(self.graph_rx.clone()),
+            "total_up" => byte_count(self.usage.up, self.byte_prefix),
+            "total_down" => byte_count(self.usage.down, self.byte_prefix),
         );
 
         self.output.set_text(self.format.render(&values)?);
@@ -693,7 +853,21 @@ fn signal_percents(raw: i8) -> i64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::blocks::net::decode_escaped_unicode;
+    use crate::blocks::net::{
+        byte_count, counter_delta, decode_escaped_unicode, signal_percents, DataUsage,
+    };
+    use crate::formatting::prefix::BytePrefix;
+    use crate::formatting::FormatTemplate;
+
+    #[test]
+    fn totals_are_scaled() {
+        let format = FormatTemplate::from_string("{total_up;M} {total_down}").unwrap();
+        let values = map!(
+            "total_up" => byte_count(123_456_789, BytePrefix::Si),
+            "total_down" => byte_count(2048, BytePrefix::Iec),
+        );
+        assert_eq!(format.render(&values).unwrap(), "123MB 2.0KiB");
+    }
 
     #[test]
     fn test_ssid_decode_escaped_unicode() {
@@ -726,4 +900,41 @@ mod tests {
             r" surrounded by spaces ".to_string()
         );
     }
+
+    #[test]
+    fn test_counter_delta() {
+        assert_eq!(counter_delta(100, 150), 50);
+        assert_eq!(counter_delta(100, 100), 0);
+        // The interface went down and up
+        assert_eq!(counter_delta(1000, 30), 30);
+    }
+
+    #[test]
+    fn test_usage_reset_at_midnight() {
+        let mut usage = DataUsage {
+            date: "2021-05-01".to_string(),
+            up: 10,
+            down: 20,
+        };
+        assert!(!usage.reset_if_new_day("2021-05-01".to_string()));
+        assert_eq!(usage.down, 20);
+        assert!(usage.reset_if_new_day("2021-05-02".to_string()));
+        assert_eq!(
+            (usage.date.as_str(), usage.up, usage.down),
+            ("2021-05-02", 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_signal_percents() {
+        assert_eq!(signal_percents(-20), 100);
+        assert_eq!(signal_percents(-10), 100);
+        assert_eq!(signal_percents(-100), 0);
+        let (good, fair, bad) = (
+            signal_percents(-40),
+            signal_percents(-60),
+            signal_percents(-85),
+        );
+        assert!(100 > good && good > fair && fair > bad && bad > 0);
+    }
 }
@@ -1,8 +1,10 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Instant;
 
 use crossbeam_channel::Sender;
+use dbus::arg::{RefArg, Variant};
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
 use dbus::ffidisp::{BusType, Connection};
 use dbus::message::SignalArgs;
@@ -18,6 +20,27 @@ use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
+/// Shows a desktop notification with `summary` and `body`, from the notification daemon
+pub fn send_notification(summary: &str, body: &str) -> Result<()> {
+    let c = Connection::get_private(BusType::Session)
+        .block_error("notify", "Failed to establish D-Bus connection")?;
+    let hints: HashMap<&str, Variant<Box<dyn RefArg>>> = HashMap::new();
+    let msg = dbus::Message::new_method_call(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        "org.freedesktop.Notifications",
+        "Notify",
+    )
+    .block_error("notify", "Failed to create D-Bus message")?
+    // app_name, replaces_id, app_icon, summary, body, actions, hints, expire_timeout
+    .append3("i3status-rs", 0u32, "")
+    .append3(summary, body, Vec::<&str>::new())
+    .append2(hints, -1i32);
+    c.send_with_reply_and_block(msg, 1000)
+        .block_error("notify", "Failed to send the notification")
+        .map(|_| ())
+}
+
 // TODO
 // Add driver option so can choose between dunst, mako, etc.
 
@@ -1,4 +1,6 @@
 use std::env;
+#[cfg(not(feature = "notmuch"))]
+use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -6,8 +8,10 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_expanded};
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
@@ -24,6 +28,7 @@ pub struct Notmuch {
     threshold_warning: u32,
     threshold_critical: u32,
     name: Option<String>,
+    format: FormatTemplate,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -32,6 +37,7 @@ pub struct NotmuchConfig {
     /// Update interval in seconds
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
+    #[serde(deserialize_with = "deserialize_expanded")]
     pub maildir: String,
     pub query: String,
     pub threshold_warning: u32,
@@ -40,6 +46,8 @@ pub struct NotmuchConfig {
     pub threshold_good: u32,
     pub name: Option<String>,
     pub no_icon: bool,
+    /// Format override
+    pub format: Option<String>,
 }
 
 impl Default for NotmuchConfig {
@@ -61,14 +69,42 @@ impl Default for NotmuchConfig {
             threshold_good: std::u32::MAX,
             name: None,
             no_icon: false,
+            format: None,
         }
     }
 }
 
-fn run_query(db_path: &str, query_string: &str) -> std::result::Result<u32, notmuch::Error> {
-    let db = notmuch::Database::open(&db_path, notmuch::DatabaseMode::ReadOnly)?;
-    let query = db.create_query(query_string)?;
-    Ok(query.count_messages()?)
+#[cfg(feature = "notmuch")]
+fn run_query(db_path: &str, query_string: &str) -> Result<u32> {
+    let count = || -> std::result::Result<u32, notmuch::Error> {
+        let db = notmuch::Database::open(&db_path, notmuch::DatabaseMode::ReadOnly)?;
+        let query = db.create_query(query_string)?;
+        query.count_messages()
+    };
+    count().map_err(|e| BlockError("notmuch".to_string(), e.to_string()))
+}
+
+/// Without libnotmuch, the messages are counted with `notmuch count`
+#[cfg(not(feature = "notmuch"))]
+fn run_query(db_path: &str, query_string: &str) -> Result<u32> {
+    let mut command = Command::new("notmuch");
+    command.arg("count").env("NOTMUCH_DATABASE", db_path);
+    if !query_string.is_empty() {
+        command.arg(query_string);
+    }
+    let output = command
+        .output()
+        .block_error("notmuch", "failed to run notmuch")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "notmuch".to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .block_error("notmuch", "could not parse the output of notmuch count")
 }
 
 impl ConfigBlock for Notmuch {
@@ -84,6 +120,13 @@ impl ConfigBlock for Notmuch {
         if !block_config.no_icon {
             widget.set_icon("mail")?;
         }
+        let default_format = match block_config.name {
+            Some(_) => "{name}:{count}",
+            None => "{count}",
+        };
+        let format = block_config
+            .format
+            .unwrap_or_else(|| default_format.to_string());
         Ok(Notmuch {
             id,
             update_interval: block_config.interval,
@@ -94,18 +137,22 @@ impl ConfigBlock for Notmuch {
             threshold_warning: block_config.threshold_warning,
             threshold_critical: block_config.threshold_critical,
             name: block_config.name,
+            format: FormatTemplate::from_string(&format)
+                .block_error("notmuch", "Invalid format specified")?,
             text: widget,
         })
     }
 }
 
 impl Notmuch {
-    fn update_text(&mut self, count: u32) {
-        let text = match self.name {
-            Some(ref s) => format!("{}:{}", s, count),
-            _ => format!("{}", count),
-        };
-        self.text.set_text(text);
+    fn update_text(&mut self, count: u32) -> Result<()> {
+        let values = map!(
+            "count" => Value::from_integer(count as i64),
+            "query" => Value::from_string(self.query.clone()),
+            "name" => Value::from_string(self.name.clone().unwrap_or_default()),
+        );
+        self.text.set_text(self.format.render(&values)?);
+        Ok(())
     }
 
     fn update_state(&mut self, count: u32) {
@@ -125,14 +172,10 @@ impl Notmuch {
 
 impl Block for Notmuch {
     fn update(&mut self) -> Result<Option<Update>> {
-        match run_query(&self.db, &self.query) {
-            Ok(count) => {
-                self.update_text(count);
-                self.update_state(count);
-                Ok(Some(self.update_interval.into()))
-            }
-            Err(e) => Err(BlockError("notmuch".to_string(), e.to_string())),
-        }
+        let count = run_query(&self.db, &self.query)?;
+        self.update_text(count)?;
+        self.update_state(count);
+        Ok(Some(self.update_interval.into()))
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
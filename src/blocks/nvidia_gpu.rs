@@ -4,11 +4,14 @@ use std::time::Duration;
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
+use crate::blocks::temperature::Aggregation;
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::config::{LogicalDirection, Scrolling};
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_one_or_many};
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::util::pseudo_uuid;
@@ -23,6 +26,11 @@ pub struct NvidiaGpu {
 
     gpu_enabled: bool,
     gpu_id: u64,
+    /// All the GPUs of `gpu_id`, shown with `format`
+    gpu_ids: Vec<u64>,
+    format: Option<FormatTemplate>,
+    output: TextWidget,
+    aggregation: Aggregation,
 
     name_widget: TextWidget,
     name_widget_mode: NameWidgetMode,
@@ -59,7 +67,6 @@ enum NameWidgetMode {
     ShowLabel,
 }
 
-// TODO add `format` option
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct NvidiaGpuConfig {
@@ -70,8 +77,15 @@ pub struct NvidiaGpuConfig {
     /// Label to show instead of the default GPU name from `nvidia-smi`
     pub label: Option<String>,
 
-    /// GPU id in system
-    pub gpu_id: u64,
+    /// GPU ids in system. Without `format` only the first one is shown.
+    #[serde(deserialize_with = "deserialize_one_or_many")]
+    pub gpu_id: Vec<u64>,
+
+    /// Format string showing all the GPUs of `gpu_id`, instead of the widgets of the first one
+    pub format: Option<String>,
+
+    /// How the temperatures and utilizations of the GPUs are combined in `format`
+    pub aggregation: Aggregation,
 
     /// GPU utilization. In percent.
     pub show_utilization: bool,
@@ -109,7 +123,9 @@ impl Default for NvidiaGpuConfig {
         Self {
             interval: Duration::from_secs(3),
             label: None,
-            gpu_id: 0,
+            gpu_id: vec![0],
+            format: None,
+            aggregation: Aggregation::default(),
             show_utilization: true,
             show_memory: true,
             show_temperature: true,
@@ -135,6 +151,9 @@ impl ConfigBlock for NvidiaGpu {
     ) -> Result<Self> {
         let id_memory = pseudo_uuid();
         let id_fans = pseudo_uuid();
+        let gpu_id = *block_config.gpu_id.first().ok_or_else(|| {
+            ConfigurationError("nvidia_gpu".to_string(), "`gpu_id` is empty".to_string())
+        })?;
 
         Ok(NvidiaGpu {
             id,
@@ -142,7 +161,15 @@ impl ConfigBlock for NvidiaGpu {
             id_memory,
             update_interval: block_config.interval,
             gpu_enabled: false,
-            gpu_id: block_config.gpu_id,
+            gpu_id,
+            gpu_ids: block_config.gpu_id,
+            format: block_config
+                .format
+                .as_deref()
+                .map(FormatTemplate::from_string)
+                .transpose()?,
+            output: TextWidget::new(id, id, shared_config.clone()).with_icon("gpu")?,
+            aggregation: block_config.aggregation,
 
             name_widget: TextWidget::new(id, id, shared_config.clone())
                 .with_icon("gpu")?
@@ -208,8 +235,229 @@ impl ConfigBlock for NvidiaGpu {
     }
 }
 
+/// What `nvidia-smi` reports about a GPU for `format`. The values a GPU doesn't support (like
+/// the fan speed of passively cooled ones) are `None`.
+#[derive(Debug, Clone, PartialEq)]
+struct GpuInfo {
+    index: u64,
+    name: String,
+    utilization: Option<u64>,
+    memory_used: Option<u64>,
+    memory_total: Option<u64>,
+    temperature: Option<u64>,
+    fan_speed: Option<u64>,
+    graphics_clock: Option<u64>,
+    memory_clock: Option<u64>,
+    power_draw: Option<f64>,
+    power_limit: Option<f64>,
+}
+
+impl GpuInfo {
+    /// The `--query-gpu` fields, in the order `parse` expects them
+    const QUERY: &'static str = "index,name,utilization.gpu,memory.used,memory.total,\
+        temperature.gpu,fan.speed,clocks.current.graphics,clocks.current.memory,power.draw,\
+        power.limit";
+
+    /// Parses a line of `nvidia-smi --format=csv,noheader,nounits` for `QUERY`
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(", ").map(str::trim).collect();
+        if fields.len() != 11 {
+            return None;
+        }
+        // Unsupported values are "[N/A]" or "[Not Supported]"
+        fn number<T: std::str::FromStr>(field: &str) -> Option<T> {
+            field.parse().ok()
+        }
+        Some(GpuInfo {
+            index: number(fields[0])?,
+            name: fields[1].to_string(),
+            utilization: number(fields[2]),
+            memory_used: number(fields[3]),
+            memory_total: number(fields[4]),
+            temperature: number(fields[5]),
+            fan_speed: number(fields[6]),
+            graphics_clock: number(fields[7]),
+            memory_clock: number(fields[8]),
+            power_draw: number(fields[9]),
+            power_limit: number(fields[10]),
+        })
+    }
+}
+
+fn or_na(value: Option<Value>) -> Value {
+    value.unwrap_or_else(|| Value::from_string("N/A".to_string()))
+}
+
+fn percents_value(value: u64) -> Value {
+    Value::from_integer(value as i64).percents()
+}
+
+fn degrees_value(value: u64) -> Value {
+    Value::from_integer(value as i64).degrees()
+}
+
+/// `nvidia-smi` reports memory in MiB
+fn mebibytes_value(value: u64) -> Value {
+    Value::from_float(value as f64 * 1024. * 1024.).bytes()
+}
+
+/// `nvidia-smi` reports clocks in MHz
+fn megahertz_value(value: u64) -> Value {
+    Value::from_float(value as f64 * 1e6).hertz()
+}
+
+fn watts_value(value: f64) -> Value {
+    Value::from_float(value).watts()
+}
+
+/// The sum of the values of the GPUs reporting one, or `None` if none does
+fn sum(values: impl Iterator<Item = f64>) -> Option<f64> {
+    values.fold(None, |sum, value| Some(sum.unwrap_or(0.) + value))
+}
+
+/// Combines the values of the GPUs reporting one
+fn aggregate(aggregation: Aggregation, values: &[u64]) -> Option<u64> {
+    match aggregation {
+        Aggregation::Max => values.iter().copied().max(),
+        Aggregation::Min => values.iter().copied().min(),
+        Aggregation::Avg if values.is_empty() => None,
+        Aggregation::Avg => {
+            Some((values.iter().sum::<u64>() as f64 / values.len() as f64).round() as u64)
+        }
+    }
+}
+
+impl NvidiaGpu {
+    fn temperature_state(&self, temp: u64) -> State {
+        match temp {
+            t if t <= self.maximum_idle => State::Idle,
+            t if t <= self.maximum_good => State::Good,
+            t if t <= self.maximum_info => State::Info,
+            t if t <= self.maximum_warning => State::Warning,
+            _ => State::Critical,
+        }
+    }
+
+    /// Renders `format` with all the GPUs of `gpu_ids`. GPUs missing from the output of
+    /// `nvidia-smi` (such as one that fell off the bus) are left out, their placeholders
+    /// are `N/A`.
+    fn update_format(&mut self) -> Result<()> {
+        let handle = Command::new("nvidia-smi")
+            .args([
+                &format!("--query-gpu={}", GpuInfo::QUERY),
+                "--format=csv,noheader,nounits",
+            ])
+            .output()
+            .block_error("gpu", "Failed to execute nvidia-smi.")?;
+        let gpus: Vec<GpuInfo> = if handle.status.success() {
+            String::from_utf8_lossy(&handle.stdout)
+                .lines()
+                .filter_map(GpuInfo::parse)
+                .filter(|gpu| self.gpu_ids.contains(&gpu.index))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        self.gpu_enabled = !gpus.is_empty();
+        if !self.gpu_enabled {
+            self.output.set_text("DISABLED".to_string());
+            self.output.set_state(State::Idle);
+            return Ok(());
+        }
+
+        let collect =
+            |f: fn(&GpuInfo) -> Option<u64>| -> Vec<u64> { gpus.iter().filter_map(f).collect() };
+        let temperature = aggregate(self.aggregation, &collect(|gpu| gpu.temperature));
+        let utilization = aggregate(self.aggregation, &collect(|gpu| gpu.utilization));
+        let memory_used: u64 = collect(|gpu| gpu.memory_used).iter().sum();
+        let memory_total: u64 = collect(|gpu| gpu.memory_total).iter().sum();
+        let fan_speed = collect(|gpu| gpu.fan_speed).into_iter().max();
+        let graphics_clock = collect(|gpu| gpu.graphics_clock).into_iter().max();
+        let memory_clock = collect(|gpu| gpu.memory_clock).into_iter().max();
+        let power_draw = sum(gpus.iter().filter_map(|gpu| gpu.power_draw));
+        let power_limit = sum(gpus.iter().filter_map(|gpu| gpu.power_limit));
+
+        let mut values = map_to_owned!(
+            "name" => Value::from_string(gpus[0].name.clone()),
+            "utilization" => or_na(utilization.map(percents_value)),
+            "memory" => mebibytes_value(memory_used),
+            "memory_total" => mebibytes_value(memory_total),
+            "temperature" => or_na(temperature.map(degrees_value)),
+            "fan" => or_na(fan_speed.map(percents_value)),
+            "graphics_clock" => or_na(graphics_clock.map(megahertz_value)),
+            "memory_clock" => or_na(memory_clock.map(megahertz_value)),
+            "power_draw" => or_na(power_draw.map(watts_value)),
+            "power_limit" => or_na(power_limit.map(watts_value))
+        );
+        for &index in &self.gpu_ids {
+            let gpu = gpus.iter().find(|gpu| gpu.index == index);
+            let field = |f: fn(&GpuInfo) -> Option<u64>| gpu.and_then(f);
+            let gpu_values = vec![
+                (
+                    "name",
+                    or_na(gpu.map(|gpu| Value::from_string(gpu.name.clone()))),
+                ),
+                (
+                    "utilization",
+                    or_na(field(|gpu| gpu.utilization).map(percents_value)),
+                ),
+                (
+                    "memory",
+                    or_na(field(|gpu| gpu.memory_used).map(mebibytes_value)),
+                ),
+                (
+                    "memory_total",
+                    or_na(field(|gpu| gpu.memory_total).map(mebibytes_value)),
+                ),
+                (
+                    "temperature",
+                    or_na(field(|gpu| gpu.temperature).map(degrees_value)),
+                ),
+                ("fan", or_na(field(|gpu| gpu.fan_speed).map(percents_value))),
+                (
+                    "graphics_clock",
+                    or_na(field(|gpu| gpu.graphics_clock).map(megahertz_value)),
+                ),
+                (
+                    "memory_clock",
+                    or_na(field(|gpu| gpu.memory_clock).map(megahertz_value)),
+                ),
+                (
+                    "power_draw",
+                    or_na(gpu.and_then(|gpu| gpu.power_draw).map(watts_value)),
+                ),
+                (
+                    "power_limit",
+                    or_na(gpu.and_then(|gpu| gpu.power_limit).map(watts_value)),
+                ),
+            ];
+            for (key, value) in gpu_values {
+                values.insert(format!("{}{}", key, index), value);
+            }
+        }
+        let values = values
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.clone()))
+            .collect();
+
+        let format = self
+            .format
+            .as_ref()
+            .internal_error("nvidia_gpu", "no format")?;
+        self.output.set_text(format.render(&values)?);
+        let state = temperature.map_or(State::Idle, |t| self.temperature_state(t));
+        self.output.set_state(state);
+        Ok(())
+    }
+}
+
 impl Block for NvidiaGpu {
     fn update(&mut self) -> Result<Option<Update>> {
+        if self.format.is_some() {
+            self.update_format()?;
+            return Ok(Some(self.update_interval.into()));
+        }
+
         let mut params = String::from("name,memory.total,");
         if self.show_utilization.is_some() {
             params += "utilization.gpu,";
@@ -330,6 +578,10 @@ impl Block for NvidiaGpu {
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.format.is_some() {
+            return vec![&self.output];
+        }
+
         let mut widgets: Vec<&dyn I3BarWidget> = Vec::new();
         widgets.push(&self.name_widget);
 
@@ -357,6 +609,9 @@ impl Block for NvidiaGpu {
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
+        if self.format.is_some() {
+            return Ok(());
+        }
         if let Some(event_id) = e.instance {
             if event_id == self.id {
                 if let MouseButton::Left = e.button {
@@ -464,3 +719,40 @@ impl Block for NvidiaGpu {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_gpu_info() {
+        let gpu = GpuInfo::parse(
+            "1, NVIDIA GeForce RTX 3090, 42, 1024, 24576, 65, [N/A], 1695, 9751, 112.47, \
+             [Not Supported]",
+        )
+        .unwrap();
+        assert_eq!(gpu.index, 1);
+        assert_eq!(gpu.name, "NVIDIA GeForce RTX 3090");
+        assert_eq!(gpu.utilization, Some(42));
+        assert_eq!(gpu.memory_total, Some(24576));
+        assert_eq!(gpu.temperature, Some(65));
+        assert_eq!(gpu.fan_speed, None);
+        assert_eq!(gpu.graphics_clock, Some(1695));
+        assert_eq!(gpu.memory_clock, Some(9751));
+        assert_eq!(gpu.power_draw, Some(112.47));
+        assert_eq!(gpu.power_limit, None);
+
+        assert_eq!(GpuInfo::parse("No devices were found"), None);
+        assert_eq!(GpuInfo::parse(""), None);
+    }
+
+    #[test]
+    fn aggregate_gpus() {
+        assert_eq!(aggregate(Aggregation::Max, &[60, 75]), Some(75));
+        assert_eq!(aggregate(Aggregation::Min, &[60, 75]), Some(60));
+        assert_eq!(aggregate(Aggregation::Avg, &[60, 75]), Some(68));
+        assert_eq!(aggregate(Aggregation::Avg, &[]), None);
+        assert_eq!(sum([100.5, 50.].iter().copied()), Some(150.5));
+        assert_eq!(sum(std::iter::empty()), None);
+    }
+}
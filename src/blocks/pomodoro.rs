@@ -4,77 +4,131 @@ use std::time::{Duration, Instant};
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
+use crate::blocks::notify::send_notification;
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::de::deserialize_expanded;
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Phase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Work => write!(f, "work"),
+            Phase::ShortBreak => write!(f, "short break"),
+            Phase::LongBreak => write!(f, "long break"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum State {
-    Started(Instant),
     Stopped,
-    Paused(Duration),
-    OnBreak(Instant),
+    Running(Phase, Instant),
+    Paused(Phase, Duration),
 }
 
 impl State {
+    fn phase(&self) -> Option<Phase> {
+        match self {
+            State::Stopped => None,
+            State::Running(phase, _) | State::Paused(phase, _) => Some(*phase),
+        }
+    }
+
     fn elapsed(&self) -> Duration {
         match self {
-            State::Started(start) => Instant::now().duration_since(start.to_owned()),
-            State::Stopped => unreachable!(),
-            State::Paused(duration) => duration.to_owned(),
-            State::OnBreak(start) => Instant::now().duration_since(start.to_owned()),
+            State::Stopped => Duration::from_secs(0),
+            State::Running(_, start) => start.elapsed(),
+            State::Paused(_, elapsed) => *elapsed,
         }
     }
-}
 
-impl fmt::Display for State {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    /// The glyph shown by `{state}`
+    fn glyph(&self) -> &'static str {
         match self {
-            State::Stopped => write!(f, "\u{25a0} 0:00"),
-            State::Started(_) => write!(
-                f,
-                "\u{f04b} {}:{:02}",
-                self.elapsed().as_secs() / 60,
-                self.elapsed().as_secs() % 60
-            ),
-            State::OnBreak(_) => write!(
-                f,
-                "\u{2615} {}:{:02}",
-                self.elapsed().as_secs() / 60,
-                self.elapsed().as_secs() % 60
-            ),
-            State::Paused(duration) => write!(
-                f,
-                "\u{f04c} {}:{:02}",
-                duration.as_secs() / 60,
-                duration.as_secs() % 60
-            ),
+            State::Stopped => "\u{25a0}",
+            State::Running(Phase::Work, _) => "\u{f04b}",
+            State::Running(_, _) => "\u{2615}",
+            State::Paused(_, _) => "\u{f04c}",
         }
     }
 }
 
+/// Formats `duration` as `m:ss`
+fn format_duration(duration: Duration) -> String {
+    format!("{}:{:02}", duration.as_secs() / 60, duration.as_secs() % 60)
+}
+
 pub struct Pomodoro {
     id: usize,
     time: TextWidget,
     state: State,
+    format: FormatTemplate,
     length: Duration,
     break_length: Duration,
+    long_break_length: Duration,
+    long_break_after: usize,
     update_interval: Duration,
     message: String,
     break_message: String,
     count: usize,
     use_nag: bool,
     nag_path: std::path::PathBuf,
+    notify: bool,
 }
 
 impl Pomodoro {
-    fn set_text(&mut self) {
-        self.time
-            .set_text(format!("{} | {}", self.count, self.state));
+    fn phase_length(&self, phase: Phase) -> Duration {
+        match phase {
+            Phase::Work => self.length,
+            Phase::ShortBreak => self.break_length,
+            Phase::LongBreak => self.long_break_length,
+        }
+    }
+
+    /// The break after the `count`th pomodoro
+    fn break_after(&self, count: usize) -> Phase {
+        if self.long_break_after > 0 && count.is_multiple_of(self.long_break_after) {
+            Phase::LongBreak
+        } else {
+            Phase::ShortBreak
+        }
+    }
+
+    fn set_text(&mut self) -> Result<()> {
+        let remaining = match self.state.phase() {
+            Some(phase) => self
+                .phase_length(phase)
+                .saturating_sub(self.state.elapsed()),
+            None => self.length,
+        };
+        let phase = match self.state.phase() {
+            Some(phase) => phase.to_string(),
+            None => "stopped".to_string(),
+        };
+        let values = map!(
+            "count" => Value::from_integer(self.count as i64),
+            "state" => Value::from_string(self.state.glyph().to_string()),
+            "phase" => Value::from_string(phase),
+            "elapsed" => Value::from_string(format_duration(self.state.elapsed())),
+            "remaining" => Value::from_string(format_duration(remaining)),
+        );
+        self.time.set_text(self.format.render(&values)?);
+        Ok(())
     }
 
     fn nag(&self, message: &str, level: &str) {
@@ -84,6 +138,17 @@ impl Pomodoro {
         )
         .expect("Failed to start i3-nagbar");
     }
+
+    /// Tells about the end of a phase with the nagbar and/or a desktop notification
+    fn announce(&self, message: &str, level: &str) -> Result<()> {
+        if self.use_nag {
+            self.nag(message, level);
+        }
+        if self.notify {
+            send_notification("Pomodoro", message)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -91,10 +156,18 @@ impl Pomodoro {
 pub struct PomodoroConfig {
     pub length: u64,
     pub break_length: u64,
+    pub long_break_length: u64,
+    /// The number of pomodoros after which the break is a long one, 0 for none
+    pub long_break_after: usize,
     pub message: String,
     pub break_message: String,
     pub use_nag: bool,
+    #[serde(deserialize_with = "deserialize_expanded")]
     pub nag_path: std::path::PathBuf,
+    /// Show a desktop notification when a phase is over
+    pub notify: bool,
+    /// Format override
+    pub format: String,
 }
 
 impl Default for PomodoroConfig {
@@ -102,10 +175,14 @@ impl Default for PomodoroConfig {
         Self {
             length: 25,
             break_length: 5,
+            long_break_length: 15,
+            long_break_after: 4,
             message: "Pomodoro over! Take a break!".to_string(),
             break_message: "Break over! Time to work!".to_string(),
             use_nag: false,
             nag_path: std::path::PathBuf::from("i3-nagbar"),
+            notify: false,
+            format: "{count} | {state} {elapsed}".to_string(),
         }
     }
 }
@@ -119,19 +196,26 @@ impl ConfigBlock for Pomodoro {
         shared_config: SharedConfig,
         _send: Sender<Task>,
     ) -> Result<Self> {
-        Ok(Pomodoro {
+        let mut pomodoro = Pomodoro {
             id,
             time: TextWidget::new(id, 0, shared_config).with_icon("pomodoro")?,
             state: State::Stopped,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("pomodoro", "Invalid format specified")?,
             length: Duration::from_secs(block_config.length * 60), // convert to minutes
             break_length: Duration::from_secs(block_config.break_length * 60), // convert to minutes
+            long_break_length: Duration::from_secs(block_config.long_break_length * 60),
+            long_break_after: block_config.long_break_after,
             update_interval: Duration::from_millis(1000),
             message: block_config.message,
             break_message: block_config.break_message,
             use_nag: block_config.use_nag,
             count: 0,
             nag_path: block_config.nag_path,
-        })
+            notify: block_config.notify,
+        };
+        pomodoro.set_text()?;
+        Ok(pomodoro)
     }
 }
 
@@ -140,61 +224,86 @@ impl Block for Pomodoro {
         self.id
     }
 
+    /// Only a running timer is updated, clicks (re)start the updates
     fn update(&mut self) -> Result<Option<Update>> {
-        self.set_text();
-        match &self.state {
-            State::Started(_) => {
-                if self.state.elapsed() >= self.length {
-                    if self.use_nag {
-                        self.nag(&self.message, "error");
-                    }
-
-                    self.state = State::OnBreak(Instant::now());
-                }
-            }
-            State::OnBreak(_) => {
-                if self.state.elapsed() >= self.break_length {
-                    if self.use_nag {
-                        self.nag(&self.break_message, "warning");
-                    }
-                    self.state = State::Stopped;
+        if let State::Running(phase, _) = self.state {
+            if self.state.elapsed() >= self.phase_length(phase) {
+                if phase == Phase::Work {
                     self.count += 1;
+                    self.state = State::Running(self.break_after(self.count), Instant::now());
+                    self.announce(&self.message, "error")?;
+                } else {
+                    self.state = State::Stopped;
+                    self.announce(&self.break_message, "warning")?;
                 }
             }
-            _ => {}
         }
+        self.set_text()?;
 
-        Ok(Some(self.update_interval.into()))
+        Ok(match self.state {
+            State::Running(_, _) => Some(self.update_interval.into()),
+            _ => None,
+        })
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
-        match event.button {
-            MouseButton::Right => {
-                self.state = State::Stopped;
+        self.state = match (event.button, self.state) {
+            (MouseButton::Right, _) => {
                 self.count = 0;
+                State::Stopped
             }
-            _ => match &self.state {
-                State::Stopped => {
-                    self.state = State::Started(Instant::now());
-                }
-                State::Started(_) => {
-                    self.state = State::Paused(self.state.elapsed());
-                }
-                State::Paused(duration) => {
-                    self.state =
-                        State::Started(Instant::now().checked_sub(duration.to_owned()).unwrap());
-                }
-                State::OnBreak(_) => {
-                    self.state = State::Started(Instant::now());
-                }
-            },
-        }
-        self.set_text();
+            (_, State::Stopped) => State::Running(Phase::Work, Instant::now()),
+            (_, State::Running(phase, _)) => State::Paused(phase, self.state.elapsed()),
+            (_, State::Paused(phase, elapsed)) => {
+                State::Running(phase, Instant::now().checked_sub(elapsed).unwrap())
+            }
+        };
+        self.set_text()
+    }
 
-        Ok(())
+    fn update_after_click(&self) -> bool {
+        true
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.time]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn work_break_cycle() {
+        let mut pomodoro = Pomodoro::new(
+            0,
+            PomodoroConfig {
+                length: 0,
+                long_break_after: 2,
+                format: "{phase} {remaining}".to_string(),
+                ..PomodoroConfig::default()
+            },
+            SharedConfig::default(),
+            crossbeam_channel::unbounded().0,
+        )
+        .unwrap();
+        assert!(pomodoro.update().unwrap().is_none());
+
+        // The pomodoros are over right away, the second one is followed by a long break
+        for &(count, phase) in &[(1, Phase::ShortBreak), (2, Phase::LongBreak)] {
+            pomodoro.state = State::Running(Phase::Work, Instant::now());
+            assert!(pomodoro.update().unwrap().is_some());
+            assert_eq!(pomodoro.count, count);
+            assert_eq!(pomodoro.state.phase(), Some(phase));
+        }
+
+        pomodoro.state = State::Paused(Phase::LongBreak, Duration::from_secs(65));
+        pomodoro.set_text().unwrap();
+        assert!(pomodoro
+            .time
+            .get_data()
+            .full_text
+            .ends_with("long break 13:55 "));
+    }
+}
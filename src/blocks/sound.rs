@@ -2,9 +2,9 @@
 use {
     crate::pulse::callbacks::ListResult,
     crate::pulse::context::{
-        introspect::ServerInfo, introspect::SinkInfo, introspect::SourceInfo, subscribe::Facility,
-        subscribe::InterestMaskSet, subscribe::Operation as SubscribeOperation, Context, FlagSet,
-        State as PulseState,
+        introspect::ServerInfo, introspect::SinkInfo, introspect::SinkInputInfo,
+        introspect::SourceInfo, subscribe::Facility, subscribe::InterestMaskSet,
+        subscribe::Operation as SubscribeOperation, Context, FlagSet, State as PulseState,
     },
     crate::pulse::mainloop::standard::IterateResult,
     crate::pulse::mainloop::standard::Mainloop,
@@ -39,6 +39,8 @@ use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
+use crate::widgets::button::ButtonWidget;
+use crate::widgets::progressbar::{self, bar_glyphs, render_bar};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, Spacing, State};
 
@@ -52,6 +54,31 @@ trait SoundDevice {
     fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()>;
     fn toggle(&mut self) -> Result<()>;
     fn monitor(&mut self, id: usize, tx_update_request: Sender<Task>) -> Result<()>;
+
+    /// Whether there is something to control, e.g. whether the application is running
+    fn available(&self) -> bool {
+        true
+    }
+
+    /// Makes the next device of the same kind the default one (and the one of this block)
+    fn switch_to_next(&mut self) -> Result<()> {
+        Err(BlockError(
+            "sound".into(),
+            "switching devices needs the PulseAudio driver".into(),
+        ))
+    }
+}
+
+/// The device after `current` in `names` (sorted), wrapping around
+#[cfg_attr(not(feature = "pulseaudio"), allow(dead_code))]
+fn next_device(names: &[String], current: &str) -> Option<String> {
+    let mut names = names.to_vec();
+    names.sort();
+    let next = match names.iter().position(|name| name == current) {
+        Some(i) => (i + 1) % names.len(),
+        None => 0,
+    };
+    names.get(next).cloned()
 }
 
 struct AlsaSoundDevice {
@@ -230,6 +257,7 @@ struct PulseAudioSoundDevice {
 #[cfg(feature = "pulseaudio")]
 #[derive(Debug)]
 struct PulseAudioVolInfo {
+    index: u32,
     volume: ChannelVolumes,
     mute: bool,
     name: String,
@@ -244,6 +272,7 @@ impl TryFrom<&SourceInfo<'_>> for PulseAudioVolInfo {
         match source_info.name.as_ref() {
             None => Err(()),
             Some(name) => Ok(PulseAudioVolInfo {
+                index: source_info.index,
                 volume: source_info.volume,
                 mute: source_info.mute,
                 name: name.to_string(),
@@ -256,6 +285,28 @@ impl TryFrom<&SourceInfo<'_>> for PulseAudioVolInfo {
     }
 }
 
+/// A stream's name is the name of the application playing it
+#[cfg(feature = "pulseaudio")]
+impl TryFrom<&SinkInputInfo<'_>> for PulseAudioVolInfo {
+    type Error = ();
+
+    fn try_from(stream_info: &SinkInputInfo) -> std::result::Result<Self, Self::Error> {
+        match stream_info.proplist.get_str(properties::APPLICATION_NAME) {
+            None => Err(()),
+            Some(name) => Ok(PulseAudioVolInfo {
+                index: stream_info.index,
+                volume: stream_info.volume,
+                mute: stream_info.mute,
+                name,
+                description: stream_info
+                    .name
+                    .clone()
+                    .map(|description| description.into_owned()),
+            }),
+        }
+    }
+}
+
 #[cfg(feature = "pulseaudio")]
 impl TryFrom<&SinkInfo<'_>> for PulseAudioVolInfo {
     type Error = ();
@@ -264,6 +315,7 @@ impl TryFrom<&SinkInfo<'_>> for PulseAudioVolInfo {
         match sink_info.name.as_ref() {
             None => Err(()),
             Some(name) => Ok(PulseAudioVolInfo {
+                index: sink_info.index,
                 volume: sink_info.volume,
                 mute: sink_info.mute,
                 name: name.to_string(),
@@ -282,6 +334,12 @@ enum PulseAudioClientRequest {
     GetDefaultDevice,
     GetInfoByIndex(DeviceKind, u32),
     GetInfoByName(DeviceKind, String),
+    GetInfoList(DeviceKind),
+    SetDefaultDevice(DeviceKind, String),
+    GetStreamInfo(u32),
+    GetStreamList,
+    SetStreamVolume(u32, ChannelVolumes),
+    SetStreamMute(u32, bool),
     SetVolumeByName(DeviceKind, String, ChannelVolumes),
     SetMuteByName(DeviceKind, String, bool),
 }
@@ -299,6 +357,10 @@ lazy_static! {
     // State for each device
     static ref PULSEAUDIO_DEVICES: Mutex<HashMap<(DeviceKind, String), PulseAudioVolInfo>> =
         Mutex::new(HashMap::new());
+
+    // State for each stream of an application playing sound (sink input), by index
+    static ref PULSEAUDIO_STREAMS: Mutex<HashMap<u32, PulseAudioVolInfo>> =
+        Mutex::new(HashMap::new());
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -431,6 +493,44 @@ impl PulseAudioClient {
                                         PulseAudioClient::source_info_callback,
                                     );
                                 }
+                                GetInfoList(DeviceKind::Sink) => {
+                                    introspector
+                                        .get_sink_info_list(PulseAudioClient::sink_info_callback);
+                                }
+                                GetInfoList(DeviceKind::Source) => {
+                                    introspector.get_source_info_list(
+                                        PulseAudioClient::source_info_callback,
+                                    );
+                                }
+                                SetDefaultDevice(DeviceKind::Sink, name) => {
+                                    connection
+                                        .context
+                                        .borrow_mut()
+                                        .set_default_sink(&name, |_| {});
+                                }
+                                SetDefaultDevice(DeviceKind::Source, name) => {
+                                    connection
+                                        .context
+                                        .borrow_mut()
+                                        .set_default_source(&name, |_| {});
+                                }
+                                GetStreamInfo(index) => {
+                                    introspector.get_sink_input_info(
+                                        index,
+                                        PulseAudioClient::stream_info_callback,
+                                    );
+                                }
+                                GetStreamList => {
+                                    introspector.get_sink_input_info_list(
+                                        PulseAudioClient::stream_info_callback,
+                                    );
+                                }
+                                SetStreamVolume(index, volumes) => {
+                                    introspector.set_sink_input_volume(index, &volumes, None);
+                                }
+                                SetStreamMute(index, mute) => {
+                                    introspector.set_sink_input_mute(index, mute, None);
+                                }
                                 SetVolumeByName(DeviceKind::Sink, name, volumes) => {
                                     introspector.set_sink_volume_by_name(&name, &volumes, None);
                                 }
@@ -467,7 +567,10 @@ impl PulseAudioClient {
                     .borrow_mut()
                     .set_subscribe_callback(Some(Box::new(PulseAudioClient::subscribe_callback)));
                 connection.context.borrow_mut().subscribe(
-                    InterestMaskSet::SERVER | InterestMaskSet::SINK | InterestMaskSet::SOURCE,
+                    InterestMaskSet::SERVER
+                        | InterestMaskSet::SINK
+                        | InterestMaskSet::SOURCE
+                        | InterestMaskSet::SINK_INPUT,
                     |_| {},
                 );
 
@@ -535,11 +638,44 @@ impl PulseAudioClient {
         }
     }
 
+    fn stream_info_callback(result: ListResult<&SinkInputInfo>) {
+        if let Some(vol_info) = Self::get_info_callback(result) {
+            PULSEAUDIO_STREAMS
+                .lock()
+                .unwrap()
+                .insert(vol_info.index, vol_info);
+
+            PulseAudioClient::send_update_event();
+        }
+    }
+
     fn subscribe_callback(
         facility: Option<Facility>,
-        _operation: Option<SubscribeOperation>,
+        operation: Option<SubscribeOperation>,
         index: u32,
     ) {
+        if let (Some(SubscribeOperation::Removed), Some(Facility::SinkInput)) =
+            (operation, facility)
+        {
+            PULSEAUDIO_STREAMS.lock().unwrap().remove(&index);
+            PulseAudioClient::send_update_event();
+            return;
+        }
+        if let (Some(SubscribeOperation::Removed), Some(Facility::Sink | Facility::Source)) =
+            (operation, facility)
+        {
+            let kind = match facility {
+                Some(Facility::Source) => DeviceKind::Source,
+                _ => DeviceKind::Sink,
+            };
+            PULSEAUDIO_DEVICES
+                .lock()
+                .unwrap()
+                .retain(|(device_kind, _), info| *device_kind != kind || info.index != index);
+            PulseAudioClient::send_update_event();
+            return;
+        }
+
         match facility {
             None => {}
             Some(facility) => match facility {
@@ -560,6 +696,9 @@ impl PulseAudioClient {
                     ))
                     .ok();
                 }
+                Facility::SinkInput => {
+                    PulseAudioClient::send(PulseAudioClientRequest::GetStreamInfo(index)).ok();
+                }
                 _ => {}
             },
         }
@@ -595,6 +734,8 @@ impl PulseAudioSoundDevice {
             device_kind,
             device.name(),
         ))?;
+        // All devices of the kind, to switch between them
+        PulseAudioClient::send(PulseAudioClientRequest::GetInfoList(device_kind))?;
 
         Ok(device)
     }
@@ -612,8 +753,33 @@ impl PulseAudioSoundDevice {
 
     fn volume(&mut self, volume: ChannelVolumes) {
         self.volume = Some(volume);
-        self.volume_avg = (volume.avg().0 as f32 / Volume::NORMAL.0 as f32 * 100.0).round() as u32;
+        self.volume_avg = volume_percents(volume);
+    }
+}
+
+/// The average of the volumes, in percents
+#[cfg(feature = "pulseaudio")]
+fn volume_percents(volume: ChannelVolumes) -> u32 {
+    (volume.avg().0 as f32 / Volume::NORMAL.0 as f32 * 100.0).round() as u32
+}
+
+/// `volume` with `step` (in percents) applied to all channels, up to `max_vol` percents
+#[cfg(feature = "pulseaudio")]
+fn stepped_volume(mut volume: ChannelVolumes, step: i32, max_vol: Option<u32>) -> ChannelVolumes {
+    let step = (step as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as i32;
+    for vol in volume.get_mut().iter_mut() {
+        let uncapped_vol = max(0, vol.0 as i32 + step) as u32;
+        let capped_vol = if let Some(vol_cap) = max_vol {
+            min(
+                uncapped_vol,
+                (vol_cap as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as u32,
+            )
+        } else {
+            uncapped_vol
+        };
+        vol.0 = min(capped_vol, Volume::MAX.0);
     }
+    volume
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -647,22 +813,11 @@ impl SoundDevice for PulseAudioSoundDevice {
     }
 
     fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()> {
-        let mut volume = self.volume.block_error("sound", "volume unknown")?;
-
-        // apply step to volumes
-        let step = (step as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as i32;
-        for vol in volume.get_mut().iter_mut() {
-            let uncapped_vol = max(0, vol.0 as i32 + step) as u32;
-            let capped_vol = if let Some(vol_cap) = max_vol {
-                min(
-                    uncapped_vol,
-                    (vol_cap as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as u32,
-                )
-            } else {
-                uncapped_vol
-            };
-            vol.0 = min(capped_vol, Volume::MAX.0);
-        }
+        let volume = stepped_volume(
+            self.volume.block_error("sound", "volume unknown")?,
+            step,
+            max_vol,
+        );
 
         // update volumes
         self.volume(volume);
@@ -694,6 +849,135 @@ impl SoundDevice for PulseAudioSoundDevice {
             .insert(id, tx_update_request);
         Ok(())
     }
+
+    fn switch_to_next(&mut self) -> Result<()> {
+        let names: Vec<String> = PULSEAUDIO_DEVICES
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|(kind, _)| *kind == self.device_kind)
+            .map(|(_, name)| name.clone())
+            .collect();
+        let next = next_device(&names, &self.name())
+            .block_error("sound", "no other device to switch to")?;
+
+        // A block showing a given device follows the switch too
+        if self.name.is_some() {
+            self.name = Some(next.clone());
+        }
+        PulseAudioClient::send(PulseAudioClientRequest::SetDefaultDevice(
+            self.device_kind,
+            next,
+        ))?;
+        PulseAudioClient::send(PulseAudioClientRequest::GetDefaultDevice)?;
+        Ok(())
+    }
+}
+
+/// The sound of an application, i.e. its streams (the first one is shown, all are controlled)
+#[cfg(feature = "pulseaudio")]
+struct PulseAudioAppDevice {
+    app_name: String,
+    streams: Vec<u32>,
+    description: Option<String>,
+    volume: Option<ChannelVolumes>,
+    volume_avg: u32,
+    muted: bool,
+}
+
+#[cfg(feature = "pulseaudio")]
+impl PulseAudioAppDevice {
+    fn new(app_name: String) -> Result<Self> {
+        PulseAudioClient::send(PulseAudioClientRequest::GetStreamList)?;
+        Ok(PulseAudioAppDevice {
+            app_name,
+            streams: Vec::new(),
+            description: None,
+            volume: None,
+            volume_avg: 0,
+            muted: false,
+        })
+    }
+}
+
+#[cfg(feature = "pulseaudio")]
+impl SoundDevice for PulseAudioAppDevice {
+    fn volume(&self) -> u32 {
+        self.volume_avg
+    }
+
+    fn muted(&self) -> bool {
+        self.muted
+    }
+
+    fn output_name(&self) -> String {
+        self.app_name.clone()
+    }
+
+    fn output_description(&self) -> Option<String> {
+        self.description.clone()
+    }
+
+    fn get_info(&mut self) -> Result<()> {
+        let streams = PULSEAUDIO_STREAMS.lock().unwrap();
+        let mut matching: Vec<&PulseAudioVolInfo> = streams
+            .values()
+            .filter(|info| info.name.eq_ignore_ascii_case(&self.app_name))
+            .collect();
+        matching.sort_by_key(|info| info.index);
+
+        self.streams = matching.iter().map(|info| info.index).collect();
+        match matching.first() {
+            Some(info) => {
+                self.volume = Some(info.volume);
+                self.volume_avg = volume_percents(info.volume);
+                self.muted = info.mute;
+                self.description = info.description.clone();
+            }
+            None => {
+                self.volume = None;
+                self.volume_avg = 0;
+                self.muted = false;
+                self.description = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()> {
+        let volume = stepped_volume(
+            self.volume.block_error("sound", "volume unknown")?,
+            step,
+            max_vol,
+        );
+
+        self.volume = Some(volume);
+        self.volume_avg = volume_percents(volume);
+        for index in &self.streams {
+            PulseAudioClient::send(PulseAudioClientRequest::SetStreamVolume(*index, volume))?;
+        }
+        Ok(())
+    }
+
+    fn toggle(&mut self) -> Result<()> {
+        self.muted = !self.muted;
+        for index in &self.streams {
+            PulseAudioClient::send(PulseAudioClientRequest::SetStreamMute(*index, self.muted))?;
+        }
+        Ok(())
+    }
+
+    fn monitor(&mut self, id: usize, tx_update_request: Sender<Task>) -> Result<()> {
+        PULSEAUDIO_EVENT_LISTENER
+            .lock()
+            .unwrap()
+            .insert(id, tx_update_request);
+        Ok(())
+    }
+
+    fn available(&self) -> bool {
+        !self.streams.is_empty()
+    }
 }
 
 // TODO: Use the alsa control bindings to implement push updates
@@ -708,9 +992,17 @@ pub struct Sound {
     show_volume_when_muted: bool,
     mappings: Option<BTreeMap<String, String>>,
     max_vol: Option<u32>,
+    switch_devices: bool,
     scrolling: Scrolling,
+    bar_glyphs: (String, String),
+    buttons: Vec<ButtonWidget>,
 }
 
+// Instances of the optional buttons, the main widget is instance 0
+const BUTTON_DOWN: usize = 1;
+const BUTTON_MUTE: usize = 2;
+const BUTTON_UP: usize = 3;
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum DeviceKind {
@@ -779,7 +1071,17 @@ pub struct SoundConfig {
 
     pub mappings: Option<BTreeMap<String, String>>,
 
+    /// Volume (in percents) scrolling up stops at, higher values allow amplifying the sound
     pub max_vol: Option<u32>,
+
+    /// Show buttons to lower the volume, toggle mute and raise the volume
+    pub show_buttons: bool,
+
+    /// Switch to the next device on left click (PulseAudio only)
+    pub switch_devices: bool,
+
+    /// Control the sound of this application instead of a device (PulseAudio only)
+    pub app_name: Option<String>,
 }
 
 impl Default for SoundConfig {
@@ -794,7 +1096,10 @@ impl Default for SoundConfig {
             format: "{volume}".to_string(),
             show_volume_when_muted: false,
             mappings: None,
-            max_vol: None,
+            max_vol: Some(100),
+            show_buttons: false,
+            switch_devices: false,
+            app_name: None,
         }
     }
 }
@@ -852,13 +1157,23 @@ impl ConfigBlock for Sound {
         };
 
         // prefer PulseAudio if available and selected, fallback to ALSA
-        let device: Box<dyn SoundDevice> = match pulseaudio_device {
-            Ok(dev) => Box::new(dev),
-            Err(_) => Box::new(AlsaSoundDevice::new(
-                block_config.name.unwrap_or_else(|| "Master".into()),
-                block_config.device.unwrap_or_else(|| "default".into()),
-                block_config.natural_mapping,
-            )?),
+        let device: Box<dyn SoundDevice> = match (block_config.app_name, pulseaudio_device) {
+            #[cfg(feature = "pulseaudio")]
+            (Some(app_name), Ok(_)) => Box::new(PulseAudioAppDevice::new(app_name)?),
+            (Some(_), _) => {
+                return Err(ConfigurationError(
+                    "sound".into(),
+                    "app_name needs the PulseAudio driver".into(),
+                ))
+            }
+            (None, pulseaudio_device) => match pulseaudio_device {
+                Ok(dev) => Box::new(dev),
+                Err(_) => Box::new(AlsaSoundDevice::new(
+                    block_config.name.unwrap_or_else(|| "Master".into()),
+                    block_config.device.unwrap_or_else(|| "default".into()),
+                    block_config.natural_mapping,
+                )?),
+            },
         };
 
         let mut sound = Self {
@@ -871,7 +1186,19 @@ impl ConfigBlock for Sound {
             show_volume_when_muted: block_config.show_volume_when_muted,
             mappings: block_config.mappings,
             max_vol: block_config.max_vol,
+            switch_devices: block_config.switch_devices,
             scrolling: shared_config.scrolling,
+            bar_glyphs: bar_glyphs(&shared_config.theme),
+            buttons: if block_config.show_buttons {
+                vec![
+                    ButtonWidget::new(id, BUTTON_DOWN, shared_config.clone()).with_text("-"),
+                    ButtonWidget::new(id, BUTTON_MUTE, shared_config.clone())
+                        .with_icon("volume_muted")?,
+                    ButtonWidget::new(id, BUTTON_UP, shared_config.clone()).with_text("+"),
+                ]
+            } else {
+                Vec::new()
+            },
             text: TextWidget::new(id, 0, shared_config).with_icon("volume_empty")?,
         };
 
@@ -915,6 +1242,7 @@ impl Block for Sound {
 
         let values = map!(
             "volume" => Value::from_integer(volume as i64).percents(),
+            "bar" => Value::from_string(render_bar(volume as f64, progressbar::DEFAULT_WIDTH, &self.bar_glyphs.0, &self.bar_glyphs.1)),
             "output_name" => Value::from_string(output_name),
             "output_description" => Value::from_string(output_description),
         );
@@ -936,20 +1264,65 @@ impl Block for Sound {
             self.text.set_text(text);
         }
 
+        let state = if self.device.muted() {
+            State::Warning
+        } else {
+            State::Idle
+        };
+        for button in &mut self.buttons {
+            button.set_state(state);
+        }
+
         Ok(None)
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.text]
+        // e.g. the application isn't running
+        if !self.device.available() {
+            return Vec::new();
+        }
+        let mut widgets: Vec<&dyn I3BarWidget> = vec![&self.text];
+        for button in &self.buttons {
+            widgets.push(button);
+        }
+        widgets
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
+        let button = self
+            .buttons
+            .iter()
+            .find(|button| button.is_clicked(e))
+            .and(e.instance);
+        match (button, e.button) {
+            (Some(BUTTON_DOWN), MouseButton::Left) => self
+                .device
+                .set_volume(-(self.step_width as i32), self.max_vol)?,
+            (Some(BUTTON_MUTE), MouseButton::Left) => self.device.toggle()?,
+            (Some(BUTTON_UP), MouseButton::Left) => self
+                .device
+                .set_volume(self.step_width as i32, self.max_vol)?,
+            _ => self.click_main(e)?,
+        }
+        self.update()?;
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+impl Sound {
+    fn click_main(&mut self, e: &I3BarEvent) -> Result<()> {
         match e.button {
             MouseButton::Right => self.device.toggle()?,
             MouseButton::Left => {
                 if let Some(ref cmd) = self.on_click {
                     spawn_child_async("sh", &["-c", cmd])
                         .block_error("sound", "could not spawn child")?;
+                } else if self.switch_devices {
+                    self.device.switch_to_next()?;
                 }
             }
             _ => {
@@ -965,11 +1338,30 @@ impl Block for Sound {
                 }
             }
         }
-        self.update()?;
         Ok(())
     }
+}
 
-    fn id(&self) -> usize {
-        self.id
+#[cfg(test)]
+mod tests {
+    use super::next_device;
+
+    #[test]
+    fn switch_devices() {
+        let names = vec!["speakers".to_string(), "headphones".to_string()];
+        assert_eq!(
+            next_device(&names, "headphones").as_deref(),
+            Some("speakers")
+        );
+        assert_eq!(
+            next_device(&names, "speakers").as_deref(),
+            Some("headphones")
+        );
+        // e.g. "@DEFAULT_SINK@" before the server answered
+        assert_eq!(
+            next_device(&names, "unknown").as_deref(),
+            Some("headphones")
+        );
+        assert_eq!(next_device(&[], "speakers"), None);
     }
 }
@@ -6,7 +6,7 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_one_or_many};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
@@ -23,6 +23,9 @@ pub struct Taskwarrior {
     critical_threshold: u32,
     filters: Vec<Filter>,
     filter_index: usize,
+    project: Option<String>,
+    tags: Vec<String>,
+    next_max_width: usize,
     format: FormatTemplate,
     format_singular: FormatTemplate,
     format_everything_done: FormatTemplate,
@@ -49,6 +52,29 @@ impl Filter {
         let filter = format!("-COMPLETED -DELETED {}", tags);
         Self::new(name, filter)
     }
+
+    /// The `task` filter selecting the tasks of this filter which also are in `project` and
+    /// have all of the `tags`, as passed to the shell and as shown by `{filter}`. The filter
+    /// is grouped, so that an `or` in it doesn't apply to the restrictions.
+    pub fn restricted(&self, project: Option<&str>, tags: &[String]) -> (String, String) {
+        let mut restrictions = Vec::new();
+        if let Some(project) = project {
+            restrictions.push(format!("project:{}", project));
+        }
+        restrictions.extend(tags.iter().map(|tag| format!("+{}", tag)));
+
+        let restrictions = restrictions.join(" ");
+        if restrictions.is_empty() {
+            (self.filter.clone(), self.filter.clone())
+        } else if self.filter.trim().is_empty() {
+            (restrictions.clone(), restrictions)
+        } else {
+            (
+                format!("'(' {} ')' {}", self.filter, restrictions),
+                format!("({}) {}", self.filter, restrictions),
+            )
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -79,6 +105,18 @@ pub struct TaskwarriorConfig {
     #[serde(default = "TaskwarriorConfig::default_filters")]
     pub filters: Vec<Filter>,
 
+    /// Only count the tasks of this project, whichever filter is active
+    #[serde(default)]
+    pub project: Option<String>,
+
+    /// Only count the tasks with all of these tags, whichever filter is active
+    #[serde(default, deserialize_with = "deserialize_one_or_many")]
+    pub tags: Vec<String>,
+
+    /// Maximum number of characters of the `{next}` task description, 0 for no limit
+    #[serde(default = "TaskwarriorConfig::default_next_max_width")]
+    pub next_max_width: usize,
+
     /// Format override
     #[serde(default = "TaskwarriorConfig::default_format")]
     pub format: String,
@@ -116,6 +154,10 @@ impl TaskwarriorConfig {
         )]
     }
 
+    fn default_next_max_width() -> usize {
+        30
+    }
+
     fn default_format() -> String {
         "{count}".to_owned()
     }
@@ -167,6 +209,9 @@ impl ConfigBlock for Taskwarrior {
             )?,
             filter_index: 0,
             filters,
+            project: block_config.project,
+            tags: block_config.tags,
+            next_max_width: block_config.next_max_width,
             output,
         })
     }
@@ -208,6 +253,68 @@ fn get_number_of_tasks(filter: &str) -> Result<u32> {
     .block_error("taskwarrior", "could not parse the result of taskwarrior")
 }
 
+/// An entry of `task export`
+#[derive(Deserialize, Debug, Clone)]
+struct ExportedTask {
+    description: String,
+    #[serde(default)]
+    urgency: f64,
+}
+
+/// The description of the most urgent task of `filter`, if any
+fn get_next_task(filter: &str) -> Result<Option<String>> {
+    let output = Command::new("sh")
+        .args([
+            "-c",
+            &format!("task rc.gc=off rc.json.array=on {} export", filter),
+        ])
+        .output()
+        .block_error(
+            "taskwarrior",
+            "failed to run taskwarrior for exporting the tasks",
+        )?;
+    let tasks: Vec<ExportedTask> = serde_json::from_slice(&output.stdout).block_error(
+        "taskwarrior",
+        "could not parse the tasks exported by taskwarrior",
+    )?;
+    Ok(next_task(tasks))
+}
+
+fn next_task(tasks: Vec<ExportedTask>) -> Option<String> {
+    tasks
+        .into_iter()
+        .max_by(|a, b| {
+            a.urgency
+                .partial_cmp(&b.urgency)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .map(|task| task.description)
+}
+
+/// Shortens `text` to `max_width` characters (0 for no limit), ending it with an ellipsis if
+/// it is cut
+fn truncate(text: &str, max_width: usize) -> String {
+    if max_width == 0 || text.chars().count() <= max_width {
+        text.to_string()
+    } else {
+        let mut truncated: String = text.chars().take(max_width.saturating_sub(1)).collect();
+        truncated.push('\u{2026}');
+        truncated
+    }
+}
+
+impl Taskwarrior {
+    fn shows_next(&self) -> bool {
+        [
+            &self.format,
+            &self.format_singular,
+            &self.format_everything_done,
+        ]
+        .iter()
+        .any(|format| format.contains("next"))
+    }
+}
+
 impl Block for Taskwarrior {
     fn update(&mut self) -> Result<Option<Update>> {
         if !has_taskwarrior()? {
@@ -217,10 +324,20 @@ impl Block for Taskwarrior {
                 "taskwarrior",
                 &format!("Filter at index {} does not exist", self.filter_index),
             )?;
-            let number_of_tasks = get_number_of_tasks(&filter.filter)?;
+            let (query, shown) = filter.restricted(self.project.as_deref(), &self.tags);
+            let number_of_tasks = get_number_of_tasks(&query)?;
+            let next = if number_of_tasks > 0 && self.shows_next() {
+                get_next_task(&query)?
+                    .map(|next| truncate(&next, self.next_max_width))
+                    .unwrap_or_default()
+            } else {
+                String::new()
+            };
             let values = map!(
                 "count" => Value::from_integer(number_of_tasks as i64),
                 "filter_name" => Value::from_string(filter.name.clone()),
+                "filter" => Value::from_string(shown),
+                "next" => Value::from_string(next),
             );
             self.output.set_text(match number_of_tasks {
                 0 => self.format_everything_done.render(&values)?,
@@ -264,3 +381,51 @@ impl Block for Taskwarrior {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restricted_filter() {
+        let filter = Filter::new(
+            "today".to_string(),
+            "+PENDING +OVERDUE or +DUETODAY".to_string(),
+        );
+        assert_eq!(
+            filter.restricted(None, &[]).0,
+            "+PENDING +OVERDUE or +DUETODAY"
+        );
+        assert_eq!(
+            filter.restricted(Some("work"), &["urgent".to_string()]),
+            (
+                "'(' +PENDING +OVERDUE or +DUETODAY ')' project:work +urgent".to_string(),
+                "(+PENDING +OVERDUE or +DUETODAY) project:work +urgent".to_string()
+            )
+        );
+        assert_eq!(
+            Filter::new("all".to_string(), String::new())
+                .restricted(Some("work"), &[])
+                .0,
+            "project:work"
+        );
+    }
+
+    #[test]
+    fn next_task_description() {
+        let tasks: Vec<ExportedTask> = serde_json::from_str(
+            r#"[
+                {"id": 1, "description": "Water the plants", "urgency": 1.8},
+                {"id": 2, "description": "Pay the rent", "urgency": 9.2, "tags": ["home"]},
+                {"id": 3, "description": "Read a book"}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(next_task(tasks), Some("Pay the rent".to_string()));
+        assert_eq!(next_task(Vec::new()), None);
+
+        assert_eq!(truncate("Pay the rent", 0), "Pay the rent");
+        assert_eq!(truncate("Pay the rent", 12), "Pay the rent");
+        assert_eq!(truncate("Pay the rent", 8), "Pay the\u{2026}");
+    }
+}
@@ -3,17 +3,18 @@ use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
+use log::warn;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::{deserialize_duration, deserialize_one_or_many};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::has_command;
+use crate::util::{has_command, Units};
 use crate::widgets::{text::TextWidget, I3BarWidget, Spacing, State};
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
@@ -29,20 +30,31 @@ impl Default for TemperatureScale {
     }
 }
 
+/// How the temperatures of several sensors are combined into the one that sets the state
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Aggregation {
+    #[default]
+    Max,
+    Avg,
+    Min,
+}
+
 pub struct Temperature {
     id: usize,
     text: TextWidget,
     output: String,
     collapsed: bool,
     update_interval: Duration,
-    scale: TemperatureScale,
+    units: Units,
     maximum_good: i64,
     maximum_idle: i64,
     maximum_info: i64,
     maximum_warning: i64,
     format: FormatTemplate,
-    chip: Option<String>,
+    chips: Vec<String>,
     inputs: Option<Vec<String>>,
+    aggregation: Aggregation,
     fallback_required: bool,
 }
 
@@ -56,10 +68,13 @@ pub struct TemperatureConfig {
     /// Collapsed by default?
     pub collapsed: bool,
 
-    /// The temperature scale to use for display and thresholds
+    /// The temperature scale to use for display and thresholds, superseded by `units`
     #[serde(default)]
     pub scale: TemperatureScale,
 
+    /// The units to use for display and thresholds
+    pub units: Option<Units>,
+
     /// Maximum temperature, below which state is set to good
     #[serde(default)]
     pub good: Option<i64>,
@@ -79,11 +94,15 @@ pub struct TemperatureConfig {
     /// Format override
     pub format: String,
 
-    /// Chip override
-    pub chip: Option<String>,
+    /// Chips to read, one name or a list
+    #[serde(deserialize_with = "deserialize_one_or_many")]
+    pub chip: Vec<String>,
 
     /// Inputs whitelist
     pub inputs: Option<Vec<String>>,
+
+    /// Which temperature of the sensors sets the state
+    pub aggregation: Aggregation,
 }
 
 impl Default for TemperatureConfig {
@@ -93,12 +112,14 @@ impl Default for TemperatureConfig {
             interval: Duration::from_secs(5),
             collapsed: true,
             scale: TemperatureScale::default(),
+            units: None,
             good: None,
             idle: None,
             info: None,
             warning: None,
-            chip: None,
+            chip: Vec::new(),
             inputs: None,
+            aggregation: Aggregation::default(),
         }
     }
 }
@@ -112,6 +133,11 @@ impl ConfigBlock for Temperature {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let units = block_config.units.unwrap_or(match block_config.scale {
+            TemperatureScale::Celsius => Units::Metric,
+            TemperatureScale::Fahrenheit => Units::Imperial,
+        });
+        let threshold = |celsius| units.temperature(celsius) as i64;
         Ok(Temperature {
             id,
             update_interval: block_config.interval,
@@ -124,35 +150,16 @@ impl ConfigBlock for Temperature {
                 }),
             output: String::new(),
             collapsed: block_config.collapsed,
-            scale: block_config.scale,
-            maximum_good: block_config
-                .good
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 20,
-                    TemperatureScale::Fahrenheit => 68,
-                }),
-            maximum_idle: block_config
-                .idle
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 45,
-                    TemperatureScale::Fahrenheit => 113,
-                }),
-            maximum_info: block_config
-                .info
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 60,
-                    TemperatureScale::Fahrenheit => 140,
-                }),
-            maximum_warning: block_config
-                .warning
-                .unwrap_or_else(|| match block_config.scale {
-                    TemperatureScale::Celsius => 80,
-                    TemperatureScale::Fahrenheit => 176,
-                }),
+            units,
+            maximum_good: block_config.good.unwrap_or_else(|| threshold(20.)),
+            maximum_idle: block_config.idle.unwrap_or_else(|| threshold(45.)),
+            maximum_info: block_config.info.unwrap_or_else(|| threshold(60.)),
+            maximum_warning: block_config.warning.unwrap_or_else(|| threshold(80.)),
             format: FormatTemplate::from_string(&block_config.format)
                 .block_error("temperature", "Invalid format specified for temperature")?,
-            chip: block_config.chip,
+            chips: block_config.chip,
             inputs: block_config.inputs,
+            aggregation: block_config.aggregation,
             fallback_required: !has_command("temperature", "sensors -j").unwrap_or(false),
         })
     }
@@ -161,18 +168,16 @@ impl ConfigBlock for Temperature {
 type SensorsOutput = HashMap<String, HashMap<String, serde_json::Value>>;
 type InputReadings = HashMap<String, f64>;
 
-impl Block for Temperature {
-    fn update(&mut self) -> Result<Option<Update>> {
+impl Temperature {
+    /// Reads the temperatures of the sensors of `chip` (or of all chips), in the block's units
+    fn read_temperatures(&self, chip: Option<&str>) -> Result<Vec<i64>> {
         let mut args = if self.fallback_required {
             vec!["-u"]
         } else {
             vec!["-j"]
         };
 
-        if let TemperatureScale::Fahrenheit = self.scale {
-            args.push("-f");
-        }
-        if let Some(ref chip) = &self.chip {
+        if let Some(chip) = chip {
             args.push(chip);
         }
         let output = Command::new("sensors")
@@ -196,7 +201,7 @@ impl Block for Temperature {
                         match rest[2].parse::<i64>() {
                             Ok(t) if t == 0 => Ok(()),
                             Ok(t) if t > -101 && t < 151 => {
-                                temperatures.push(t);
+                                temperatures.push(self.units.temperature(t as f64) as i64);
                                 Ok(())
                             }
                             Ok(t) => {
@@ -234,7 +239,7 @@ impl Block for Temperature {
                         }
 
                         if value > -101f64 && value < 151f64 {
-                            temperatures.push(value as i64);
+                            temperatures.push(self.units.temperature(value) as i64);
                         } else {
                             // This error is recoverable and therefore should not stop the program
                             eprintln!("Temperature ({}) outside of range ([-100, 150])", value);
@@ -244,6 +249,37 @@ impl Block for Temperature {
             }
         }
 
+        Ok(temperatures)
+    }
+
+    /// The temperatures of all configured chips. The chips that can't be read are skipped,
+    /// unless none of them can be read.
+    fn read_all_temperatures(&self) -> Result<Vec<i64>> {
+        if self.chips.is_empty() {
+            return self.read_temperatures(None);
+        }
+        let mut temperatures = Vec::new();
+        let mut error = None;
+        for chip in &self.chips {
+            match self.read_temperatures(Some(chip)) {
+                Ok(chip_temperatures) => temperatures.extend(chip_temperatures),
+                Err(e) => {
+                    warn!("temperature: skipping chip '{}': {}", chip, e);
+                    error = Some(e);
+                }
+            }
+        }
+        match error {
+            Some(e) if temperatures.is_empty() => Err(e),
+            _ => Ok(temperatures),
+        }
+    }
+}
+
+impl Block for Temperature {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let temperatures = self.read_all_temperatures()?;
+
         if !temperatures.is_empty() {
             let max: i64 = *temperatures
                 .iter()
@@ -257,9 +293,10 @@ impl Block for Temperature {
                 .round() as i64;
 
             let values = map!(
-                "average" => Value::from_integer(avg).degrees(),
-                "min" => Value::from_integer(min).degrees(),
-                "max" => Value::from_integer(max).degrees()
+                "average" => self.units.temperature_value(avg),
+                "min" => self.units.temperature_value(min),
+                "max" => self.units.temperature_value(max),
+                "unit" => Value::from_string(self.units.symbol().to_string())
             );
 
             self.output = self.format.render(&values)?;
@@ -267,7 +304,12 @@ impl Block for Temperature {
                 self.text.set_text(self.output.clone());
             }
 
-            let state = match max {
+            let value = match self.aggregation {
+                Aggregation::Max => max,
+                Aggregation::Avg => avg,
+                Aggregation::Min => min,
+            };
+            let state = match value {
                 m if m <= self.maximum_good => State::Good,
                 m if m <= self.maximum_idle => State::Idle,
                 m if m <= self.maximum_info => State::Info,
@@ -8,6 +8,8 @@ use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
 use crate::util::read_file;
 use crate::widgets::text::TextWidget;
@@ -17,6 +19,7 @@ pub struct Uptime {
     id: usize,
     text: TextWidget,
     update_interval: Duration,
+    format: FormatTemplate,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -25,12 +28,16 @@ pub struct UptimeConfig {
     /// Update interval in seconds
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
+
+    /// Format override
+    pub format: String,
 }
 
 impl Default for UptimeConfig {
     fn default() -> Self {
         Self {
             interval: Duration::from_secs(60),
+            format: "{uptime}".to_string(),
         }
     }
 }
@@ -47,6 +54,8 @@ impl ConfigBlock for Uptime {
         Ok(Uptime {
             id,
             update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("uptime", "Invalid format specified")?,
             text: TextWidget::new(id, 0, shared_config).with_icon("uptime")?,
         })
     }
@@ -67,35 +76,13 @@ impl Block for Uptime {
 
         let total_seconds = uptime
             .parse::<f64>()
-            .map(|x| x as u32)
+            .map(|x| x as u64)
             .block_error("Uptime", "Failed to convert uptime float to integer)")?;
 
-        // split up seconds into more human readable portions
-        let weeks = (total_seconds / 604_800) as u32;
-        let rem_weeks = (total_seconds % 604_800) as u32;
-        let days = (rem_weeks / 86_400) as u32;
-        let rem_days = (rem_weeks % 86_400) as u32;
-        let hours = (rem_days / 3600) as u32;
-        let rem_hours = (rem_days % 3600) as u32;
-        let minutes = (rem_hours / 60) as u32;
-        let rem_minutes = (rem_hours % 60) as u32;
-        let seconds = rem_minutes as u32;
-
-        // Display the two largest units.
-        let text = if hours == 0 && days == 0 && weeks == 0 {
-            format!("{}m {}s", minutes, seconds)
-        } else if hours > 0 && days == 0 && weeks == 0 {
-            format!("{}h {}m", hours, minutes)
-        } else if days > 0 && weeks == 0 {
-            format!("{}d {}h", days, hours)
-        } else if days == 0 && weeks > 0 {
-            format!("{}w {}h", weeks, hours)
-        } else if weeks > 0 {
-            format!("{}w {}d", weeks, days)
-        } else {
-            unreachable!()
-        };
-        self.text.set_text(text);
+        let values = map!(
+            "uptime" => Value::from_duration(Duration::from_secs(total_seconds)),
+        );
+        self.text.set_text(self.format.render(&values)?);
         Ok(Some(self.update_interval.into()))
     }
 
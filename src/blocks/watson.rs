@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
@@ -6,11 +7,14 @@ use std::time::{Duration, Instant};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
 use crate::de::deserialize_local_timestamp;
+use crate::de::{deserialize_duration, deserialize_expanded};
 use crate::errors::*;
-use crate::protocol::i3bar_event::I3BarEvent;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
 use crate::util::xdg_config_home;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -27,12 +31,15 @@ pub struct Watson {
     show_time: bool,
     prev_state: Option<WatsonState>,
     update_interval: Duration,
+    format: Option<FormatTemplate>,
+    format_idle: FormatTemplate,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct WatsonConfig {
     /// Path to state of watson
+    #[serde(deserialize_with = "deserialize_expanded")]
     pub state_path: PathBuf,
 
     /// Update interval in seconds
@@ -41,6 +48,12 @@ pub struct WatsonConfig {
 
     /// Show time spent
     pub show_time: bool,
+
+    /// Format override, replaces the default text (and `show_time`) when set
+    pub format: Option<String>,
+
+    /// Format override for when no project is tracked, used with `format`
+    pub format_idle: String,
 }
 
 impl Default for WatsonConfig {
@@ -51,6 +64,8 @@ impl Default for WatsonConfig {
             state_path: config_dir,
             interval: Duration::from_secs(60),
             show_time: false,
+            format: None,
+            format_idle: String::new(),
         }
     }
 }
@@ -71,6 +86,15 @@ impl ConfigBlock for Watson {
             show_time: block_config.show_time,
             update_interval: block_config.interval,
             prev_state: None,
+            format: match &block_config.format {
+                Some(format) => Some(
+                    FormatTemplate::from_string(format)
+                        .block_error("watson", "Invalid format specified")?,
+                ),
+                None => None,
+            },
+            format_idle: FormatTemplate::from_string(&block_config.format_idle)
+                .block_error("watson", "Invalid format specified for watson::format_idle")?,
         };
 
         // Spin up a thread to watch for changes to the brightness file for the
@@ -124,14 +148,55 @@ impl ConfigBlock for Watson {
     }
 }
 
+impl Watson {
+    fn read_state(&self) -> Result<WatsonState> {
+        let file = BufReader::new(
+            File::open(&self.state_path).block_error("watson", "unable to open state file")?,
+        );
+        serde_json::from_reader(file).block_error("watson", "unable to deserialize state")
+    }
+
+    /// Shows `state` with `format` (or `format_idle`)
+    fn update_format(&mut self, state: WatsonState) -> Result<Option<Update>> {
+        let format = self.format.as_ref().internal_error("watson", "no format")?;
+        let update = match &state {
+            WatsonState::Active {
+                project,
+                start,
+                tags,
+            } => {
+                let elapsed = (Local::now() - *start).to_std().unwrap_or_default();
+                let values = map!(
+                    "project" => Value::from_string(project.clone()),
+                    "tags" => Value::from_string(tags.join(" ")),
+                    "elapsed" => Value::from_duration(elapsed),
+                );
+                self.text.set_text(format.render(&values)?);
+                self.text.set_state(State::Good);
+                if format.contains("elapsed") {
+                    Some(self.update_interval.into())
+                } else {
+                    None
+                }
+            }
+            WatsonState::Idle {} => {
+                self.text
+                    .set_text(self.format_idle.render(&HashMap::new())?);
+                self.text.set_state(State::Idle);
+                None
+            }
+        };
+        self.prev_state = Some(state);
+        Ok(update)
+    }
+}
+
 impl Block for Watson {
     fn update(&mut self) -> Result<Option<Update>> {
-        let state = {
-            let file = BufReader::new(
-                File::open(&self.state_path).block_error("watson", "unable to open state file")?,
-            );
-            serde_json::from_reader(file).block_error("watson", "unable to deserialize state")?
-        };
+        let state = self.read_state()?;
+        if self.format.is_some() {
+            return self.update_format(state);
+        }
 
         match state {
             state @ WatsonState::Active { .. } => {
@@ -182,9 +247,24 @@ impl Block for Watson {
         }
     }
 
-    fn click(&mut self, _e: &I3BarEvent) -> Result<()> {
-        self.show_time = !self.show_time;
-        self.update()?;
+    /// Left click stops the tracked project, or restarts the last one. The block is updated
+    /// once watson writes its new state.
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => {
+                let command = match self.read_state()? {
+                    WatsonState::Active { .. } => "stop",
+                    WatsonState::Idle {} => "restart",
+                };
+                spawn_child_async("watson", &[command])
+                    .block_error("watson", "failed to run watson")?;
+            }
+            MouseButton::Right => {
+                self.show_time = !self.show_time;
+                self.update()?;
+            }
+            _ => {}
+        }
         Ok(())
     }
 
@@ -272,3 +352,38 @@ impl WatsonState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_state() {
+        let mut watson = Watson {
+            id: 0,
+            text: TextWidget::new(0, 0, SharedConfig::default()),
+            state_path: PathBuf::new(),
+            show_time: false,
+            prev_state: None,
+            update_interval: Duration::from_secs(60),
+            format: Some(FormatTemplate::from_string("{project} [{tags}] {elapsed}").unwrap()),
+            format_idle: FormatTemplate::from_string("off").unwrap(),
+        };
+
+        let start = Local::now().timestamp() - 3720;
+        let active: WatsonState = serde_json::from_str(&format!(
+            r#"{{"project": "i3status", "start": {}, "tags": ["docs", "review"]}}"#,
+            start
+        ))
+        .unwrap();
+        assert!(watson.update_format(active).unwrap().is_some());
+        assert_eq!(
+            watson.text.get_data().full_text.trim(),
+            "i3status [docs review] 1h 2m"
+        );
+
+        let idle: WatsonState = serde_json::from_str("{}").unwrap();
+        assert!(watson.update_format(idle).unwrap().is_none());
+        assert_eq!(watson.text.get_data().full_text.trim(), "off");
+    }
+}
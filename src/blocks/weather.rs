@@ -14,6 +14,7 @@ use crate::formatting::FormatTemplate;
 use crate::http;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::util::Units;
 use crate::widgets::{text::TextWidget, I3BarWidget, State};
 
 const OPENWEATHERMAP_API_KEY_ENV: &str = "OPENWEATHERMAP_API_KEY";
@@ -31,10 +32,18 @@ pub enum WeatherService {
         #[serde(default = "WeatherService::getenv_openweathermap_place")]
         place: Option<String>,
         coordinates: Option<(String, String)>,
-        units: OpenWeatherMapUnits,
+        units: Units,
         #[serde(default = "WeatherService::default_lang")]
         lang: Option<String>,
     },
+    /// The forecast of the Norwegian Meteorological Institute, which needs no API key
+    Metno {
+        coordinates: Option<(String, String)>,
+        #[serde(default)]
+        units: Units,
+        /// Shown as `{location}`, the coordinates are shown if not set
+        place: Option<String>,
+    },
 }
 
 impl WeatherService {
@@ -50,13 +59,330 @@ impl WeatherService {
     fn default_lang() -> Option<String> {
         Some("en".to_string())
     }
+
+    fn units(&self) -> Units {
+        match self {
+            WeatherService::OpenWeatherMap { units, .. } | WeatherService::Metno { units, .. } => {
+                *units
+            }
+        }
+    }
+
+    fn into_provider(self) -> Box<dyn WeatherProvider> {
+        match self {
+            WeatherService::OpenWeatherMap {
+                api_key,
+                city_id,
+                place,
+                coordinates,
+                lang,
+                ..
+            } => Box::new(OpenWeatherMap {
+                api_key,
+                city_id,
+                place,
+                coordinates,
+                lang,
+            }),
+            WeatherService::Metno {
+                coordinates, place, ..
+            } => Box::new(Metno { coordinates, place }),
+        }
+    }
+}
+
+/// The kind of weather, which selects the icon
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Conditions {
+    Clear,
+    Clouds,
+    Rain,
+    Thunder,
+    Snow,
+    Other,
+}
+
+impl Conditions {
+    fn icon(self) -> &'static str {
+        match self {
+            Conditions::Clear => "weather_sun",
+            Conditions::Clouds => "weather_clouds",
+            Conditions::Rain => "weather_rain",
+            Conditions::Thunder => "weather_thunder",
+            Conditions::Snow => "weather_snow",
+            Conditions::Other => "weather_default",
+        }
+    }
+}
+
+/// The current weather as reported by any provider, in metric units
+#[derive(Clone, Debug)]
+struct Observation {
+    conditions: Conditions,
+    /// Brief description, e.g. "Rain"
+    weather: String,
+    /// Longer description, e.g. "light rain"
+    weather_verbose: String,
+    /// Temperature, in °C
+    temp: f64,
+    /// Relative humidity, in percents
+    humidity: f64,
+    /// Wind speed, in m/s
+    wind: f64,
+    /// Speed of the gusts, in m/s
+    gust: Option<f64>,
+    /// Direction the wind comes from, in degrees
+    wind_direction: Option<f64>,
+    location: String,
+}
+
+/// Where the IP address of this machine is, see `find_ip_location`
+struct IpLocation {
+    city: Option<String>,
+    coordinates: Option<(String, String)>,
+}
+
+/// A weather service the block can show the weather from
+trait WeatherProvider {
+    /// The current weather at `location` if set (from `autolocate`), at the configured location
+    /// otherwise
+    fn current(&self, location: Option<&IpLocation>) -> Result<Observation>;
+}
+
+struct OpenWeatherMap {
+    api_key: Option<String>,
+    city_id: Option<String>,
+    place: Option<String>,
+    coordinates: Option<(String, String)>,
+    lang: Option<String>,
+}
+
+impl WeatherProvider for OpenWeatherMap {
+    fn current(&self, location: Option<&IpLocation>) -> Result<Observation> {
+        let api_key = match &self.api_key {
+            Some(api_key) => api_key,
+            None => return Err(configuration_error(&format!(
+                "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
+                OPENWEATHERMAP_API_KEY_ENV.to_string()))),
+        };
+
+        let location_query = if let Some(city) = location.and_then(|l| l.city.as_ref()) {
+            format!("q={}", city)
+        } else if let Some(cid) = self.city_id.as_ref() {
+            format!("id={}", cid)
+        } else if let Some(p) = self.place.as_ref() {
+            format!("q={}", p)
+        } else if let Some((lat, lon)) = &self.coordinates {
+            format!("lat={}&lon={}", lat, lon)
+        } else if location.is_some() {
+            return Err(configuration_error(
+                "weather is configured to use geolocation, but it could not be obtained",
+            ));
+        } else {
+            return Err(configuration_error(&format!(
+                "Either 'service.city_id' or 'service.place' must be provided. Add one to your config file or set with the environment variables {} or {}",
+                OPENWEATHERMAP_CITY_ID_ENV.to_string(),
+                OPENWEATHERMAP_PLACE_ENV.to_string())));
+        };
+
+        // This uses the "Current Weather Data" API endpoint
+        // Refer to https://openweathermap.org/current
+        let openweather_url = &format!(
+            "https://api.openweathermap.org/data/2.5/weather?{location_query}&appid={api_key}&units=metric&lang={lang}",
+            location_query = location_query,
+            api_key = api_key,
+            lang = self.lang.as_deref().unwrap_or("en"),
+        );
+
+        let output = http::http_get_json(openweather_url, Some(Duration::from_secs(3)), vec![])?;
+
+        // All 300-399 and >500 http codes should be considered as temporary error,
+        // and not result in block error, i.e. leave the output empty.
+        if (output.status >= 300 && output.status < 400) || output.status >= 500 {
+            return Err(BlockError(
+                "weather".to_owned(),
+                format!("Invalid result from curl: {}", output.status),
+            ));
+        };
+
+        let json = output.content;
+
+        // Try to convert an API error into a block error.
+        if let Some(val) = json.get("message") {
+            return Err(BlockError(
+                "weather".to_string(),
+                format!("API Error: {}", val.as_str().unwrap()),
+            ));
+        };
+
+        let raw_weather = json
+            .pointer("/weather/0/main")
+            .and_then(|v| v.as_str())
+            .ok_or_else(malformed_json_error)?
+            .to_string();
+
+        let raw_weather_verbose = json
+            .pointer("/weather/0/description")
+            .ok_or_else(malformed_json_error)?
+            .to_string();
+
+        let raw_temp = json
+            .pointer("/main/temp")
+            .and_then(|v| v.as_f64())
+            .ok_or_else(malformed_json_error)?;
+
+        let raw_humidity = json
+            .pointer("/main/humidity")
+            .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+            .ok_or_else(malformed_json_error)?;
+
+        let raw_wind_speed: f64 = json
+            .pointer("/wind/speed")
+            .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+            .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
+
+        let raw_wind_gust = json.pointer("/wind/gust").and_then(|v| v.as_f64());
+
+        let raw_wind_direction: Option<f64> = json
+            .pointer("/wind/deg")
+            .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
+            .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
+
+        let raw_location = json
+            .pointer("/name")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .ok_or_else(malformed_json_error)?;
+
+        Ok(Observation {
+            conditions: match raw_weather.as_str() {
+                "Clear" => Conditions::Clear,
+                "Rain" | "Drizzle" => Conditions::Rain,
+                "Clouds" | "Fog" | "Mist" => Conditions::Clouds,
+                "Thunderstorm" => Conditions::Thunder,
+                "Snow" => Conditions::Snow,
+                _ => Conditions::Other,
+            },
+            weather: raw_weather,
+            weather_verbose: raw_weather_verbose,
+            temp: raw_temp,
+            humidity: raw_humidity,
+            wind: raw_wind_speed,
+            gust: raw_wind_gust,
+            wind_direction: raw_wind_direction,
+            location: raw_location,
+        })
+    }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum OpenWeatherMapUnits {
-    Metric,
-    Imperial,
+struct Metno {
+    coordinates: Option<(String, String)>,
+    place: Option<String>,
+}
+
+/// Met.no asks clients to identify themselves, see https://api.met.no/doc/TermsOfService
+const METNO_USER_AGENT: &str = concat!(
+    "i3status-rust/",
+    env!("CARGO_PKG_VERSION"),
+    " https://github.com/greshake/i3status-rust"
+);
+
+impl WeatherProvider for Metno {
+    fn current(&self, location: Option<&IpLocation>) -> Result<Observation> {
+        let autolocated = location.and_then(|l| l.coordinates.as_ref());
+        let (lat, lon) = match autolocated.or(self.coordinates.as_ref()) {
+            Some(coordinates) => coordinates,
+            None if location.is_some() => {
+                return Err(configuration_error(
+                    "weather is configured to use geolocation, but it could not be obtained",
+                ))
+            }
+            None => {
+                return Err(configuration_error(
+                    "'service.coordinates' must be provided for the metno service",
+                ))
+            }
+        };
+
+        // Refer to https://api.met.no/weatherapi/locationforecast/2.0/documentation
+        let url = format!(
+            "https://api.met.no/weatherapi/locationforecast/2.0/complete?lat={}&lon={}",
+            lat, lon
+        );
+        let output = http::http_get_json(
+            &url,
+            Some(Duration::from_secs(3)),
+            vec![("User-Agent", METNO_USER_AGENT)],
+        )?;
+        if output.status != 200 {
+            return Err(BlockError(
+                "weather".to_owned(),
+                format!("Invalid result from curl: {}", output.status),
+            ));
+        }
+
+        let now = output
+            .content
+            .pointer("/properties/timeseries/0/data")
+            .ok_or_else(malformed_json_error)?;
+        let detail = |name: &str| {
+            now.pointer(&format!("/instant/details/{}", name))
+                .and_then(|v| v.as_f64())
+        };
+        let symbol = now
+            .pointer("/next_1_hours/summary/symbol_code")
+            .and_then(|v| v.as_str())
+            .unwrap_or("");
+        let (conditions, weather, weather_verbose) = metno_symbol(symbol);
+
+        let location = match (location.and_then(|l| l.city.as_ref()), &self.place) {
+            (Some(city), _) if autolocated.is_some() => city.clone(),
+            (_, Some(place)) => place.clone(),
+            _ => format!("{}, {}", lat, lon),
+        };
+
+        Ok(Observation {
+            conditions,
+            weather: weather.to_string(),
+            weather_verbose,
+            temp: detail("air_temperature").ok_or_else(malformed_json_error)?,
+            humidity: detail("relative_humidity").unwrap_or(0.0),
+            wind: detail("wind_speed").unwrap_or(0.0),
+            gust: detail("wind_speed_of_gust"),
+            wind_direction: detail("wind_from_direction"),
+            location,
+        })
+    }
+}
+
+/// The conditions and the brief and verbose descriptions for a Met.no weather symbol, e.g.
+/// `lightrainshowers_day`. The brief descriptions are the ones of OpenWeatherMap.
+fn metno_symbol(symbol: &str) -> (Conditions, &'static str, String) {
+    let name = symbol.split('_').next().unwrap_or_default();
+    let (conditions, weather) = if name.contains("thunder") {
+        (Conditions::Thunder, "Thunderstorm")
+    } else if name.contains("snow") || name.contains("sleet") {
+        (Conditions::Snow, "Snow")
+    } else if name.contains("rain") {
+        (Conditions::Rain, "Rain")
+    } else if name == "clearsky" || name == "fair" {
+        (Conditions::Clear, "Clear")
+    } else if name == "fog" {
+        (Conditions::Clouds, "Fog")
+    } else if name.contains("cloudy") {
+        (Conditions::Clouds, "Clouds")
+    } else {
+        (Conditions::Other, "Unknown")
+    };
+    // Spell out the symbol, e.g. "light rain showers"
+    let mut verbose = name.to_string();
+    for word in &[
+        "light", "heavy", "partly", "clear", "rain", "sleet", "snow", "showers", "and", "thunder",
+    ] {
+        verbose = verbose.replace(word, &format!(" {} ", word));
+    }
+    let verbose = verbose.split_whitespace().collect::<Vec<_>>().join(" ");
+    (conditions, weather, verbose)
 }
 
 pub struct Weather {
@@ -64,9 +390,11 @@ pub struct Weather {
     weather: TextWidget,
     format: String,
     weather_keys: HashMap<&'static str, Value>,
-    service: WeatherService,
+    provider: Box<dyn WeatherProvider>,
+    units: Units,
     update_interval: Duration,
     autolocate: bool,
+    compass_points: usize,
 }
 
 fn malformed_json_error() -> Error {
@@ -74,231 +402,100 @@ fn malformed_json_error() -> Error {
 }
 
 // TODO: might be good to allow for different geolocation services to be used, similar to how we have `service` for the weather API
-fn find_ip_location() -> Result<Option<String>> {
+fn find_ip_location() -> Result<IpLocation> {
     let http_call_result = http::http_get_json(
         "https://ipapi.co/json/",
         Some(Duration::from_secs(3)),
         vec![],
     )?;
+    let json = http_call_result.content;
 
-    let city = http_call_result
-        .content
+    let city = json
         .pointer("/city")
-        .map(|v| v.as_str())
-        .flatten()
+        .and_then(|v| v.as_str())
         .map(|s| s.to_string());
+    let coordinate = |name: &str| json.pointer(name).and_then(|v| v.as_f64());
+    let coordinates = match (coordinate("/latitude"), coordinate("/longitude")) {
+        (Some(lat), Some(lon)) => Some((lat.to_string(), lon.to_string())),
+        _ => None,
+    };
 
-    Ok(city)
+    Ok(IpLocation { city, coordinates })
 }
 
 // Compute the Australian Apparent Temperature (AT),
 // using the metric formula found on Wikipedia.
-// If using imperial units, we must first convert to metric.
-fn australian_apparent_temp(
-    raw_temp: f64,
-    raw_humidity: f64,
-    raw_wind_speed: f64,
-    units: OpenWeatherMapUnits,
-) -> f64 {
-    let metric = units == OpenWeatherMapUnits::Metric;
-
-    let temp_celsius = if units == OpenWeatherMapUnits::Metric {
-        raw_temp
-    } else {
-        // convert Fahrenheit to Celsius
-        (raw_temp - 32.0) * 0.556
-    };
-
+fn australian_apparent_temp(temp_celsius: f64, humidity: f64, wind_speed: f64) -> f64 {
     let exponent = 17.27 * temp_celsius / (237.7 + temp_celsius);
-    let water_vapor_pressure = raw_humidity * 0.06105 * exponent.exp();
-
-    let metric_wind_speed = if metric {
-        raw_wind_speed
-    } else {
-        // convert mph to m/s
-        raw_wind_speed * 0.447
-    };
-
-    let metric_apparent_temp =
-        temp_celsius + 0.33 * water_vapor_pressure - 0.7 * metric_wind_speed - 4.0;
+    let water_vapor_pressure = humidity * 0.06105 * exponent.exp();
 
-    if metric {
-        metric_apparent_temp
-    } else {
-        1.8 * metric_apparent_temp + 32.0
-    }
+    temp_celsius + 0.33 * water_vapor_pressure - 0.7 * wind_speed - 4.0
 }
 
-// Convert wind direction in azimuth degrees to abbreviation names
-fn convert_wind_direction(direction_opt: Option<f64>) -> String {
-    match direction_opt {
-        Some(direction) => match direction.round() as i64 {
-            24..=68 => "NE".to_string(),
-            69..=113 => "E".to_string(),
-            114..=158 => "SE".to_string(),
-            159..=203 => "S".to_string(),
-            204..=248 => "SW".to_string(),
-            249..=293 => "W".to_string(),
-            294..=338 => "NW".to_string(),
-            _ => "N".to_string(),
-        },
+const COMPASS_POINTS: [&str; 16] = [
+    "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW", "NW",
+    "NNW",
+];
+
+/// The abbreviation of the nearest of the `points` (4, 8 or 16) compass points to `direction`
+/// (in degrees), or `-` if the direction is unknown
+fn compass_direction(direction: Option<f64>, points: usize) -> String {
+    match direction {
+        Some(direction) => {
+            let sector = (direction.rem_euclid(360.) / (360. / points as f64)).round() as usize;
+            COMPASS_POINTS[sector % points * (16 / points)].to_string()
+        }
         None => "-".to_string(),
     }
 }
 
-fn configuration_error(msg: &str) -> Result<()> {
-    Err(ConfigurationError("weather".to_owned(), msg.to_owned()))
+fn configuration_error(msg: &str) -> Error {
+    ConfigurationError("weather".to_owned(), msg.to_owned())
 }
 
 impl Weather {
     fn update_weather(&mut self) -> Result<()> {
-        match &self.service {
-            WeatherService::OpenWeatherMap {
-                api_key: api_key_opt,
-                city_id,
-                place,
-                units,
-                coordinates,
-                lang,
-            } => {
-                if api_key_opt.is_none() {
-                    return configuration_error(&format!(
-                        "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
-                        OPENWEATHERMAP_API_KEY_ENV.to_string()));
-                }
-
-                let api_key = api_key_opt.as_ref().unwrap();
-
-                let geoip_city = if self.autolocate {
-                    find_ip_location().ok().unwrap_or(None) // If geo location fails, try other configuration methods
-                } else {
-                    None
-                };
-
-                let location_query = if let Some(city) = geoip_city {
-                    format!("q={}", city)
-                } else if let Some(cid) = city_id.as_ref() {
-                    format!("id={}", cid)
-                } else if let Some(p) = place.as_ref() {
-                    format!("q={}", p)
-                } else if let Some((lat, lon)) = coordinates {
-                    format!("lat={}&lon={}", lat, lon)
-                } else if self.autolocate {
-                    return configuration_error(
-                        "weather is configured to use geolocation, but it could not be obtained",
-                    );
-                } else {
-                    return configuration_error(&format!(
-                        "Either 'service.city_id' or 'service.place' must be provided. Add one to your config file or set with the environment variables {} or {}",
-                        OPENWEATHERMAP_CITY_ID_ENV.to_string(),
-                        OPENWEATHERMAP_PLACE_ENV.to_string()));
-                };
-
-                // This uses the "Current Weather Data" API endpoint
-                // Refer to https://openweathermap.org/current
-                let openweather_url = &format!(
-                    "https://api.openweathermap.org/data/2.5/weather?{location_query}&appid={api_key}&units={units}&lang={lang}",
-                    location_query = location_query,
-                    api_key = api_key,
-                    units = match *units {
-                        OpenWeatherMapUnits::Metric => "metric",
-                        OpenWeatherMapUnits::Imperial => "imperial",
-                    },
-                    lang = lang.as_ref().unwrap(),
-                );
-
-                let output =
-                    http::http_get_json(openweather_url, Some(Duration::from_secs(3)), vec![])?;
-
-                // All 300-399 and >500 http codes should be considered as temporary error,
-                // and not result in block error, i.e. leave the output empty.
-                if (output.code >= 300 && output.code < 400) || output.code >= 500 {
-                    return Err(BlockError(
-                        "weather".to_owned(),
-                        format!("Invalid result from curl: {}", output.code),
-                    ));
-                };
-
-                let json = output.content;
-
-                // Try to convert an API error into a block error.
-                if let Some(val) = json.get("message") {
-                    return Err(BlockError(
-                        "weather".to_string(),
-                        format!("API Error: {}", val.as_str().unwrap()),
-                    ));
-                };
-
-                let raw_weather = json
-                    .pointer("/weather/0/main")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(malformed_json_error)?
-                    .to_string();
-
-                let raw_weather_verbose = json
-                    .pointer("/weather/0/description")
-                    .ok_or_else(malformed_json_error)?
-                    .to_string();
-
-                let raw_temp = json
-                    .pointer("/main/temp")
-                    .and_then(|v| v.as_f64())
-                    .ok_or_else(malformed_json_error)?;
-
-                let raw_humidity = json
-                    .pointer("/main/humidity")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
-                    .ok_or_else(malformed_json_error)?;
-
-                let raw_wind_speed: f64 = json
-                    .pointer("/wind/speed")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
-                    .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
-
-                let raw_wind_direction: Option<f64> = json
-                    .pointer("/wind/deg")
-                    .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
-                    .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
-
-                let raw_location = json
-                    .pointer("/name")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .ok_or_else(malformed_json_error)?;
-
-                self.weather.set_icon(match raw_weather.as_str() {
-                    "Clear" => "weather_sun",
-                    "Rain" | "Drizzle" => "weather_rain",
-                    "Clouds" | "Fog" | "Mist" => "weather_clouds",
-                    "Thunderstorm" => "weather_thunder",
-                    "Snow" => "weather_snow",
-                    _ => "weather_default",
-                })?;
-
-                let kmh_wind_speed = if *units == OpenWeatherMapUnits::Metric {
-                    raw_wind_speed * 3600.0 / 1000.0
-                } else {
-                    // convert mph to m/s, then km/h
-                    (raw_wind_speed * 0.447) * 3600.0 / 1000.0
-                };
-
-                let apparent_temp =
-                    australian_apparent_temp(raw_temp, raw_humidity, raw_wind_speed, *units);
-
-                self.weather_keys = map!(
-                    "weather" => Value::from_string(raw_weather),
-                    "weather_verbose" => Value::from_string(raw_weather_verbose),
-                    "temp" => Value::from_integer(raw_temp as i64).degrees(),
-                    "humidity" => Value::from_integer(raw_humidity as i64),
-                    "apparent" => Value::from_integer(apparent_temp as i64).degrees(),
-                    "wind" => Value::from_float(raw_wind_speed),
-                    "wind_kmh" => Value::from_float(kmh_wind_speed),
-                    "direction" => Value::from_string(convert_wind_direction(raw_wind_direction)),
-                    "location" => Value::from_string(raw_location),
-                );
-                Ok(())
-            }
-        }
+        let location = if self.autolocate {
+            // If geo location fails, try other configuration methods
+            Some(find_ip_location().unwrap_or(IpLocation {
+                city: None,
+                coordinates: None,
+            }))
+        } else {
+            None
+        };
+        let observation = self.provider.current(location.as_ref())?;
+
+        self.weather.set_icon(observation.conditions.icon())?;
+
+        let apparent_temp =
+            australian_apparent_temp(observation.temp, observation.humidity, observation.wind);
+        // Without a report of the gusts, there are none above the wind speed
+        let gust = observation.gust.unwrap_or(observation.wind);
+        let temp = self.units.temperature(observation.temp);
+        let apparent_temp = self.units.temperature(apparent_temp);
+        let (wind, gust) = match self.units {
+            // mph
+            Units::Imperial => (observation.wind / 0.447, gust / 0.447),
+            Units::Metric | Units::Kelvin => (observation.wind, gust),
+        };
+
+        self.weather_keys = map!(
+            "weather" => Value::from_string(observation.weather),
+            "weather_verbose" => Value::from_string(observation.weather_verbose),
+            "temp" => self.units.temperature_value(temp as i64),
+            "humidity" => Value::from_integer(observation.humidity as i64),
+            "apparent" => self.units.temperature_value(apparent_temp as i64),
+            "unit" => Value::from_string(self.units.symbol().to_string()),
+            "wind" => Value::from_float(wind),
+            "wind_speed" => Value::from_float(wind),
+            "wind_gust" => Value::from_float(gust),
+            "wind_kmh" => Value::from_float(observation.wind * 3600.0 / 1000.0),
+            "direction" => Value::from_string(compass_direction(observation.wind_direction, 8)),
+            "wind_direction" => Value::from_string(compass_direction(observation.wind_direction, self.compass_points)),
+            "location" => Value::from_string(observation.location),
+        );
+        Ok(())
     }
 }
 
@@ -315,6 +512,9 @@ pub struct WeatherConfig {
     pub service: WeatherService,
     #[serde(default = "WeatherConfig::default_autolocate")]
     pub autolocate: bool,
+    /// Number of compass points (4, 8 or 16) `{wind_direction}` is rounded to
+    #[serde(default = "WeatherConfig::default_compass_points")]
+    pub compass_points: usize,
 }
 
 impl WeatherConfig {
@@ -329,6 +529,10 @@ impl WeatherConfig {
     fn default_autolocate() -> bool {
         false
     }
+
+    fn default_compass_points() -> usize {
+        8
+    }
 }
 
 impl ConfigBlock for Weather {
@@ -340,14 +544,19 @@ impl ConfigBlock for Weather {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        if ![4, 8, 16].contains(&block_config.compass_points) {
+            return Err(configuration_error("compass_points must be 4, 8 or 16"));
+        }
         Ok(Weather {
             id,
             weather: TextWidget::new(id, 0, shared_config),
             format: block_config.format,
             weather_keys: HashMap::new(),
-            service: block_config.service,
+            units: block_config.service.units(),
+            provider: block_config.service.into_provider(),
             update_interval: block_config.interval,
             autolocate: block_config.autolocate,
+            compass_points: block_config.compass_points,
         })
     }
 }
@@ -390,3 +599,59 @@ impl Block for Weather {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metno_symbols() {
+        let (conditions, weather, verbose) = metno_symbol("lightrainshowers_day");
+        assert_eq!(conditions, Conditions::Rain);
+        assert_eq!(weather, "Rain");
+        assert_eq!(verbose, "light rain showers");
+
+        let (conditions, _, verbose) = metno_symbol("heavysleetshowersandthunder_night");
+        assert_eq!(conditions, Conditions::Thunder);
+        assert_eq!(verbose, "heavy sleet showers and thunder");
+
+        assert_eq!(metno_symbol("clearsky_day").0, Conditions::Clear);
+        assert_eq!(metno_symbol("clearsky_day").2, "clear sky");
+        assert_eq!(metno_symbol("partlycloudy_night").2, "partly cloudy");
+        assert_eq!(metno_symbol("fog").1, "Fog");
+        assert_eq!(metno_symbol("").0, Conditions::Other);
+    }
+
+    #[test]
+    fn compass_directions() {
+        let eight = |degrees| compass_direction(Some(degrees), 8);
+        assert_eq!(eight(0.), "N");
+        assert_eq!(eight(22.), "N");
+        assert_eq!(eight(23.), "NE");
+        assert_eq!(eight(90.), "E");
+        assert_eq!(eight(200.), "S");
+        assert_eq!(eight(315.), "NW");
+        assert_eq!(eight(350.), "N");
+        assert_eq!(eight(360.), "N");
+        assert_eq!(eight(-45.), "NW");
+
+        let sixteen = |degrees| compass_direction(Some(degrees), 16);
+        assert_eq!(sixteen(0.), "N");
+        assert_eq!(sixteen(22.5), "NNE");
+        assert_eq!(sixteen(200.), "SSW");
+        assert_eq!(sixteen(292.), "WNW");
+        assert_eq!(sixteen(355.), "N");
+
+        assert_eq!(compass_direction(Some(100.), 4), "E");
+        assert_eq!(compass_direction(Some(150.), 4), "S");
+        assert_eq!(compass_direction(None, 8), "-");
+    }
+
+    #[test]
+    fn apparent_temp() {
+        // Without wind and at 50% humidity, 25°C feel like about 26°C
+        let at = australian_apparent_temp(25.0, 50.0, 0.0);
+        assert!((at - 26.2).abs() < 0.1, "{}", at);
+        assert!(australian_apparent_temp(25.0, 50.0, 10.0) < at);
+    }
+}
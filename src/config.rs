@@ -1,14 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::Duration;
 
+use lazy_static::lazy_static;
+use log::warn;
 use serde::de::{Deserialize, Deserializer};
 use serde_derive::Deserialize;
 use toml::value;
 
-use crate::errors;
-use crate::icons::Icons;
+use crate::de::deserialize_duration;
+use crate::errors::{self, ConfigurationError, ResultExtInternal};
+use crate::icons::{Icon, Icons};
 use crate::protocol::i3bar_event::MouseButton;
 use crate::themes::Theme;
+use crate::util;
+
+lazy_static! {
+    /// Icons a warning has already been printed for
+    static ref MISSING_ICONS: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
 
 #[derive(Debug)]
 pub struct SharedConfig {
@@ -32,6 +44,12 @@ impl SharedConfig {
         self.icons_format = icons_format;
     }
 
+    pub fn icons_override(&mut self, overrides: &HashMap<String, Icon>) {
+        let mut icons = self.icons.as_ref().clone();
+        icons.extend(overrides.clone());
+        self.icons = Rc::new(icons);
+    }
+
     pub fn theme_override(&mut self, overrides: &HashMap<String, String>) -> errors::Result<()> {
         let mut theme = self.theme.as_ref().clone();
         for entry in overrides {
@@ -46,6 +64,14 @@ impl SharedConfig {
                 "warning_bg" => theme.warning_bg = Some(entry.1.to_string()),
                 "critical_fg" => theme.critical_fg = Some(entry.1.to_string()),
                 "critical_bg" => theme.critical_bg = Some(entry.1.to_string()),
+                "bar_filled" => theme.bar_filled = Some(entry.1.to_string()),
+                "bar_empty" => theme.bar_empty = Some(entry.1.to_string()),
+                "graph_ramp" => theme.graph_ramp = Some(entry.1.to_string()),
+                "idle_bg_end" => theme.idle_bg_end = Some(entry.1.to_string()),
+                "info_bg_end" => theme.info_bg_end = Some(entry.1.to_string()),
+                "good_bg_end" => theme.good_bg_end = Some(entry.1.to_string()),
+                "warning_bg_end" => theme.warning_bg_end = Some(entry.1.to_string()),
+                "critical_bg_end" => theme.critical_bg_end = Some(entry.1.to_string()),
                 x => {
                     return Err(errors::ConfigurationError(
                         format!("Theme element \"{}\" cannot be overriden", x),
@@ -58,15 +84,32 @@ impl SharedConfig {
         Ok(())
     }
 
+    /// Icons missing from the icon set are replaced by its `fallback` icon (nothing unless set),
+    /// so a partial icon set doesn't keep blocks from starting.
     pub fn get_icon(&self, icon: &str) -> crate::errors::Result<String> {
-        use crate::errors::OptionExt;
-        Ok(self.icons_format.clone().replace(
-            "{icon}",
-            self.icons
-                .0
-                .get(icon)
-                .internal_error("get_icon()", &format!("icon '{}' not found: please check your icon file or open a new issue on GitHub if you use a precompiled icons.", icon))?,
-        ))
+        let glyph = match self.icons.icons.get(icon) {
+            Some(glyph) => glyph.as_str(),
+            None => {
+                if let Ok(mut missing) = MISSING_ICONS.lock() {
+                    if missing.insert(icon.to_string()) {
+                        warn!("icon '{}' not found: please check your icon file or open a new issue on GitHub if you use a precompiled icons.", icon);
+                    }
+                }
+                self.icons.icons.get("fallback").map_or("", String::as_str)
+            }
+        };
+        Ok(self.icons_format.replace("{icon}", glyph))
+    }
+
+    pub fn has_icon(&self, icon: &str) -> bool {
+        self.icons.icons.contains_key(icon)
+    }
+
+    /// The icon of the ramp `ramp` for `percents`, if the icon set has such a ramp
+    pub fn get_ramp_icon(&self, ramp: &str, percents: f64) -> Option<String> {
+        self.icons
+            .ramp_icon(ramp, percents)
+            .map(|glyph| self.icons_format.replace("{icon}", glyph))
     }
 }
 
@@ -111,14 +154,147 @@ pub struct Config {
     #[serde(default)]
     pub scrolling: Scrolling,
 
-    #[serde(rename = "block", deserialize_with = "deserialize_blocks")]
+    /// Fraction (0 to 1) of their interval the updates of blocks are randomly delayed by, so that
+    /// blocks with the same interval don't all update at once.
+    #[serde(default)]
+    pub interval_jitter: f64,
+
+    /// How long to wait after a block changed before printing the bar, so that the changes of
+    /// several blocks arriving in this window are written at once.
+    #[serde(
+        default = "Config::default_flush_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub flush_interval: Duration,
+
+    /// Whether environment variables that are not set are an error in the paths of the blocks,
+    /// instead of being kept as they are
+    #[serde(default)]
+    pub strict_env_vars: bool,
+
+    /// Files (relative to this one) whose blocks are inserted before the blocks of this file,
+    /// see `Config::expand_includes`
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+
+    #[serde(rename = "block", default, deserialize_with = "deserialize_blocks")]
     pub blocks: Vec<(String, value::Value)>,
 }
 
+/// A file listed in `include`, which may only contain blocks and further includes
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct IncludedConfig {
+    #[serde(default)]
+    include: Vec<PathBuf>,
+
+    #[serde(rename = "block", default, deserialize_with = "deserialize_blocks")]
+    blocks: Vec<(String, value::Value)>,
+}
+
 impl Config {
     fn default_icons_format() -> String {
         " {icon} ".to_string()
     }
+
+    fn default_flush_interval() -> Duration {
+        Duration::from_millis(50)
+    }
+
+    /// Inserts the blocks of the included files (and of the files they include, recursively) in
+    /// place of the `include` list of the config loaded from `config_path`, which comes before
+    /// its own blocks.
+    pub fn expand_includes(&mut self, config_path: &Path) -> errors::Result<()> {
+        let mut stack = vec![canonical(config_path)?];
+        let mut blocks = Vec::new();
+        let dir = config_path.parent().unwrap_or_else(|| Path::new(""));
+        for include in self.include.drain(..) {
+            include_blocks(&dir.join(include), &mut stack, &mut blocks)?;
+        }
+        blocks.append(&mut self.blocks);
+        self.blocks = blocks;
+        Ok(())
+    }
+
+    /// Checks the top-level options that are not validated by their types
+    pub fn validate(&self, errors: &mut ConfigErrors) {
+        if !(0. ..=1.).contains(&self.interval_jitter) {
+            errors.push(ConfigurationError(
+                "interval_jitter must be between 0 and 1".to_string(),
+                String::new(),
+            ));
+        }
+    }
+}
+
+/// The errors found while setting up the bar from the config (e.g. of every block that failed to
+/// be created), collected instead of stopping at the first one so that they are reported at once.
+#[derive(Default)]
+pub struct ConfigErrors(Vec<errors::Error>);
+
+impl ConfigErrors {
+    pub fn push(&mut self, error: errors::Error) {
+        self.0.push(error);
+    }
+
+    /// The value of `result`, or `None` after recording its error
+    pub fn check<T>(&mut self, result: errors::Result<T>) -> Option<T> {
+        result.map_err(|e| self.push(e)).ok()
+    }
+
+    /// Fails with the collected errors, a single error being returned as it is
+    pub fn into_result(mut self) -> errors::Result<()> {
+        match self.0.len() {
+            0 => Ok(()),
+            1 => Err(self.0.remove(0)),
+            count => Err(ConfigurationError(
+                format!(
+                    "{} errors:\n{}",
+                    count,
+                    self.0
+                        .iter()
+                        .map(|e| format!("- {}", e))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ),
+                self.0
+                    .iter()
+                    .map(|e| format!("{:?}", e))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )),
+        }
+    }
+}
+
+fn canonical(path: &Path) -> errors::Result<PathBuf> {
+    path.canonicalize()
+        .configuration_error(&format!("failed to open config file '{}'", path.display()))
+}
+
+/// Appends the blocks of the included file at `path` to `blocks`, after those of the files it
+/// includes. `stack` holds the files currently being included, to detect include cycles.
+fn include_blocks(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    blocks: &mut Vec<(String, value::Value)>,
+) -> errors::Result<()> {
+    let path = canonical(path)?;
+    if stack.contains(&path) {
+        return Err(ConfigurationError(
+            format!("config file '{}' includes itself", path.display()),
+            String::new(),
+        ));
+    }
+    let mut included: IncludedConfig = util::deserialize_file(&path)?;
+    let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    stack.push(path);
+    for include in included.include.drain(..) {
+        include_blocks(&dir.join(include), stack, blocks)?;
+    }
+    blocks.append(&mut included.blocks);
+    stack.pop();
+    Ok(())
 }
 
 impl Default for Config {
@@ -128,6 +304,10 @@ impl Default for Config {
             theme: Theme::default(),
             icons_format: Config::default_icons_format(),
             scrolling: Scrolling::default(),
+            interval_jitter: 0.,
+            flush_interval: Config::default_flush_interval(),
+            strict_env_vars: false,
+            include: Vec::new(),
             blocks: Vec::new(),
         }
     }
@@ -181,3 +361,69 @@ where
 
     Ok(blocks)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_names(config: &Config) -> Vec<&str> {
+        config
+            .blocks
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn includes() {
+        let dir = util::test_path("includes");
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let main = dir.join("config.toml");
+        std::fs::write(
+            &main,
+            "include = [\"sub/a.toml\", \"b.toml\"]\n[[block]]\nblock = \"time\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("sub/a.toml"),
+            "include = [\"c.toml\"]\n[[block]]\nblock = \"cpu\"\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("sub/c.toml"), "[[block]]\nblock = \"load\"\n").unwrap();
+        std::fs::write(dir.join("b.toml"), "[[block]]\nblock = \"memory\"\n").unwrap();
+
+        let mut config: Config = util::deserialize_file(&main).unwrap();
+        config.expand_includes(&main).unwrap();
+        assert_eq!(block_names(&config), ["load", "cpu", "memory", "time"]);
+        assert!(config.include.is_empty());
+
+        std::fs::write(dir.join("sub/c.toml"), "include = [\"../config.toml\"]\n").unwrap();
+        let mut config: Config = util::deserialize_file(&main).unwrap();
+        let err = config.expand_includes(&main).err().unwrap();
+        assert!(err.to_string().contains("includes itself"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn config_errors() {
+        assert!(ConfigErrors::default().into_result().is_ok());
+
+        let mut errors = ConfigErrors::default();
+        assert_eq!(errors.check(Ok(1)), Some(1));
+        let err: errors::Result<()> = Err(ConfigurationError("first".into(), String::new()));
+        assert_eq!(errors.check(err), None);
+        assert_eq!(
+            errors.into_result().err().unwrap().to_string(),
+            "Configuration error: first"
+        );
+
+        let mut errors = ConfigErrors::default();
+        errors.push(ConfigurationError("first".into(), String::new()));
+        errors.push(errors::Error::BlockError("cpu".into(), "second".into()));
+        assert_eq!(
+            errors.into_result().err().unwrap().to_string(),
+            "Configuration error: 2 errors:\n- Configuration error: first\n- Error in block 'cpu': second"
+        );
+    }
+}
@@ -1,9 +1,12 @@
+use std::env;
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 use crate::blocks::Update;
 use chrono::{DateTime, Local};
 use serde::de::{self, Deserialize, Deserializer};
+use serde_derive::Deserialize;
 
 pub fn deserialize_update<'de, D>(deserializer: D) -> Result<Update, D::Error>
 where
@@ -100,11 +103,374 @@ where
     i64::deserialize(deserializer).map(|seconds| Local.timestamp(seconds, 0))
 }
 
+/// Parses a size like `"512MiB"`, `"2 GB"` or `"100"` (bytes) into bytes. Both SI (`kB`, `MB`,
+/// ...) and IEC (`KiB`, `MiB`, ...) suffixes are accepted, in any case.
+pub fn parse_bytes(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, suffix) = size.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", size))?;
+    let multiplier: u64 = match suffix.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "tb" => 1_000_000_000_000,
+        "pb" => 1_000_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        "pib" => 1 << 50,
+        _ => {
+            return Err(format!(
+                "unknown unit '{}' in size '{}'",
+                suffix.trim(),
+                size
+            ))
+        }
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Deserializes a number of bytes, given either as an integer or as a size string (see
+/// `parse_bytes`).
+pub fn deserialize_bytes<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct BytesWrapper;
+
+    impl<'de> de::Visitor<'de> for BytesWrapper {
+        type Value = u64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str(r#"a number of bytes or a size like "512MiB""#)
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            if value < 0 {
+                Err(de::Error::custom("a size can't be negative"))
+            } else {
+                Ok(value as u64)
+            }
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            Ok(value)
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            parse_bytes(value).map_err(de::Error::custom)
+        }
+    }
+
+    deserializer.deserialize_any(BytesWrapper)
+}
+
+/// Normalizes a percentage given as a number: numbers below 1 are fractions (`0.8` is 80%),
+/// others are percents.
+fn percent_from_number(value: f64) -> Result<f64, String> {
+    let percents = if (0. ..1.).contains(&value) {
+        value * 100.
+    } else {
+        value
+    };
+    if (0. ..=100.).contains(&percents) {
+        Ok(percents)
+    } else {
+        Err(format!(
+            "invalid percentage {}, expected a value between 0% and 100%",
+            value
+        ))
+    }
+}
+
+/// Parses a percentage like `"80%"`
+fn parse_percent(value: &str) -> Result<f64, String> {
+    let percents: f64 = value
+        .trim()
+        .strip_suffix('%')
+        .and_then(|number| number.trim().parse().ok())
+        .ok_or_else(|| format!("invalid percentage '{}', expected e.g. \"80%\"", value))?;
+    if (0. ..=100.).contains(&percents) {
+        Ok(percents)
+    } else {
+        Err(format!(
+            "invalid percentage '{}', expected a value between 0% and 100%",
+            value
+        ))
+    }
+}
+
+struct PercentVisitor;
+
+impl<'de> de::Visitor<'de> for PercentVisitor {
+    type Value = f64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(r#"a percentage like "80%", 80 or 0.8"#)
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        percent_from_number(value as f64).map_err(de::Error::custom)
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        percent_from_number(value as f64).map_err(de::Error::custom)
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        percent_from_number(value).map_err(de::Error::custom)
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        parse_percent(value).map_err(de::Error::custom)
+    }
+}
+
+/// Deserializes a percentage given as `"80%"`, `80` or `0.8` into percents (0 to 100)
+pub fn deserialize_percent<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_any(PercentVisitor)
+}
+
+pub fn deserialize_opt_percent<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_percent(deserializer).map(Some)
+}
+
+/// Deserializes a `Threshold` that is either a percentage (see `deserialize_percent`) or a size
+/// (see `parse_bytes`), e.g. `"80%"`, `80` or `"4GiB"`
+pub fn deserialize_percent_threshold<'de, D>(deserializer: D) -> Result<Threshold, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct ThresholdVisitor;
+
+    impl<'de> de::Visitor<'de> for ThresholdVisitor {
+        type Value = Threshold;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str(r#"a percentage like "80%", 80 or 0.8, or a size like "4GiB""#)
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            PercentVisitor.visit_i64(value).map(Threshold::Value)
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            PercentVisitor.visit_u64(value).map(Threshold::Value)
+        }
+
+        fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            PercentVisitor.visit_f64(value).map(Threshold::Value)
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: de::Error,
+        {
+            if value.trim_end().ends_with('%') {
+                PercentVisitor.visit_str(value).map(Threshold::Value)
+            } else {
+                parse_bytes(value)
+                    .map(Threshold::Bytes)
+                    .map_err(de::Error::custom)
+            }
+        }
+    }
+
+    deserializer.deserialize_any(ThresholdVisitor)
+}
+
+/// Deserializes either a single value or a list of values (usually strings)
+pub fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    })
+}
+
+/// A threshold of a block: either a number in the block's own scale (usually percents), or an
+/// absolute size like `"2GiB"`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum Threshold {
+    Value(f64),
+    Bytes(#[serde(deserialize_with = "deserialize_bytes")] u64),
+}
+
+impl Threshold {
+    /// The threshold in the block's scale, where one unit is `bytes_per_unit` bytes
+    pub fn value(&self, bytes_per_unit: f64) -> f64 {
+        match *self {
+            Threshold::Value(value) => value,
+            Threshold::Bytes(bytes) => bytes as f64 / bytes_per_unit,
+        }
+    }
+}
+
+/// Whether unset environment variables in the config are an error, instead of being kept as
+/// they are. Set from the top-level `strict_env_vars` option before the blocks are created.
+static STRICT_ENV_VARS: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strict_env_vars(strict: bool) {
+    STRICT_ENV_VARS.store(strict, Ordering::Relaxed);
+}
+
+/// Replaces the `$VAR` and `${VAR}` references in `value` with the values returned by `lookup`.
+/// `$$` is a literal `$`. Unknown variables are kept as they are, unless `strict` is set.
+pub fn expand_vars(
+    value: &str,
+    strict: bool,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        if let Some(escaped) = after.strip_prefix('$') {
+            result.push('$');
+            rest = escaped;
+            continue;
+        }
+        let (name, reference_len) = if let Some(braced) = after.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| format!("missing '}}' after '${{' in '{}'", value))?;
+            (&braced[..end], end + 3)
+        } else {
+            let end = after
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after.len());
+            (&after[..end], end + 1)
+        };
+
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            // Not a variable, e.g. a lone `$` or awk's `$1`
+            result.push('$');
+            rest = after;
+            continue;
+        }
+        match lookup(name) {
+            Some(var) => result.push_str(&var),
+            None if strict => return Err(format!("environment variable '{}' is not set", name)),
+            None => result.push_str(&rest[start..start + reference_len]),
+        }
+        rest = &rest[start + reference_len..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// Deserializes a string (or path) with the environment variables in it expanded, see
+/// `expand_vars`
+pub fn deserialize_expanded<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<String>,
+{
+    let value = String::deserialize(deserializer)?;
+    expand_vars(&value, STRICT_ENV_VARS.load(Ordering::Relaxed), |name| {
+        env::var(name).ok()
+    })
+    .map(T::from)
+    .map_err(de::Error::custom)
+}
+
+pub fn deserialize_opt_expanded<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: From<String>,
+{
+    deserialize_expanded(deserializer).map(Some)
+}
+
+#[cfg(feature = "maildir")]
+pub fn deserialize_vec_expanded<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let strict = STRICT_ENV_VARS.load(Ordering::Relaxed);
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|value| expand_vars(value, strict, |name| env::var(name).ok()))
+        .collect::<Result<_, _>>()
+        .map_err(de::Error::custom)
+}
+
+/// `deserialize_one_or_many` with the environment variables expanded
+pub fn deserialize_one_or_many_expanded<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let strict = STRICT_ENV_VARS.load(Ordering::Relaxed);
+    deserialize_one_or_many::<D, String>(deserializer)?
+        .iter()
+        .map(|value| expand_vars(value, strict, |name| env::var(name).ok()))
+        .collect::<Result<_, _>>()
+        .map_err(de::Error::custom)
+}
+
 #[cfg(test)]
 mod tests {
     use crate::blocks::Update;
     use crate::blocks::Update::{Every, Once};
-    use crate::de::{deserialize_duration, deserialize_update};
+    use crate::de::{
+        deserialize_duration, deserialize_one_or_many, deserialize_percent,
+        deserialize_percent_threshold, deserialize_update, expand_vars, parse_bytes, Threshold,
+    };
     use serde_derive::Deserialize;
     use std::time::Duration;
 
@@ -146,4 +512,114 @@ mod tests {
         let deserialized: UpdateConfig = toml::from_str(duration_toml).unwrap();
         assert_eq!(Once, deserialized.interval);
     }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("100"), Ok(100));
+        assert_eq!(parse_bytes("100B"), Ok(100));
+        assert_eq!(parse_bytes("512MiB"), Ok(512 << 20));
+        assert_eq!(parse_bytes("2GB"), Ok(2_000_000_000));
+        assert_eq!(parse_bytes("1.5 kib"), Ok(1536));
+        assert_eq!(parse_bytes(" 3 TB "), Ok(3_000_000_000_000));
+        assert!(parse_bytes("2XB").is_err());
+        assert!(parse_bytes("MiB").is_err());
+        assert!(parse_bytes("").is_err());
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct ThresholdConfig {
+        pub warning: Threshold,
+    }
+
+    #[test]
+    fn test_deserialize_threshold() {
+        let threshold = |toml: &str| toml::from_str::<ThresholdConfig>(toml).map(|c| c.warning);
+        assert_eq!(threshold("warning = 80").unwrap(), Threshold::Value(80.));
+        assert_eq!(threshold("warning = 12.5").unwrap(), Threshold::Value(12.5));
+        assert_eq!(
+            threshold(r#"warning = "2GiB""#).unwrap(),
+            Threshold::Bytes(2 << 30)
+        );
+        assert!(threshold(r#"warning = "lots""#).is_err());
+        assert_eq!(Threshold::Bytes(2 << 30).value((1 << 30) as f64), 2.);
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct PercentConfig {
+        #[serde(deserialize_with = "deserialize_percent")]
+        pub warning: f64,
+    }
+
+    #[test]
+    fn test_deserialize_percent() {
+        let percent = |toml: &str| toml::from_str::<PercentConfig>(toml).map(|c| c.warning);
+        assert_eq!(percent("warning = 80").unwrap(), 80.);
+        assert_eq!(percent("warning = 0.8").unwrap(), 80.);
+        assert_eq!(percent(r#"warning = "80%""#).unwrap(), 80.);
+        assert_eq!(percent(r#"warning = "0.5 %""#).unwrap(), 0.5);
+        assert_eq!(percent("warning = 100").unwrap(), 100.);
+        assert!(percent("warning = 120").is_err());
+        assert!(percent("warning = -1").is_err());
+        assert!(percent(r#"warning = "80""#).is_err());
+        assert!(percent(r#"warning = "150%""#).is_err());
+    }
+
+    #[derive(Deserialize, Debug, Clone)]
+    pub struct PercentThresholdConfig {
+        #[serde(deserialize_with = "deserialize_percent_threshold")]
+        pub warning: Threshold,
+    }
+
+    #[test]
+    fn test_deserialize_percent_threshold() {
+        let threshold =
+            |toml: &str| toml::from_str::<PercentThresholdConfig>(toml).map(|c| c.warning);
+        assert_eq!(threshold("warning = 0.8").unwrap(), Threshold::Value(80.));
+        assert_eq!(
+            threshold(r#"warning = "80%""#).unwrap(),
+            Threshold::Value(80.)
+        );
+        assert_eq!(
+            threshold(r#"warning = "4GiB""#).unwrap(),
+            Threshold::Bytes(4 << 30)
+        );
+        assert!(threshold("warning = 200").is_err());
+    }
+
+    #[test]
+    fn test_deserialize_one_or_many() {
+        #[derive(Deserialize)]
+        struct Chips {
+            #[serde(deserialize_with = "deserialize_one_or_many")]
+            chip: Vec<String>,
+        }
+        let chips = |toml: &str| toml::from_str::<Chips>(toml).map(|c| c.chip);
+        assert_eq!(chips(r#"chip = "k10temp-*""#).unwrap(), vec!["k10temp-*"]);
+        assert_eq!(
+            chips(r#"chip = ["coretemp-*", "nvme-*"]"#).unwrap(),
+            vec!["coretemp-*", "nvme-*"]
+        );
+        assert!(chips("chip = 5").is_err());
+    }
+
+    #[test]
+    fn test_expand_vars() {
+        let lookup = |name: &str| match name {
+            "HOME" => Some("/home/user".to_string()),
+            "MAIL_DIR" => Some("mail".to_string()),
+            _ => None,
+        };
+        let expand = |value: &str, strict: bool| expand_vars(value, strict, lookup);
+        assert_eq!(expand("$HOME/.mail", false).unwrap(), "/home/user/.mail");
+        assert_eq!(
+            expand("${HOME}/${MAIL_DIR}x", false).unwrap(),
+            "/home/user/mailx"
+        );
+        assert_eq!(expand("cost: $$5, $1 $", false).unwrap(), "cost: $5, $1 $");
+        assert_eq!(expand("$UNSET/${UNSET}", false).unwrap(), "$UNSET/${UNSET}");
+        assert!(expand("$UNSET", true)
+            .unwrap_err()
+            .contains("'UNSET' is not set"));
+        assert!(expand("${HOME", false).is_err());
+    }
 }
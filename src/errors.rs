@@ -41,6 +41,16 @@ where
     }
 }
 
+pub trait ResultExtContext<T> {
+    fn in_block(self, name: &str, id: usize) -> Result<T>;
+}
+
+impl<T> ResultExtContext<T> for Result<T> {
+    fn in_block(self, name: &str, id: usize) -> Result<T> {
+        self.map_err(|e| e.in_block(name, id))
+    }
+}
+
 pub trait OptionExt<T> {
     fn block_error(self, block: &str, message: &str) -> Result<T>;
     fn internal_error(self, context: &str, message: &str) -> Result<T>;
@@ -64,6 +74,23 @@ pub enum Error {
     InternalError(String, String, Option<(String, String)>),
 }
 
+impl Error {
+    /// Adds the name of the block the error occurred in and, to tell apart several blocks of the
+    /// same kind, its position in the config (`id` is 0-based).
+    pub fn in_block(self, name: &str, id: usize) -> Error {
+        let block = format!("{} #{}", name, id + 1);
+        match self {
+            BlockError(_, message) => BlockError(block, message),
+            ConfigurationError(message, cause) => {
+                ConfigurationError(format!("in block '{}': {}", block, message), cause)
+            }
+            InternalError(context, message, cause) => {
+                InternalError(format!("{} in block {}", context, block), message, cause)
+            }
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -117,6 +144,43 @@ impl StdError for Error {
     }
 }
 
+/// Errors of the requests made with the `http` module, so that the blocks can handle them
+/// differently (e.g. retry after a timeout, but not after a rejected token)
+#[derive(Debug)]
+pub enum HttpError {
+    /// The request did not complete within its timeout
+    Timeout,
+    /// The server answered with an unexpected status code
+    Status(u16),
+    /// The request failed for any other reason, e.g. the server could not be resolved
+    Request(String),
+    /// The response is not the expected JSON
+    Parse(String),
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HttpError::Timeout => f.write_str("the request timed out"),
+            HttpError::Status(status) => write!(f, "unexpected HTTP status {}", status),
+            HttpError::Request(message) => write!(f, "the request failed: {}", message),
+            HttpError::Parse(message) => write!(f, "invalid response: {}", message),
+        }
+    }
+}
+
+impl StdError for HttpError {}
+
+impl From<HttpError> for Error {
+    fn from(err: HttpError) -> Self {
+        InternalError(
+            "curl".to_owned(),
+            err.to_string(),
+            Some((err.to_string(), format!("{:?}", err))),
+        )
+    }
+}
+
 impl<T> From<::crossbeam_channel::SendError<T>> for Error
 where
     T: Send,
@@ -125,3 +189,20 @@ where
         InternalError("unknown".to_string(), "send error".to_string(), None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn in_block() {
+        let err = BlockError("cpu".to_string(), "oops".to_string()).in_block("cpu", 2);
+        assert_eq!(err.to_string(), "Error in block 'cpu #3': oops");
+
+        let err: Result<()> = Err(ConfigurationError("bad".to_string(), String::new()));
+        assert_eq!(
+            err.in_block("time", 0).err().unwrap().to_string(),
+            "Configuration error: in block 'time #1': bad"
+        );
+    }
+}
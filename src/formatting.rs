@@ -1,3 +1,4 @@
+pub mod gradient;
 pub mod placeholder;
 pub mod prefix;
 pub mod unit;
@@ -21,6 +22,33 @@ pub struct FormatTemplate {
 enum Token {
     Text(String),
     Var(Placeholder),
+    /// `{?name}...{/name}`: the inner tokens are rendered only if `name` is non-zero/non-empty
+    Section(String, Vec<Token>),
+}
+
+/// A `format` template together with an optional `format_short` template, which the bar may
+/// show instead of the full text when it runs out of space.
+#[derive(Debug, Clone)]
+pub struct FormatPair {
+    full: FormatTemplate,
+    short: Option<FormatTemplate>,
+}
+
+impl FormatPair {
+    pub fn new(full: &str, short: Option<&str>) -> Result<Self> {
+        Ok(FormatPair {
+            full: FormatTemplate::from_string(full)?,
+            short: short.map(FormatTemplate::from_string).transpose()?,
+        })
+    }
+
+    /// Renders the full and (if configured) the short text
+    pub fn render(&self, vars: &HashMap<&str, Value>) -> Result<(String, Option<String>)> {
+        Ok((
+            self.full.render(vars)?,
+            self.short.as_ref().map(|f| f.render(vars)).transpose()?,
+        ))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -39,28 +67,82 @@ fn unexpected_token<T>(token: char) -> Result<T> {
     ))
 }
 
-impl FormatTemplate {
-    /// Whether the format string contains given placeholder
-    pub fn contains(&self, var: &str) -> bool {
-        for token in &self.tokens {
-            if let Token::Var(ref placeholder) = token {
-                if placeholder.name == var {
-                    return true;
+fn get_var<'a>(vars: &'a HashMap<&str, Value>, name: &str) -> Result<&'a Value> {
+    vars.get(name).internal_error(
+        "util",
+        &format!("Unknown placeholder in format string: {}", name),
+    )
+}
+
+fn tokens_contain(tokens: &[Token], var: &str) -> bool {
+    tokens.iter().any(|token| match token {
+        Token::Text(_) => false,
+        Token::Var(placeholder) => placeholder.name == var,
+        Token::Section(name, inner) => name == var || tokens_contain(inner, var),
+    })
+}
+
+fn render_tokens(
+    tokens: &[Token],
+    vars: &HashMap<&str, Value>,
+    rendered: &mut String,
+) -> Result<()> {
+    for token in tokens {
+        match token {
+            Token::Text(text) => rendered.push_str(text),
+            Token::Var(var) => rendered.push_str(&get_var(vars, &var.name)?.format(var)?),
+            Token::Section(name, inner) => {
+                if !get_var(vars, name)?.is_empty() {
+                    render_tokens(inner, vars, rendered)?;
                 }
             }
         }
-        false
+    }
+    Ok(())
+}
+
+fn render_token_widgets(
+    tokens: &[Token],
+    config: &SharedConfig,
+    id: usize,
+    vars: &HashMap<&str, Value>,
+    rendered: &mut Vec<RenderedWidget>,
+) -> Result<()> {
+    for token in tokens {
+        match token {
+            Token::Text(text) => rendered.push(RenderedWidget::Text(
+                TextWidget::new(id, rendered.len(), config.clone()).with_text(text),
+            )),
+            Token::Var(var) => rendered.push(RenderedWidget::Var(
+                var.name.clone(),
+                TextWidget::new(id, rendered.len(), config.clone())
+                    .with_spacing(Spacing::Hidden)
+                    .with_text(&get_var(vars, &var.name)?.format(var)?),
+            )),
+            Token::Section(name, inner) => {
+                if !get_var(vars, name)?.is_empty() {
+                    render_token_widgets(inner, config, id, vars, rendered)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl FormatTemplate {
+    /// Whether the format string contains given placeholder
+    pub fn contains(&self, var: &str) -> bool {
+        tokens_contain(&self.tokens, var)
     }
 
     pub fn from_string(s: &str) -> Result<Self> {
-        let mut tokens = vec![];
+        // The outermost frame holds the top level tokens, every other frame is an open section
+        let mut frames: Vec<(String, Vec<Token>)> = vec![(String::new(), vec![])];
 
         let mut text_buf = String::new();
         let mut var_buf = String::new();
         let mut inside_var = false;
 
-        let mut current_buf = &mut text_buf;
-
         for c in s.chars() {
             match c {
                 '{' => {
@@ -68,22 +150,40 @@ impl FormatTemplate {
                         return unexpected_token(c);
                     }
                     if !text_buf.is_empty() {
+                        let (_, tokens) = frames.last_mut().unwrap();
                         tokens.push(Token::Text(text_buf.clone()));
                         text_buf.clear();
                     }
-                    current_buf = &mut var_buf;
                     inside_var = true;
                 }
                 '}' => {
                     if !inside_var {
                         return unexpected_token(c);
                     }
-                    tokens.push(Token::Var(var_buf.as_str().try_into()?));
+                    if let Some(name) = var_buf.strip_prefix('?') {
+                        frames.push((name.to_string(), vec![]));
+                    } else if let Some(name) = var_buf.strip_prefix('/') {
+                        if frames.len() == 1 || frames.last().unwrap().0 != name {
+                            return Err(ConfigurationError(
+                                format!(
+                                    "failed to parse formatting string: unexpected '{{/{}}}'",
+                                    name
+                                ),
+                                String::new(),
+                            ));
+                        }
+                        let (name, inner) = frames.pop().unwrap();
+                        let (_, tokens) = frames.last_mut().unwrap();
+                        tokens.push(Token::Section(name, inner));
+                    } else {
+                        let (_, tokens) = frames.last_mut().unwrap();
+                        tokens.push(Token::Var(var_buf.as_str().try_into()?));
+                    }
                     var_buf.clear();
-                    current_buf = &mut text_buf;
                     inside_var = false;
                 }
-                x => current_buf.push(x),
+                x if inside_var => var_buf.push(x),
+                x => text_buf.push(x),
             }
         }
         if inside_var {
@@ -92,6 +192,16 @@ impl FormatTemplate {
                 "".to_string(),
             ));
         }
+        if frames.len() > 1 {
+            return Err(ConfigurationError(
+                format!(
+                    "failed to parse formatting string: missing '{{/{}}}'",
+                    frames.last().unwrap().0
+                ),
+                String::new(),
+            ));
+        }
+        let (_, mut tokens) = frames.pop().unwrap();
         if !text_buf.is_empty() {
             tokens.push(Token::Text(text_buf.clone()));
         }
@@ -101,22 +211,7 @@ impl FormatTemplate {
 
     pub fn render(&self, vars: &HashMap<&str, Value>) -> Result<String> {
         let mut rendered = String::new();
-
-        for token in &self.tokens {
-            match token {
-                Token::Text(text) => rendered.push_str(&text),
-                Token::Var(var) => rendered.push_str(
-                    &vars
-                        .get(&*var.name)
-                        .internal_error(
-                            "util",
-                            &format!("Unknown placeholder in format string: {}", var.name),
-                        )?
-                        .format(&var)?,
-                ),
-            }
-        }
-
+        render_tokens(&self.tokens, vars, &mut rendered)?;
         Ok(rendered)
     }
 
@@ -129,29 +224,7 @@ impl FormatTemplate {
         vars: &HashMap<&str, Value>,
     ) -> Result<Vec<RenderedWidget>> {
         let mut rendered = Vec::new();
-
-        for token in &self.tokens {
-            match token {
-                Token::Text(text) => rendered.push(RenderedWidget::Text(
-                    TextWidget::new(id, rendered.len(), config.clone()).with_text(text),
-                )),
-                Token::Var(var) => rendered.push(RenderedWidget::Var(
-                    var.name.clone(),
-                    TextWidget::new(id, rendered.len(), config.clone())
-                        .with_spacing(Spacing::Hidden)
-                        .with_text(
-                            &vars
-                                .get(&*var.name)
-                                .internal_error(
-                                    "util",
-                                    &format!("Unknown placeholder in format string: {}", var.name),
-                                )?
-                                .format(&var)?,
-                        ),
-                )),
-            }
-        }
-
+        render_token_widgets(&self.tokens, &config, id, vars, &mut rendered)?;
         Ok(rendered)
     }
 }
@@ -181,6 +254,9 @@ mod tests {
                 min_width: None,
                 max_width: None,
                 pad_with: None,
+                align: None,
+                grouping: None,
+                precision: None,
                 min_prefix: None,
                 unit: None,
                 unit_hidden: false,
@@ -198,6 +274,9 @@ mod tests {
                 min_width: None,
                 max_width: None,
                 pad_with: None,
+                align: None,
+                grouping: None,
+                precision: None,
                 min_prefix: None,
                 unit: Some(Unit::None),
                 unit_hidden: true,
@@ -211,6 +290,9 @@ mod tests {
                 min_width: Some(3),
                 max_width: None,
                 pad_with: None,
+                align: None,
+                grouping: None,
+                precision: None,
                 min_prefix: None,
                 unit: None,
                 unit_hidden: false,
@@ -225,6 +307,9 @@ mod tests {
                 min_width: Some(2),
                 max_width: None,
                 pad_with: None,
+                align: None,
+                grouping: None,
+                precision: None,
                 min_prefix: None,
                 unit: None,
                 unit_hidden: false,
@@ -239,6 +324,9 @@ mod tests {
                 min_width: None,
                 max_width: None,
                 pad_with: None,
+                align: None,
+                grouping: None,
+                precision: None,
                 min_prefix: Some(Prefix::One),
                 unit: None,
                 unit_hidden: false,
@@ -269,6 +357,142 @@ mod tests {
         );
     }
 
+    #[test]
+    fn render_grouping() {
+        let ft = FormatTemplate::from_string("{a:g} {b:g } {c:8g} {d:g}").unwrap();
+        let values = map!(
+            "a" => Value::from_integer(1234567),
+            "b" => Value::from_integer(-1234),
+            "c" => Value::from_integer(12345),
+            "d" => Value::from_float(12345.),
+        );
+        assert_eq!(
+            ft.render(&values).unwrap().as_str(),
+            "1,234,567 -1 234   12,345 12,345"
+        );
+    }
+
+    #[test]
+    fn render_alignment() {
+        let ft = FormatTemplate::from_string("{a:>4}|{b:<4}|{c:_<4}|{d:█>3}").unwrap();
+        let values = map!(
+            "a" => Value::from_string("ab".to_string()),
+            "b" => Value::from_integer(12),
+            "c" => Value::from_integer(12),
+            "d" => Value::from_string("a".to_string()),
+        );
+        assert_eq!(ft.render(&values).unwrap().as_str(), "  ab|12  |12__|██a");
+    }
+
+    #[test]
+    fn render_precision() {
+        let ft = FormatTemplate::from_string("{a:.2} {a:.0} {b:6.1} {c:g..1}").unwrap();
+        let values = map!(
+            "a" => Value::from_float(1.23456),
+            "b" => Value::from_float(2048.).bytes(),
+            "c" => Value::from_float(1234.56),
+        );
+        assert_eq!(
+            ft.render(&values).unwrap().as_str(),
+            "1.23   1   2.0KB 1.234.6"
+        );
+    }
+
+    #[test]
+    fn render_byte_prefixes() {
+        let ft = FormatTemplate::from_string("{a} {a;Mi} {b}").unwrap();
+        let values = map!(
+            "a" => Value::from_float(1536. * 1024.).bytes(),
+            "b" => Value::from_float(1_500_000.).bytes().byte_prefix(prefix::BytePrefix::Si),
+        );
+        assert_eq!(ft.render(&values).unwrap().as_str(), "1.5MB 1.5MiB 1.5MB");
+    }
+
+    #[test]
+    fn render_escaping() {
+        let ft = FormatTemplate::from_string("<b>{a}</b> {b} {c^5}").unwrap();
+        let values = map!(
+            "a" => Value::from_string("Tom & Jerry".to_string()),
+            "b" => Value::from_string("<i>x</i>".to_string()).markup(),
+            "c" => Value::from_string("a<b>c".to_string()),
+        );
+        assert_eq!(
+            ft.render(&values).unwrap().as_str(),
+            "<b>Tom &amp; Jerry</b> <i>x</i> a&lt;b&gt;c"
+        );
+    }
+
+    #[test]
+    fn render_sections() {
+        let ft = FormatTemplate::from_string("{total}{?mention} ({mention} mentions){/mention}")
+            .unwrap();
+        let values = map!(
+            "total" => Value::from_integer(3),
+            "mention" => Value::from_integer(2),
+        );
+        assert_eq!(ft.render(&values).unwrap().as_str(), " 3 ( 2 mentions)");
+        let values = map!(
+            "total" => Value::from_integer(3),
+            "mention" => Value::from_integer(0),
+        );
+        assert_eq!(ft.render(&values).unwrap().as_str(), " 3");
+        assert!(ft.contains("mention"));
+
+        assert!(FormatTemplate::from_string("{?a}{?b}{/a}{/b}").is_err());
+        assert!(FormatTemplate::from_string("{?a} text").is_err());
+        assert!(FormatTemplate::from_string("text {/a}").is_err());
+    }
+
+    #[test]
+    fn render_pair() {
+        let values = map!("total" => Value::from_integer(5));
+        let pair = FormatPair::new("{total} notifications", Some("{total}")).unwrap();
+        assert_eq!(
+            pair.render(&values).unwrap(),
+            (" 5 notifications".to_string(), Some(" 5".to_string()))
+        );
+        let pair = FormatPair::new("{total}", None).unwrap();
+        assert_eq!(pair.render(&values).unwrap(), (" 5".to_string(), None));
+        assert!(FormatPair::new("{total}", Some("{total")).is_err());
+    }
+
+    #[test]
+    fn render_gradient() {
+        let gradient: gradient::Gradient = toml::from_str::<HashMap<String, gradient::Gradient>>(
+            r##"g = [{value = 0, color = "#00FF00"}, {value = 100, color = "#FF0000"}]"##,
+        )
+        .unwrap()
+        .remove("g")
+        .unwrap();
+        let ft = FormatTemplate::from_string("{a} {b}").unwrap();
+        let values = map!(
+            "a" => Value::from_integer(50).percents().gradient(&gradient),
+            "b" => Value::from_string("text".to_string()).gradient(&gradient),
+        );
+        assert_eq!(
+            ft.render(&values).unwrap().as_str(),
+            "<span foreground=\"#808000\">50%</span> text"
+        );
+    }
+
+    #[test]
+    fn render_duration() {
+        use std::time::Duration;
+
+        let ft = FormatTemplate::from_string("{a}|{a:1}|{a:3}|{a*h}|{b}|{c}").unwrap();
+        let values = map!(
+            "a" => Value::from_duration(Duration::from_secs(2 * 86_400 + 3 * 3_600 + 42)),
+            "b" => Value::from_duration(Duration::from_secs(3_900)),
+            "c" => Value::from_duration(Duration::from_secs(0)),
+        );
+        assert_eq!(
+            ft.render(&values).unwrap().as_str(),
+            "2d 3h|2d|2d 3h 42s|51h 42s|1h 5m|0s"
+        );
+        let ft = FormatTemplate::from_string("{a*B}").unwrap();
+        assert!(ft.render(&values).is_err());
+    }
+
     #[test]
     fn contains() {
         let format = FormatTemplate::from_string("some text {foo} {bar:1} foobar");
@@ -0,0 +1,99 @@
+use std::convert::TryFrom;
+
+use serde_derive::Deserialize;
+
+use crate::util::color_from_rgba;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GradientStop {
+    pub value: f64,
+    pub color: String,
+}
+
+/// Maps numeric values to colors. Values between two stops get a linearly interpolated color,
+/// values outside of the stops get the color of the nearest one.
+///
+/// Configured as a list of stops, e.g.
+/// `gradient = [{value = 0, color = "#00FF00"}, {value = 100, color = "#FF0000"}]`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(try_from = "Vec<GradientStop>")]
+pub struct Gradient {
+    stops: Vec<(f64, (u8, u8, u8))>,
+}
+
+impl TryFrom<Vec<GradientStop>> for Gradient {
+    type Error = String;
+
+    fn try_from(stops: Vec<GradientStop>) -> Result<Self, Self::Error> {
+        if stops.is_empty() {
+            return Err("a gradient needs at least one stop".to_string());
+        }
+        let mut stops = stops
+            .into_iter()
+            .map(|stop| {
+                let (r, g, b, _) = color_from_rgba(&stop.color)
+                    .map_err(|e| format!("invalid color '{}': {}", stop.color, e))?;
+                Ok((stop.value, (r, g, b)))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        stops.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(Gradient { stops })
+    }
+}
+
+impl Gradient {
+    /// The color (as `#RRGGBB`) for `value`
+    pub fn color_at(&self, value: f64) -> String {
+        let (first, last) = (self.stops[0], self.stops[self.stops.len() - 1]);
+        let (r, g, b) = if value <= first.0 {
+            first.1
+        } else if value >= last.0 {
+            last.1
+        } else {
+            let upper = self.stops.iter().position(|s| s.0 >= value).unwrap();
+            let ((lo, lo_c), (hi, hi_c)) = (self.stops[upper - 1], self.stops[upper]);
+            let t = (value - lo) / (hi - lo);
+            let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+            (
+                mix(lo_c.0, hi_c.0),
+                mix(lo_c.1, hi_c.1),
+                mix(lo_c.2, hi_c.2),
+            )
+        };
+        format!("#{:02X}{:02X}{:02X}", r, g, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(value: f64, color: &str) -> GradientStop {
+        GradientStop {
+            value,
+            color: color.to_string(),
+        }
+    }
+
+    #[test]
+    fn color_at() {
+        let gradient = Gradient::try_from(vec![
+            stop(100., "#FF0000"),
+            stop(0., "#00FF00"),
+            stop(50., "#FFFF00"),
+        ])
+        .unwrap();
+        assert_eq!(gradient.color_at(-5.), "#00FF00");
+        assert_eq!(gradient.color_at(25.), "#80FF00");
+        assert_eq!(gradient.color_at(50.), "#FFFF00");
+        assert_eq!(gradient.color_at(80.), "#FF6600");
+        assert_eq!(gradient.color_at(150.), "#FF0000");
+    }
+
+    #[test]
+    fn invalid_gradients() {
+        assert!(Gradient::try_from(vec![]).is_err());
+        assert!(Gradient::try_from(vec![stop(0., "green")]).is_err());
+    }
+}
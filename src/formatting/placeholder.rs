@@ -9,6 +9,15 @@ const MAX_WIDTH_TOKEN: char = '^';
 const MIN_SUFFIX_TOKEN: char = ';';
 const UNIT_TOKEN: char = '*';
 const BAR_MAX_VAL_TOKEN: char = '#';
+const GROUPING_FLAG: char = 'g';
+const DEFAULT_GROUPING_SEPARATOR: char = ',';
+const PRECISION_TOKEN: char = '.';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    Left,
+    Right,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Placeholder {
@@ -16,6 +25,9 @@ pub struct Placeholder {
     pub min_width: Option<usize>,
     pub max_width: Option<usize>,
     pub pad_with: Option<char>,
+    pub align: Option<Alignment>,
+    pub grouping: Option<char>,
+    pub precision: Option<usize>,
     pub min_prefix: Option<Prefix>,
     pub unit: Option<Unit>,
     pub unit_hidden: bool,
@@ -81,6 +93,56 @@ impl TryInto<Placeholder> for &str {
             }
         }
 
+        // Parse alignment (`<` or `>`, optionally preceded by the fill character)
+        let align_of = |c: Option<char>| match c {
+            Some('<') => Some(Alignment::Left),
+            Some('>') => Some(Alignment::Right),
+            _ => None,
+        };
+        let mut chars = min_width_buf.chars();
+        let (first, second) = (chars.next(), chars.next());
+        let (fill, align) = if let Some(align) = align_of(second) {
+            (first, Some(align))
+        } else if let Some(align) = align_of(first) {
+            (None, Some(align))
+        } else {
+            (None, None)
+        };
+        if align.is_some() {
+            let skip = if fill.is_some() { 2 } else { 1 };
+            min_width_buf = min_width_buf.chars().skip(skip).collect();
+        }
+        // Parse precision (`.` followed by the number of decimals). This is done before parsing
+        // the grouping so that `g.` still selects `.` as the separator.
+        let precision = match min_width_buf.rfind(PRECISION_TOKEN) {
+            Some(pos)
+                if pos + 1 < min_width_buf.len()
+                    && min_width_buf[pos + 1..].chars().all(|c| c.is_ascii_digit()) =>
+            {
+                let precision = min_width_buf[pos + 1..]
+                    .parse()
+                    .configuration_error(&format!(
+                        "failed to parse precision '{}'",
+                        &min_width_buf[pos + 1..]
+                    ))?;
+                min_width_buf.truncate(pos);
+                Some(precision)
+            }
+            _ => None,
+        };
+        // Parse digit grouping (`g` optionally followed by the separator)
+        let grouping = match min_width_buf.find(GROUPING_FLAG) {
+            Some(pos) => {
+                let mut separator = min_width_buf[pos + 1..].chars();
+                let grouping = separator.next().unwrap_or(DEFAULT_GROUPING_SEPARATOR);
+                if let Some(c) = separator.next() {
+                    return unexpected_token(c);
+                }
+                min_width_buf.truncate(pos);
+                Some(grouping)
+            }
+            None => None,
+        };
         // Parse padding
         let (min_width, pad_with) =
             if min_width_buf.is_empty() {
@@ -142,7 +204,10 @@ impl TryInto<Placeholder> for &str {
             name: var_buf,
             min_width,
             max_width,
-            pad_with,
+            pad_with: fill.or(pad_with),
+            align,
+            grouping,
+            precision,
             min_prefix,
             unit,
             unit_hidden,
@@ -1,6 +1,8 @@
 use std::convert::TryInto;
 use std::fmt;
 
+use serde_derive::Deserialize;
+
 use crate::errors::*;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -14,6 +16,66 @@ pub enum Prefix {
     Mega,
     Giga,
     Tera,
+    // IEC (binary) prefixes
+    Kibi,
+    Mebi,
+    Gibi,
+    Tebi,
+}
+
+/// How byte and bit values are scaled and labelled.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BytePrefix {
+    /// Powers of 1024 labelled with SI symbols, e.g. `1.0M` for 2^20 bytes
+    Binary,
+    /// Powers of 1000 labelled with SI symbols, e.g. `1.0M` for 10^6 bytes
+    Si,
+    /// Powers of 1024 labelled with IEC symbols, e.g. `1.0Mi` for 2^20 bytes
+    Iec,
+}
+
+impl Default for BytePrefix {
+    fn default() -> Self {
+        Self::Binary
+    }
+}
+
+impl Prefix {
+    /// The power of 1000 (or 1024 for binary prefixes) that this prefix stands for
+    pub fn exp_level(self) -> i32 {
+        match self {
+            Self::Tera | Self::Tebi => 4,
+            Self::Giga | Self::Gibi => 3,
+            Self::Mega | Self::Mebi => 2,
+            Self::Kilo | Self::Kibi => 1,
+            Self::One => 0,
+            Self::Milli => -1,
+            Self::Micro => -2,
+            Self::Nano => -3,
+        }
+    }
+
+    pub fn is_iec(self) -> bool {
+        matches!(self, Self::Kibi | Self::Mebi | Self::Gibi | Self::Tebi)
+    }
+
+    pub fn from_exp_level(exp_level: i32, iec: bool) -> Self {
+        match (exp_level, iec) {
+            (4, false) => Self::Tera,
+            (3, false) => Self::Giga,
+            (2, false) => Self::Mega,
+            (1, false) => Self::Kilo,
+            (4, true) => Self::Tebi,
+            (3, true) => Self::Gibi,
+            (2, true) => Self::Mebi,
+            (1, true) => Self::Kibi,
+            (0, _) => Self::One,
+            (-1, _) => Self::Milli,
+            (-2, _) => Self::Micro,
+            _ => Self::Nano,
+        }
+    }
 }
 
 impl fmt::Display for Prefix {
@@ -31,6 +93,11 @@ impl fmt::Display for Prefix {
                 Self::Mega => "M",
                 Self::Giga => "G",
                 Self::Tera => "T",
+                // IEC prefixes
+                Self::Kibi => "Ki",
+                Self::Mebi => "Mi",
+                Self::Gibi => "Gi",
+                Self::Tebi => "Ti",
             }
         )
     }
@@ -50,6 +117,11 @@ impl TryInto<Prefix> for &str {
             "M" => Ok(Prefix::Mega),
             "G" => Ok(Prefix::Giga),
             "T" => Ok(Prefix::Tera),
+            // IEC prefixes
+            "Ki" => Ok(Prefix::Kibi),
+            "Mi" => Ok(Prefix::Mebi),
+            "Gi" => Ok(Prefix::Gibi),
+            "Ti" => Ok(Prefix::Tebi),
             x => Err(ConfigurationError(
                 "Can not parse prefix".to_string(),
                 format!("unknown prefix: '{}'", x.to_string()),
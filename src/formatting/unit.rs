@@ -10,6 +10,10 @@ pub enum Unit {
     Percents,
     Degrees,
     Seconds,
+    Minutes,
+    Hours,
+    Days,
+    Weeks,
     Watts,
     Hertz,
     None,
@@ -26,6 +30,10 @@ impl fmt::Display for Unit {
                 Self::Percents => "%",
                 Self::Degrees => "°",
                 Self::Seconds => "s",
+                Self::Minutes => "m",
+                Self::Hours => "h",
+                Self::Days => "d",
+                Self::Weeks => "w",
                 Self::Watts => "W",
                 Self::Hertz => "Hz",
                 Self::None => "",
@@ -44,6 +52,10 @@ impl TryInto<Unit> for &str {
             "%" => Ok(Unit::Percents),
             "deg" => Ok(Unit::Degrees),
             "s" => Ok(Unit::Seconds),
+            "m" => Ok(Unit::Minutes),
+            "h" => Ok(Unit::Hours),
+            "d" => Ok(Unit::Days),
+            "w" => Ok(Unit::Weeks),
             "W" => Ok(Unit::Watts),
             "Hz" => Ok(Unit::Hertz),
             "" => Ok(Unit::None),
@@ -58,6 +70,9 @@ impl TryInto<Unit> for &str {
 impl Unit {
     //TODO support more complex conversions like Celsius -> Fahrenheit
     pub fn convert(&self, into: Self) -> Result<f64> {
+        if let (Some(from), Some(to)) = (self.seconds(), into.seconds()) {
+            return Ok(from / to);
+        }
         match self {
             Self::Bits if into == Self::Bytes => Ok(1. / 8.),
             Self::Bytes if into == Self::Bits => Ok(8.),
@@ -69,6 +84,18 @@ impl Unit {
         }
     }
 
+    /// The length of a time unit in seconds, `None` for other units
+    pub fn seconds(&self) -> Option<f64> {
+        match self {
+            Self::Seconds => Some(1.),
+            Self::Minutes => Some(60.),
+            Self::Hours => Some(3_600.),
+            Self::Days => Some(86_400.),
+            Self::Weeks => Some(604_800.),
+            _ => None,
+        }
+    }
+
     pub fn is_byte(&self) -> bool {
         matches!(self, Self::Bytes | Self::Bits)
     }
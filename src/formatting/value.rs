@@ -1,14 +1,21 @@
+use std::time::Duration;
+
 use crate::errors::*;
+use crate::util::escape_pango_text;
 
-use super::placeholder::Placeholder;
-use super::prefix::Prefix;
+use super::gradient::Gradient;
+use super::placeholder::{Alignment, Placeholder};
+use super::prefix::{BytePrefix, Prefix};
 use super::unit::Unit;
 
 #[derive(Debug, Clone)]
 pub struct Value {
     unit: Unit,
+    byte_prefix: BytePrefix,
     min_width: usize,
     icon: Option<String>,
+    markup: bool,
+    color: Option<String>,
     value: InternalValue,
 }
 
@@ -17,59 +24,78 @@ enum InternalValue {
     Text(String),
     Integer(i64),
     Float(f64),
+    Duration(u64),
+}
+
+/// Inserts `separator` between every group of three digits of the leading integer part of
+/// `number` (e.g. `"-1234567.8K"` becomes `"-1,234,567.8K"`).
+fn group_digits(number: &str, separator: char) -> String {
+    let start = if number.starts_with('-') { 1 } else { 0 };
+    let end = number[start..]
+        .find(|c: char| !c.is_ascii_digit())
+        .map_or(number.len(), |pos| pos + start);
+    let digits = &number[start..end];
+
+    // Digits are ASCII, so splitting the bytes can't break a character
+    let groups: Vec<&str> = digits
+        .as_bytes()
+        .rchunks(3)
+        .rev()
+        .map(|group| std::str::from_utf8(group).unwrap())
+        .collect();
+
+    format!(
+        "{}{}{}",
+        &number[..start],
+        groups.join(&separator.to_string()),
+        &number[end..]
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 fn format_number(
     raw_value: f64,
     min_width: usize,
     min_prefix: Prefix,
     unit: Unit,
+    byte_prefix: BytePrefix,
+    precision: Option<usize>,
+    grouping: Option<char>,
     pad_with: char,
+    align: Alignment,
 ) -> String {
+    // Grouped before padding, which would hide the digits from `group_digits`
+    let group = |number: String| match grouping {
+        Some(separator) => group_digits(&number, separator),
+        None => number,
+    };
+
     let is_byte = unit.is_byte();
 
-    let mut min_exp_level = match min_prefix {
-        Prefix::Tera => 4,
-        Prefix::Giga => 3,
-        Prefix::Mega => 2,
-        Prefix::Kilo => 1,
-        Prefix::One => 0,
-        Prefix::Milli => -1,
-        Prefix::Micro => -2,
-        Prefix::Nano => -3,
+    // An IEC minimal prefix always selects IEC prefixes
+    let byte_prefix = if min_prefix.is_iec() {
+        BytePrefix::Iec
+    } else {
+        byte_prefix
     };
 
+    let mut min_exp_level = min_prefix.exp_level();
+
     if is_byte {
         min_exp_level = min_exp_level.max(0);
     }
 
-    let (mut value, mut prefix) = if !is_byte {
+    let (mut value, mut prefix) = if !is_byte || byte_prefix == BytePrefix::Si {
         let exp_level = (raw_value.log10().div_euclid(3.) as i32).clamp(min_exp_level, 4);
         let value = raw_value / (10f64).powi(exp_level * 3);
-
-        let prefix = match exp_level {
-            4 => Prefix::Tera,
-            3 => Prefix::Giga,
-            2 => Prefix::Mega,
-            1 => Prefix::Kilo,
-            0 => Prefix::One,
-            -1 => Prefix::Milli,
-            -2 => Prefix::Micro,
-            _ => Prefix::Nano,
-        };
-        (value, prefix)
+        (value, Prefix::from_exp_level(exp_level, false))
     } else {
         let exp_level = (raw_value.log2().div_euclid(10.) as i32).clamp(min_exp_level, 4);
         let value = raw_value / (2f64).powi(exp_level * 10);
-
-        let prefix = match exp_level {
-            4 => Prefix::Tera,
-            3 => Prefix::Giga,
-            2 => Prefix::Mega,
-            1 => Prefix::Kilo,
-            _ => Prefix::One,
-        };
-        (value, prefix)
+        (
+            value,
+            Prefix::from_exp_level(exp_level, byte_prefix == BytePrefix::Iec),
+        )
     };
 
     if unit == Unit::Percents || unit == Unit::None {
@@ -77,16 +103,80 @@ fn format_number(
         prefix = Prefix::One;
     }
 
-    // The length of the integer part of a number
-    let digits = (value.log10().floor() + 1.0).max(1.0) as isize;
+    // An explicit precision overrides the one derived from the width
+    if let Some(precision) = precision {
+        return pad(
+            &group(format!("{:.*}{}", precision, value, prefix)),
+            min_width,
+            pad_with,
+            align,
+        );
+    }
+
+    // The length of the integer part of a number, with its separators
+    let mut digits = (value.log10().floor() + 1.0).max(1.0) as isize;
+    if grouping.is_some() {
+        digits += (digits - 1) / 3;
+    }
     // How many characters is left for "." and the fractional part?
     match min_width as isize - digits {
         // No characters left
-        x if x <= 0 => format!("{:.0}{}", value, prefix),
-        // Only one character -> pad text to the right
-        x if x == 1 => format!("{}{:.0}{}", pad_with, value, prefix),
+        x if x <= 0 => group(format!("{:.0}{}", value, prefix)),
+        // Only one character -> pad text
+        x if x == 1 => {
+            let number = group(format!("{:.0}{}", value, prefix));
+            match align {
+                Alignment::Right => format!("{}{}", pad_with, number),
+                Alignment::Left => format!("{}{}", number, pad_with),
+            }
+        }
         // There is space for fractional part
-        rest => format!("{:.*}{}", (rest as usize) - 1, value, prefix),
+        rest => group(format!("{:.*}{}", (rest as usize) - 1, value, prefix)),
+    }
+}
+
+/// Pads `text` with `pad_with` up to `min_width` characters
+fn pad(text: &str, min_width: usize, pad_with: char, align: Alignment) -> String {
+    let padding: String = (text.chars().count()..min_width)
+        .map(|_| pad_with)
+        .collect();
+    match align {
+        Alignment::Left => format!("{}{}", text, padding),
+        Alignment::Right => format!("{}{}", padding, text),
+    }
+}
+
+/// Formats `secs` using the `count` largest non-zero units, none of which is bigger than
+/// `largest` (e.g. `2d 3h` or, with `largest` set to hours, `51h 12m`)
+fn format_duration(secs: u64, largest: Unit, count: usize) -> String {
+    const UNITS: &[Unit] = &[
+        Unit::Weeks,
+        Unit::Days,
+        Unit::Hours,
+        Unit::Minutes,
+        Unit::Seconds,
+    ];
+    let largest = largest.seconds().unwrap_or(1.) as u64;
+
+    let mut rest = secs;
+    let mut parts = Vec::new();
+    for unit in UNITS {
+        let length = unit.seconds().unwrap() as u64;
+        if length > largest {
+            continue;
+        }
+        let amount = rest / length;
+        rest %= length;
+        if amount > 0 {
+            parts.push(format!("{}{}", amount, unit));
+        }
+    }
+    parts.truncate(count.max(1));
+
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
     }
 }
 
@@ -120,6 +210,9 @@ impl Value {
             icon: None,
             min_width: 0,
             unit: Unit::None,
+            byte_prefix: BytePrefix::default(),
+            markup: false,
+            color: None,
             value: InternalValue::Text(text),
         }
     }
@@ -128,14 +221,32 @@ impl Value {
             icon: None,
             min_width: 2,
             unit: Unit::None,
+            byte_prefix: BytePrefix::default(),
+            markup: false,
+            color: None,
             value: InternalValue::Integer(value),
         }
     }
+    pub fn from_duration(value: Duration) -> Self {
+        Self {
+            icon: None,
+            // The number of units to show
+            min_width: 2,
+            unit: Unit::Seconds,
+            byte_prefix: BytePrefix::default(),
+            markup: false,
+            color: None,
+            value: InternalValue::Duration(value.as_secs()),
+        }
+    }
     pub fn from_float(value: f64) -> Self {
         Self {
             icon: None,
             min_width: 3,
             unit: Unit::None,
+            byte_prefix: BytePrefix::default(),
+            markup: false,
+            color: None,
             value: InternalValue::Float(value),
         }
     }
@@ -145,6 +256,26 @@ impl Value {
         self.icon = Some(icon);
         self
     }
+    pub fn byte_prefix(mut self, byte_prefix: BytePrefix) -> Self {
+        self.byte_prefix = byte_prefix;
+        self
+    }
+    /// Colors a numeric value according to `gradient`. Has no effect on text values.
+    pub fn gradient(mut self, gradient: &Gradient) -> Self {
+        self.color = match self.value {
+            InternalValue::Text(_) => None,
+            InternalValue::Integer(value) => Some(gradient.color_at(value as f64)),
+            InternalValue::Float(value) => Some(gradient.color_at(value)),
+            InternalValue::Duration(value) => Some(gradient.color_at(value as f64)),
+        };
+        self
+    }
+    /// Text values are escaped for Pango by default. Use this for text that intentionally
+    /// contains markup (or that is escaped later on) to insert it verbatim.
+    pub fn markup(mut self) -> Self {
+        self.markup = true;
+        self
+    }
     //pub fn min_width(mut self, min_width: usize) -> Self {
     //self.min_width = min_width;
     //self
@@ -180,8 +311,47 @@ impl Value {
         self
     }
 
+    /// Whether the value is zero (for numbers) or an empty string (for text)
+    pub fn is_empty(&self) -> bool {
+        match self.value {
+            InternalValue::Text(ref text) => text.is_empty(),
+            InternalValue::Integer(value) => value == 0,
+            InternalValue::Float(value) => value == 0.,
+            InternalValue::Duration(value) => value == 0,
+        }
+    }
+
     pub fn format(&self, var: &Placeholder) -> Result<String> {
+        let text = self.format_uncolored(var)?;
+        Ok(match self.color {
+            Some(ref color) => format!("<span foreground=\"{}\">{}</span>", color, text),
+            None => text,
+        })
+    }
+
+    fn format_uncolored(&self, var: &Placeholder) -> Result<String> {
         let min_width = var.min_width.unwrap_or(self.min_width);
+
+        // Durations carry their own units and use `min_width` as the number of units to show.
+        // The placeholder's unit selects the largest unit.
+        if let InternalValue::Duration(secs) = self.value {
+            let largest = match var.unit {
+                None => Unit::Weeks,
+                Some(unit) if unit.seconds().is_some() => unit,
+                Some(unit) => {
+                    return Err(ConfigurationError(
+                        "Can not convert unit".to_string(),
+                        format!("durations can not be shown in '{:?}'", unit),
+                    ))
+                }
+            };
+            let icon_str = self.icon.as_deref().unwrap_or("");
+            return Ok(format!(
+                "{}{}",
+                icon_str,
+                format_duration(secs, largest, min_width)
+            ));
+        }
         let pad_with = var.pad_with.unwrap_or(' ');
         let unit = var.unit.unwrap_or(self.unit);
 
@@ -199,28 +369,37 @@ impl Value {
 
         let value = match self.value {
             InternalValue::Text(ref text) => {
-                let mut text = text.clone();
-                for _ in (text.chars().count())..min_width {
-                    text.push(pad_with);
-                }
+                let mut text = pad(
+                    text,
+                    min_width,
+                    pad_with,
+                    var.align.unwrap_or(Alignment::Left),
+                );
                 if let Some(max_width) = var.max_width {
                     for _ in 0..(text.chars().count() as isize - max_width as isize) {
                         text.pop();
                     }
                 }
-                text
+                // Escape after truncating so that entities are never cut in half
+                if self.markup {
+                    text
+                } else {
+                    escape_pango_text(text)
+                }
             }
             InternalValue::Integer(value) => {
                 let value = (value as f64 * self.unit.convert(unit)?) as i64;
 
-                let text = value.to_string();
-                let mut retval = String::new();
-                let text_len = text.len();
-                for _ in text_len..min_width {
-                    retval.push(pad_with);
+                let mut text = value.to_string();
+                if let Some(separator) = var.grouping {
+                    text = group_digits(&text, separator);
                 }
-                retval.push_str(&text);
-                retval
+                pad(
+                    &text,
+                    min_width,
+                    pad_with,
+                    var.align.unwrap_or(Alignment::Right),
+                )
             }
             InternalValue::Float(value) => {
                 let value = value * self.unit.convert(unit)?;
@@ -230,9 +409,15 @@ impl Value {
                     min_width,
                     var.min_prefix.unwrap_or(Prefix::Nano),
                     unit,
+                    self.byte_prefix,
+                    var.precision,
+                    var.grouping,
                     pad_with,
+                    var.align.unwrap_or(Alignment::Right),
                 )
             }
+            // Handled above
+            InternalValue::Duration(_) => unreachable!(),
         };
 
         let icon_str = self.icon.as_deref().unwrap_or("");
@@ -243,3 +428,31 @@ impl Value {
         Ok(format!("{}{}{}", icon_str, value, unit_str))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grouped(value: f64, min_width: usize, precision: Option<usize>) -> String {
+        format_number(
+            value,
+            min_width,
+            Prefix::Nano,
+            Unit::None,
+            BytePrefix::Si,
+            precision,
+            Some(','),
+            ' ',
+            Alignment::Right,
+        )
+    }
+
+    #[test]
+    fn format_number_grouped_and_padded() {
+        assert_eq!(grouped(1234567.891, 0, None), "1,234,568");
+        // The separators count towards the width, which leaves room for the padding only
+        assert_eq!(grouped(1234567.891, 10, None), " 1,234,568");
+        assert_eq!(grouped(1234567.891, 12, None), "1,234,567.89");
+        assert_eq!(grouped(1234.5, 9, Some(1)), "  1,234.5");
+    }
+}
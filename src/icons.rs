@@ -6,13 +6,26 @@ use serde_derive::Deserialize;
 
 use crate::util;
 
+/// An entry of an icon set: a single icon, or a ramp of icons going from the lowest to the
+/// highest level, like `bat = ["\uf244", "\uf243", "\uf242", "\uf241", "\uf240"]`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Icon {
+    Single(String),
+    Ramp(Vec<String>),
+}
+
 #[derive(Debug, Clone)]
-pub struct Icons(pub HashMap<String, String>);
+pub struct Icons {
+    pub icons: HashMap<String, String>,
+    pub ramps: HashMap<String, Vec<String>>,
+}
 
 impl Default for Icons {
     fn default() -> Self {
         // "none" icon set
-        Self(map_to_owned! {
+        Self {
+            icons: map_to_owned! {
             "backlight_empty" => "BRIGHT",
             "backlight_full" => "BRIGHT",
             "backlight_1" =>  "BRIGHT",
@@ -45,6 +58,7 @@ impl Default for Icons {
             "cpu" => "CPU",
             "disk_drive" => "DISK",
             "docker" => "DOCKER",
+            "error" => "ERR",
             "github" => "GITHUB",
             "gpu" => "GPU",
             "headphones" => "HEAD",
@@ -59,8 +73,10 @@ impl Default for Icons {
             "music_pause" => "||",
             "music_play" => ">",
             "music_prev" => "<",
+            "net_bridge" => "BRIDGE",
             "net_down" => "DOWN",
             "net_loopback" => "LO",
+            "net_modem" => "MODEM",
             "net_up" => "UP ",
             "net_vpn" => "VPN",
             "net_wired" => "ETH",
@@ -76,6 +92,7 @@ impl Default for Icons {
             "time" => "TIME",
             "toggle_off" => "OFF",
             "toggle_on" => "ON",
+            "unknown" => "??",
             "update" => "UPD",
             "uptime" => "UP",
             "volume_empty" => "VOL",
@@ -93,7 +110,9 @@ impl Default for Icons {
             "weather_sun" => "SUNNY",
             "weather_thunder" => "STORM",
             "xrandr" => "SCREEN"
-        })
+            },
+            ramps: HashMap::new(),
+        }
     }
 }
 
@@ -103,9 +122,42 @@ impl Icons {
             Some(Icons::default())
         } else {
             let file = util::find_file(file, Some("icons"), Some("toml"))?;
-            Some(Icons(util::deserialize_file(&file).ok()?))
+            let entries: HashMap<String, Icon> = util::deserialize_file(&file).ok()?;
+            let mut icons = Icons {
+                icons: HashMap::new(),
+                ramps: HashMap::new(),
+            };
+            icons.extend(entries);
+            Some(icons)
         }
     }
+
+    /// Adds the `entries`, replacing the icons or ramps of the same names
+    pub fn extend(&mut self, entries: HashMap<String, Icon>) {
+        for (name, icon) in entries {
+            match icon {
+                Icon::Single(icon) => {
+                    self.icons.insert(name, icon);
+                }
+                Icon::Ramp(ramp) => {
+                    self.ramps.insert(name, ramp);
+                }
+            }
+        }
+    }
+
+    /// The icon of the ramp `name` for `percents` (0–100). Every icon of the ramp covers an
+    /// equal part of the range, the last one also includes 100%.
+    pub fn ramp_icon(&self, name: &str, percents: f64) -> Option<&str> {
+        let ramp = self.ramps.get(name).filter(|ramp| !ramp.is_empty())?;
+        let percents = if percents.is_nan() {
+            0.
+        } else {
+            percents.clamp(0., 100.)
+        };
+        let index = ((percents / 100. * ramp.len() as f64) as usize).min(ramp.len() - 1);
+        Some(&ramp[index])
+    }
 }
 
 impl<'de> Deserialize<'de> for Icons {
@@ -118,6 +170,7 @@ impl<'de> Deserialize<'de> for Icons {
         enum Field {
             Name,
             File,
+            Fallback,
             Overrides,
         }
 
@@ -154,7 +207,8 @@ impl<'de> Deserialize<'de> for Icons {
                 V: MapAccess<'de>,
             {
                 let mut icons: Option<&str> = None;
-                let mut overrides: Option<HashMap<String, String>> = None;
+                let mut overrides: Option<HashMap<String, Icon>> = None;
+                let mut fallback: Option<String> = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         // TODO merge name and file into one option (let's say "icons")
@@ -170,6 +224,12 @@ impl<'de> Deserialize<'de> for Icons {
                             }
                             icons = Some(map.next_value()?);
                         }
+                        Field::Fallback => {
+                            if fallback.is_some() {
+                                return Err(de::Error::duplicate_field("fallback"));
+                            }
+                            fallback = Some(map.next_value()?);
+                        }
                         Field::Overrides => {
                             if overrides.is_some() {
                                 return Err(de::Error::duplicate_field("overrides"));
@@ -187,9 +247,10 @@ impl<'de> Deserialize<'de> for Icons {
                 };
 
                 if let Some(overrides) = overrides {
-                    for icon in overrides {
-                        icons.0.insert(icon.0, icon.1);
-                    }
+                    icons.extend(overrides);
+                }
+                if let Some(fallback) = fallback {
+                    icons.icons.insert("fallback".to_string(), fallback);
                 }
                 Ok(icons)
             }
@@ -198,3 +259,35 @@ impl<'de> Deserialize<'de> for Icons {
         deserializer.deserialize_any(IconsVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nerd_fonts_has_all_icons() {
+        let nerd_fonts: HashMap<String, Icon> =
+            toml::from_str(include_str!("../files/icons/nerd-fonts.toml")).unwrap();
+        // The default icon set has an entry for every icon used by the blocks
+        for icon in Icons::default().icons.keys() {
+            assert!(nerd_fonts.contains_key(icon), "missing icon '{}'", icon);
+        }
+    }
+
+    #[test]
+    fn ramp_icon() {
+        let mut icons = Icons::default();
+        let ramp = ["0", "1", "2", "3", "4"].iter().map(|i| i.to_string());
+        icons.extend(map_to_owned! { "bat" => Icon::Ramp(ramp.collect()) });
+
+        assert_eq!(icons.ramp_icon("bat", 0.), Some("0"));
+        assert_eq!(icons.ramp_icon("bat", 19.9), Some("0"));
+        assert_eq!(icons.ramp_icon("bat", 20.), Some("1"));
+        assert_eq!(icons.ramp_icon("bat", 79.9), Some("3"));
+        assert_eq!(icons.ramp_icon("bat", 80.), Some("4"));
+        assert_eq!(icons.ramp_icon("bat", 100.), Some("4"));
+        assert_eq!(icons.ramp_icon("bat", -5.), Some("0"));
+        assert_eq!(icons.ramp_icon("bat", 150.), Some("4"));
+        assert_eq!(icons.ramp_icon("bat_charging", 50.), None);
+    }
+}
@@ -0,0 +1,42 @@
+//! A logger for the `log` macros, which writes the messages to stderr prefixed with the time
+//! and their level
+
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{} {:<5} {}",
+                Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Installs the logger. `verbosity` is the number of `-v` flags: only errors are logged without
+/// any, then warnings, info and debug messages are added one by one.
+pub fn init(verbosity: u64) {
+    let level = match verbosity {
+        0 => LevelFilter::Error,
+        1 => LevelFilter::Warn,
+        2 => LevelFilter::Info,
+        _ => LevelFilter::Debug,
+    };
+    // This only fails if a logger is already installed
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level);
+}
@@ -8,6 +8,7 @@ mod errors;
 mod formatting;
 mod http;
 mod icons;
+mod logging;
 mod protocol;
 mod scheduler;
 mod signals;
@@ -23,17 +24,19 @@ use std::ops::DerefMut;
 #[cfg(feature = "pulseaudio")]
 use libpulse_binding as pulse;
 
+use std::path::Path;
 use std::time::Duration;
 
 use clap::{crate_authors, crate_description, App, Arg, ArgMatches};
 use crossbeam_channel::{select, Receiver, Sender};
+use log::{debug, error, info};
 
 use crate::blocks::create_block;
 use crate::blocks::Block;
-use crate::config::Config;
 use crate::config::SharedConfig;
+use crate::config::{Config, ConfigErrors};
 use crate::errors::*;
-use crate::protocol::i3bar_event::{process_events, I3BarEvent};
+use crate::protocol::i3bar_event::{process_events, I3BarEvent, MouseButton};
 use crate::scheduler::{Task, UpdateScheduler};
 use crate::signals::process_signals;
 use crate::util::deserialize_file;
@@ -70,6 +73,14 @@ fn main() {
                 .long("exit-on-error")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("verbose")
+                .help("Log more details to stderr, repeat for even more (-vv, -vvv)")
+                .short("v")
+                .long("verbose")
+                .multiple(true)
+                .takes_value(false),
+        )
         .arg(
             Arg::with_name("never-pause")
                 .help("Ignore any attempts by i3 to pause the bar when hidden/fullscreen")
@@ -111,6 +122,7 @@ fn main() {
 
     let matches = builder.get_matches();
     let exit_on_error = matches.is_present("exit-on-error");
+    logging::init(matches.occurrences_of("verbose"));
 
     // Run and match for potential error
     if let Err(error) = run(&matches) {
@@ -149,11 +161,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         Some(config_path) => std::path::PathBuf::from(config_path),
         None => util::xdg_config_home().join("i3status-rust/config.toml"),
     };
-    let config: Config = deserialize_file(&config_path)?;
-
-    // Update request channel
-    let (tx_update_requests, rx_update_requests): (Sender<Task>, Receiver<Task>) =
-        crossbeam_channel::unbounded();
+    let config = load_config(&config_path)?;
 
     // In dev build, we might diverge into profiling blocks here
     #[cfg(feature = "profiling")]
@@ -163,26 +171,12 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 name,
                 matches.value_of("profile-runs").unwrap(),
                 &config,
-                tx_update_requests,
+                crossbeam_channel::unbounded().0,
             );
         }
     }
 
-    let shared_config = SharedConfig::new(&config);
-
-    // Initialize the blocks
-    let mut blocks: Vec<Box<dyn Block>> = Vec::new();
-    for &(ref block_name, ref block_config) in &config.blocks {
-        blocks.push(create_block(
-            blocks.len(),
-            block_name,
-            block_config.clone(),
-            shared_config.clone(),
-            tx_update_requests.clone(),
-        )?);
-    }
-
-    let mut scheduler = UpdateScheduler::new(&blocks);
+    let mut bar = Bar::new(config)?;
 
     // We wait for click events in a separate thread, to avoid blocking to wait for stdin
     let (tx_clicks, rx_clicks): (Sender<I3BarEvent>, Receiver<I3BarEvent>) =
@@ -197,6 +191,17 @@ fn run(matches: &ArgMatches) -> Result<()> {
     // Fires immediately for first updates
     let mut ttnu = crossbeam_channel::after(Duration::from_millis(0));
 
+    // Set while the bar is hidden, see `protocol::init`
+    let mut paused = false;
+
+    // The bar is printed at most once per `flush_interval`, with all changes made meanwhile
+    let mut flush = crossbeam_channel::never();
+    let mut flush_pending = false;
+    let mut dirty = false;
+
+    // Set when SIGHUP is received
+    let mut reload = false;
+
     let one_shot = matches.is_present("one-shot");
     loop {
         // We use the message passing concept of channel selection
@@ -205,32 +210,38 @@ fn run(matches: &ArgMatches) -> Result<()> {
             // Receive click events
             recv(rx_clicks) -> res => if let Ok(event) = res {
                 if let Some(id) = event.id {
-                        blocks.get_mut(id)
-                    .internal_error("click handler", "could not get required block")?
-                            .click(&event)?;
-                    protocol::print_blocks(&blocks, &shared_config)?;
+                    let block = bar.blocks.get_mut(id)
+                        .internal_error("click handler", "could not get required block")?;
+                    block.click(&event)?;
+                    if (event.button == MouseButton::Middle && block.refresh_on_middle_click())
+                        || block.update_after_click()
+                    {
+                        bar.scheduler.update_now(id);
+                    }
+                    dirty = true;
                 }
             },
             // Receive async update requests
-            recv(rx_update_requests) -> request => if let Ok(req) = request {
+            recv(bar.rx_update_requests) -> request => if let Ok(req) = request {
                 // Process immediately and forget
-                blocks.get_mut(req.id)
+                bar.blocks.get_mut(req.id)
                     .internal_error("scheduler", "could not get required block")?
                     .update()?;
-                protocol::print_blocks(&blocks, &shared_config)?;
+                dirty = true;
             },
             // Receive update timer events
             recv(ttnu) -> _ => {
-                scheduler.do_scheduled_updates(&mut blocks)?;
+                bar.scheduler.do_scheduled_updates(&mut bar.blocks)?;
                 // redraw the blocks, state changed
-                protocol::print_blocks(&blocks, &shared_config)?;
+                dirty = true;
             },
             // Receive signal events
             recv(rx_signals) -> res => if let Ok(sig) = res {
+                debug!("received signal {}", sig);
                 match sig {
                     signal_hook::consts::SIGUSR1 => {
                         //USR1 signal that updates every block in the bar
-                        for block in blocks.iter_mut() {
+                        for block in bar.blocks.iter_mut() {
                             block.update()?;
                         }
                     },
@@ -238,28 +249,146 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         //USR2 signal that should reload the config
                         restart();
                     },
+                    signal_hook::consts::SIGHUP => {
+                        //HUP signal that reloads the config in place, see below
+                        reload = true;
+                    },
+                    signal_hook::consts::SIGTSTP => {
+                        //The bar got hidden, stop updating the blocks
+                        paused = true;
+                    },
+                    signal_hook::consts::SIGCONT => {
+                        //The bar is shown again, refresh everything once
+                        paused = false;
+                        bar.scheduler.update_all_now();
+                    },
                     _ => {
                         //Real time signal that updates only the blocks listening
                         //for that signal
-                        for block in blocks.iter_mut() {
+                        for block in bar.blocks.iter_mut() {
                             block.signal(sig)?;
                         }
                     },
                 };
-                protocol::print_blocks(&blocks, &shared_config)?;
+                dirty = true;
+            },
+            // Print the blocks changed since the last flush
+            recv(flush) -> _ => {
+                protocol::print_blocks(&bar.blocks, &bar.shared_config)?;
+                dirty = false;
+                flush = crossbeam_channel::never();
+                flush_pending = false;
+            }
+        }
+
+        // Replace the blocks by those of the edited config, keeping the running ones if the new
+        // config is invalid
+        if reload {
+            reload = false;
+            match bar.reload(&config_path) {
+                Ok(()) => info!("reloaded the config from '{}'", config_path.display()),
+                Err(e) => error!(
+                    "failed to reload the config, keeping the current one: {:?}",
+                    e
+                ),
             }
         }
 
         // Set the time-to-next-update timer
-        if let Some(time) = scheduler.time_to_next_update() {
+        if paused {
+            ttnu = crossbeam_channel::never();
+        } else if let Some(time) = bar.scheduler.time_to_next_update() {
             ttnu = crossbeam_channel::after(time)
         }
+
+        // Set the flush timer, unless one is already running
+        if paused {
+            flush = crossbeam_channel::never();
+            flush_pending = false;
+        } else if dirty && one_shot {
+            protocol::print_blocks(&bar.blocks, &bar.shared_config)?;
+        } else if dirty && !flush_pending {
+            flush = crossbeam_channel::after(bar.config.flush_interval);
+            flush_pending = true;
+        }
+
         if one_shot {
             break Ok(());
         }
     }
 }
 
+/// Loads the config at `config_path`, including the blocks of the files it includes
+fn load_config(config_path: &Path) -> Result<Config> {
+    let mut config: Config = deserialize_file(config_path)?;
+    config.expand_includes(config_path)?;
+    de::set_strict_env_vars(config.strict_env_vars);
+    Ok(config)
+}
+
+/// The blocks created from a config, with the state needed to run them
+struct Bar {
+    config: Config,
+    shared_config: SharedConfig,
+    blocks: Vec<Box<dyn Block>>,
+    scheduler: UpdateScheduler,
+    /// The update requests of the blocks. Every `Bar` has its own channel, so that the requests
+    /// of the blocks replaced by a reload can't reach the new blocks with the same ids (and the
+    /// threads of the replaced blocks end when they fail to send their next request).
+    rx_update_requests: Receiver<Task>,
+}
+
+impl Bar {
+    /// Creates all blocks of `config`, reporting the errors of all of them at once
+    fn new(config: Config) -> Result<Bar> {
+        let (tx_update_requests, rx_update_requests): (Sender<Task>, Receiver<Task>) =
+            crossbeam_channel::unbounded();
+        let shared_config = SharedConfig::new(&config);
+
+        let mut errors = ConfigErrors::default();
+        config.validate(&mut errors);
+        let mut blocks: Vec<Box<dyn Block>> = Vec::new();
+        for (id, &(ref block_name, ref block_config)) in config.blocks.iter().enumerate() {
+            let block = create_block(
+                id,
+                block_name,
+                block_config.clone(),
+                shared_config.clone(),
+                tx_update_requests.clone(),
+            )
+            .in_block(block_name, id);
+            if let Some(block) = errors.check(block) {
+                blocks.push(block);
+            }
+        }
+        errors.into_result()?;
+
+        let scheduler = UpdateScheduler::new(&blocks, config.interval_jitter);
+        Ok(Bar {
+            config,
+            shared_config,
+            blocks,
+            scheduler,
+            rx_update_requests,
+        })
+    }
+
+    /// Replaces the blocks by those of the config at `config_path`. If it is invalid, the
+    /// current blocks keep running and the error is returned.
+    fn reload(&mut self, config_path: &Path) -> Result<()> {
+        match load_config(config_path).and_then(Bar::new) {
+            Ok(bar) => {
+                *self = bar;
+                Ok(())
+            }
+            Err(e) => {
+                de::set_strict_env_vars(self.config.strict_env_vars);
+                Err(e)
+            }
+        }
+    }
+}
+
 /// Restart `i3status-rs` in-place
 fn restart() -> ! {
     use std::env;
@@ -281,6 +410,7 @@ fn restart() -> ! {
     }
 
     // Restart
+    http::remove_ca_bundles();
     nix::unistd::execvp(&exe, &arg).unwrap();
     unreachable!();
 }
@@ -326,3 +456,30 @@ fn profile_config(name: &str, runs: &str, config: &Config, update: Sender<Task>)
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_reload_keeps_the_bar() {
+        let path = util::test_path("reload.toml");
+        std::fs::write(&path, "[[block]]\nblock = \"load\"\n").unwrap();
+        let mut bar = Bar::new(load_config(&path).unwrap()).unwrap();
+
+        std::fs::write(&path, "[[block]]\nblock = \"no_such_block\"\n").unwrap();
+        assert!(bar.reload(&path).is_err());
+        assert_eq!(bar.blocks.len(), 1);
+        assert_eq!(bar.config.blocks[0].0, "load");
+
+        std::fs::write(
+            &path,
+            "[[block]]\nblock = \"load\"\n[[block]]\nblock = \"memory\"\n",
+        )
+        .unwrap();
+        bar.reload(&path).unwrap();
+        assert_eq!(bar.blocks.len(), 2);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
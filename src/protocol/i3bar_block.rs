@@ -1,5 +1,9 @@
-/// Represent block as described in https://i3wm.org/docs/i3bar-protocol.html
+use crate::util::{color_from_rgba, mix_colors};
+
+/// The maximum number of segments a block with a background gradient is split into
+const GRADIENT_SEGMENTS: usize = 8;
 
+/// Represent block as described in https://i3wm.org/docs/i3bar-protocol.html
 #[derive(Debug, Clone)]
 pub struct I3BarBlock {
     pub full_text: String,
@@ -19,6 +23,8 @@ pub struct I3BarBlock {
     pub separator: Option<bool>,
     pub separator_block_width: Option<usize>,
     pub markup: Option<String>,
+    /// Second color of the background gradient. Not part of the protocol, see `split_gradient`
+    pub background_end: Option<String>,
 }
 
 macro_rules! json_add_str {
@@ -81,6 +87,62 @@ impl I3BarBlock {
         retval.push('}');
         retval
     }
+
+    /// Splits the block into segments whose backgrounds go from `background` to
+    /// `background_end`. Blocks that cannot be split (no second color, invalid colors, markup
+    /// tags in the text, or a `short_text` or `min_width` which only apply to whole blocks) are
+    /// returned as they are, i.e. with the first color only.
+    pub fn split_gradient(self) -> Vec<I3BarBlock> {
+        let (start, end) = match (&self.background, &self.background_end) {
+            (Some(start), Some(end))
+                if color_from_rgba(start).is_ok() && color_from_rgba(end).is_ok() =>
+            {
+                (start.clone(), end.clone())
+            }
+            _ => return vec![self],
+        };
+        if self.short_text.is_some() || self.min_width.is_some() || self.full_text.contains('<') {
+            return vec![self];
+        }
+
+        // Split between characters, keeping escaped entities such as `&amp;` in one piece
+        let text = &self.full_text;
+        let mut bounds = vec![0];
+        let mut pos = 0;
+        while pos < text.len() {
+            let rest = &text[pos..];
+            pos += rest
+                .strip_prefix('&')
+                .and_then(|_| rest.find(';'))
+                .map(|end| end + 1)
+                .unwrap_or_else(|| rest.chars().next().unwrap().len_utf8());
+            bounds.push(pos);
+        }
+        let units = bounds.len() - 1;
+        let count = units.min(GRADIENT_SEGMENTS);
+        if count < 2 {
+            return vec![self];
+        }
+
+        (0..count)
+            .map(|n| {
+                let last = n == count - 1;
+                let (from, to) = (bounds[n * units / count], bounds[(n + 1) * units / count]);
+                I3BarBlock {
+                    full_text: text[from..to].to_string(),
+                    background: mix_colors(&start, &end, n as f64 / (count - 1) as f64).ok(),
+                    background_end: None,
+                    separator: if last { self.separator } else { Some(false) },
+                    separator_block_width: if last {
+                        self.separator_block_width
+                    } else {
+                        Some(0)
+                    },
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
 }
 
 impl Default for I3BarBlock {
@@ -107,6 +169,7 @@ impl Default for I3BarBlock {
             separator: Some(false),
             separator_block_width: Some(0),
             markup: Some("pango".to_string()),
+            background_end: None,
         }
     }
 }
@@ -123,3 +186,49 @@ pub enum I3BarBlockMinWidth {
     Pixels(usize),
     Text(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient_block(text: &str) -> I3BarBlock {
+        I3BarBlock {
+            full_text: text.to_string(),
+            background: Some("#000000".to_string()),
+            background_end: Some("#FF0000".to_string()),
+            separator: None,
+            separator_block_width: None,
+            ..I3BarBlock::default()
+        }
+    }
+
+    #[test]
+    fn split_gradient() {
+        let segments = gradient_block("a&amp;b").split_gradient();
+        let texts: Vec<_> = segments.iter().map(|s| s.full_text.as_str()).collect();
+        assert_eq!(texts, ["a", "&amp;", "b"]);
+        let backgrounds: Vec<_> = segments
+            .iter()
+            .map(|s| s.background.as_deref().unwrap())
+            .collect();
+        assert_eq!(backgrounds, ["#000000FF", "#800000FF", "#FF0000FF"]);
+        // Only the last segment keeps the separator of the block
+        assert_eq!(segments[0].separator, Some(false));
+        assert_eq!(segments[2].separator, None);
+
+        let long = gradient_block("0123456789abcdef").split_gradient();
+        assert_eq!(long.len(), GRADIENT_SEGMENTS);
+        assert!(long.iter().all(|s| s.full_text.len() == 2));
+    }
+
+    #[test]
+    fn split_gradient_fallback() {
+        assert_eq!(gradient_block("<b>bold</b>").split_gradient().len(), 1);
+        assert_eq!(gradient_block("a").split_gradient().len(), 1);
+        let mut invalid = gradient_block("text");
+        invalid.background_end = Some("red".to_string());
+        let segments = invalid.split_gradient();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].background.as_deref(), Some("#000000"));
+    }
+}
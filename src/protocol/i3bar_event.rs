@@ -20,15 +20,37 @@ pub enum MouseButton {
     Unknown,
 }
 
+/// A key held while clicking, as named by i3bar. `Mod4` is usually the Super key and `Mod1` Alt.
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Modifier {
+    Shift,
+    Control,
+    Lock,
+    Mod1,
+    Mod2,
+    Mod3,
+    Mod4,
+    Mod5,
+    #[serde(other)]
+    Unknown,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 struct I3BarEventInternal {
     pub name: Option<String>,
     pub instance: Option<String>,
     pub x: u64,
     pub y: u64,
+    #[serde(default)]
+    pub relative_x: Option<u64>,
+    #[serde(default)]
+    pub width: Option<u64>,
 
     #[serde(deserialize_with = "deserialize_mousebutton")]
     pub button: MouseButton,
+
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +58,12 @@ pub struct I3BarEvent {
     pub id: Option<usize>,
     pub instance: Option<usize>,
     pub button: MouseButton,
+    /// The keys held while clicking
+    pub modifiers: Vec<Modifier>,
+    /// Where the click happened, in pixels from the left edge of the block
+    pub relative_x: Option<u64>,
+    /// The width of the block in pixels
+    pub width: Option<u64>,
 }
 
 impl I3BarEvent {
@@ -45,6 +73,19 @@ impl I3BarEvent {
             _ => false,
         }
     }
+
+    pub fn has_modifier(&self, modifier: Modifier) -> bool {
+        self.modifiers.contains(&modifier)
+    }
+
+    /// Where the click happened, as a fraction of the width of the block (0 at the left edge,
+    /// 1 at the right one). Only recent versions of i3bar and swaybar send what is needed.
+    pub fn relative_position(&self) -> Option<f64> {
+        match (self.relative_x, self.width) {
+            (Some(x), Some(width)) if width > 0 => Some((x as f64 / width as f64).min(1.)),
+            _ => None,
+        }
+    }
 }
 
 pub fn process_events(sender: Sender<I3BarEvent>) {
@@ -65,6 +106,9 @@ pub fn process_events(sender: Sender<I3BarEvent>) {
                         id: e.name.map(|x| x.parse::<usize>().unwrap()),
                         instance: e.instance.map(|x| x.parse::<usize>().unwrap()),
                         button: e.button,
+                        modifiers: e.modifiers,
+                        relative_x: e.relative_x,
+                        width: e.width,
                     })
                     .unwrap();
             }
@@ -106,3 +150,48 @@ where
 
     deserializer.deserialize_any(MouseButtonVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_modifiers() {
+        let e: I3BarEventInternal = serde_json::from_str(
+            r#"{"name":"0","button":1,"x":0,"y":0,"modifiers":["Shift","Mod4","Hyper"]}"#,
+        )
+        .unwrap();
+        assert_eq!(e.button, MouseButton::Left);
+        assert_eq!(
+            e.modifiers,
+            [Modifier::Shift, Modifier::Mod4, Modifier::Unknown]
+        );
+
+        let e: I3BarEventInternal =
+            serde_json::from_str(r#"{"name":"0","button":4,"x":0,"y":0}"#).unwrap();
+        assert_eq!(e.button, MouseButton::WheelUp);
+        assert!(e.modifiers.is_empty());
+    }
+
+    #[test]
+    fn relative_position() {
+        let event = |relative_x, width| I3BarEvent {
+            id: Some(0),
+            instance: None,
+            button: MouseButton::Left,
+            modifiers: Vec::new(),
+            relative_x,
+            width,
+        };
+        assert_eq!(event(Some(25), Some(100)).relative_position(), Some(0.25));
+        assert_eq!(event(Some(120), Some(100)).relative_position(), Some(1.));
+        assert_eq!(event(Some(0), Some(0)).relative_position(), None);
+        assert_eq!(event(None, None).relative_position(), None);
+
+        let e: I3BarEventInternal = serde_json::from_str(
+            r#"{"name":"0","button":1,"x":300,"y":0,"relative_x":30,"width":120}"#,
+        )
+        .unwrap();
+        assert_eq!((e.relative_x, e.width), (Some(30), Some(120)));
+    }
+}
@@ -12,7 +12,12 @@ pub fn init(never_pause: bool) {
     if never_pause {
         println!("{{\"version\": 1, \"click_events\": true, \"stop_signal\": 0}}\n[");
     } else {
-        println!("{{\"version\": 1, \"click_events\": true}}\n[");
+        // Unlike the default SIGSTOP, SIGTSTP can be handled: the updates are paused while the
+        // bar is hidden, and resumed on SIGCONT
+        println!(
+            "{{\"version\": 1, \"click_events\": true, \"stop_signal\": {}}}\n[",
+            signal_hook::consts::SIGTSTP
+        );
     }
 }
 
@@ -41,8 +46,8 @@ pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<
 
         let mut rendered_widgets: Vec<I3BarBlock> = widgets
             .iter()
-            .map(|widget| {
-                let mut data = widget.get_data();
+            .flat_map(|widget| widget.get_data().split_gradient())
+            .map(|mut data| {
                 if alternator {
                     // Apply tint for all widgets of every second block
                     data.background = add_colors(
@@ -52,7 +57,7 @@ pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<
                     .unwrap();
                     data.color = add_colors(
                         data.color.as_deref(),
-                        config.theme.alternating_tint_bg.as_deref(),
+                        config.theme.alternating_tint_fg.as_deref(),
                     )
                     .unwrap();
                 }
@@ -68,6 +73,15 @@ pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<
             rendered_widgets.last_mut().unwrap().separator_block_width = None;
         }
 
+        if let Some(spacing) = block.spacing() {
+            let last = rendered_widgets.last_mut().unwrap();
+            last.separator_block_width = Some(spacing);
+            if spacing == 0 {
+                // Hide the native separator as well to glue blocks together
+                last.separator = Some(false);
+            }
+        }
+
         // Serialize and concatenate widgets
         let block_str = rendered_widgets
             .iter()
@@ -95,9 +109,10 @@ pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<
             config.theme.separator_bg.clone()
         };
 
-        if let Some(ref separator) = config.theme.separator {
+        let separator = block.separator().or(config.theme.separator.as_deref());
+        if let Some(separator) = separator.filter(|s| !s.is_empty()) {
             let separator = I3BarBlock {
-                full_text: separator.clone(),
+                full_text: separator.to_string(),
                 background: sep_bg,
                 color: sep_fg,
                 ..Default::default()
@@ -3,7 +3,7 @@ use std::cmp;
 use std::collections::BinaryHeap;
 use std::fmt;
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::blocks::Block;
 use crate::errors::*;
@@ -42,10 +42,14 @@ impl cmp::Ord for Task {
 
 pub struct UpdateScheduler {
     schedule: BinaryHeap<Task>,
+    /// Fraction of their interval updates are randomly delayed by
+    jitter: f64,
+    /// State of the xorshift generator the delays are drawn from
+    rng_state: u64,
 }
 
 impl UpdateScheduler {
-    pub fn new(blocks: &[Box<dyn Block>]) -> UpdateScheduler {
+    pub fn new(blocks: &[Box<dyn Block>], jitter: f64) -> UpdateScheduler {
         let mut schedule = BinaryHeap::new();
 
         let now = Instant::now();
@@ -56,7 +60,58 @@ impl UpdateScheduler {
             });
         }
 
-        UpdateScheduler { schedule }
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+
+        UpdateScheduler {
+            schedule,
+            jitter,
+            // xorshift never leaves the all-zero state
+            rng_state: seed | 1,
+        }
+    }
+
+    /// A pseudo-random number in `[0, 1)`
+    fn next_random(&mut self) -> f64 {
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        (self.rng_state >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// `interval`, extended by a random part of up to `jitter` times itself so that blocks
+    /// with the same interval don't all update at the same time
+    fn jittered(&mut self, interval: Duration) -> Duration {
+        if self.jitter > 0. {
+            interval + interval.mul_f64(self.jitter * self.next_random())
+        } else {
+            interval
+        }
+    }
+
+    /// Schedules all blocks which have a pending update to be updated right away, after which
+    /// they continue with their usual intervals
+    pub fn update_all_now(&mut self) {
+        let now = Instant::now();
+        self.schedule = self
+            .schedule
+            .drain()
+            .map(|task| Task {
+                id: task.id,
+                update_time: now,
+            })
+            .collect();
+    }
+
+    /// Schedules the block `id` to be updated right away, even if it has no pending update
+    pub fn update_now(&mut self, id: usize) {
+        self.schedule = self.schedule.drain().filter(|task| task.id != id).collect();
+        self.schedule.push(Task {
+            id,
+            update_time: Instant::now(),
+        });
     }
 
     pub fn time_to_next_update(&self) -> Option<Duration> {
@@ -74,6 +129,19 @@ impl UpdateScheduler {
         }
     }
 
+    /// Moves the tasks due at `now` to `tasks`, skipping the blocks `tasks` already has. After
+    /// a gap (such as the process being stopped) this runs the overdue updates in a single pass
+    /// and once per block instead of in a burst.
+    fn take_overdue(&mut self, now: Instant, tasks: &mut Vec<Task>) {
+        while matches!(self.schedule.peek(), Some(task) if task.update_time <= now) {
+            if let Some(task) = self.schedule.pop() {
+                if !tasks.iter().any(|t| t.id == task.id) {
+                    tasks.push(task);
+                }
+            }
+        }
+    }
+
     pub fn do_scheduled_updates(&mut self, blocks: &mut Vec<Box<dyn Block>>) -> Result<()> {
         let t = self
             .schedule
@@ -102,6 +170,7 @@ impl UpdateScheduler {
         }
 
         let now = Instant::now();
+        self.take_overdue(now, &mut tasks_next);
 
         for task in tasks_next {
             if let Some(dur) = blocks
@@ -110,10 +179,13 @@ impl UpdateScheduler {
                 .update()?
             {
                 match dur {
-                    Update::Every(d) => self.schedule.push(Task {
-                        id: task.id,
-                        update_time: now + d,
-                    }),
+                    Update::Every(d) => {
+                        let d = self.jittered(d);
+                        self.schedule.push(Task {
+                            id: task.id,
+                            update_time: now + d,
+                        })
+                    }
                     Update::Once => {} // do not schedule this task again
                 }
             }
@@ -122,3 +194,85 @@ impl UpdateScheduler {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jittered() {
+        let interval = Duration::from_secs(10);
+
+        let mut scheduler = UpdateScheduler::new(&[], 0.);
+        assert_eq!(scheduler.jittered(interval), interval);
+
+        let mut scheduler = UpdateScheduler::new(&[], 0.2);
+        for _ in 0..100 {
+            let d = scheduler.jittered(interval);
+            assert!(d >= interval && d < Duration::from_secs(12));
+        }
+    }
+
+    #[test]
+    fn update_all_now() {
+        let mut scheduler = UpdateScheduler::new(&[], 0.);
+        let later = Instant::now() + Duration::from_secs(60);
+        for id in 0..3 {
+            scheduler.schedule.push(Task {
+                id,
+                update_time: later + Duration::from_secs(id as u64),
+            });
+        }
+
+        scheduler.update_all_now();
+        assert_eq!(scheduler.schedule.len(), 3);
+        assert_eq!(
+            scheduler.time_to_next_update(),
+            Some(Duration::from_secs(0))
+        );
+        assert!(scheduler.schedule.iter().all(|t| t.update_time < later));
+    }
+
+    #[test]
+    fn update_now() {
+        let mut scheduler = UpdateScheduler::new(&[], 0.);
+        let later = Instant::now() + Duration::from_secs(60);
+        for id in 0..2 {
+            scheduler.schedule.push(Task {
+                id,
+                update_time: later,
+            });
+        }
+
+        scheduler.update_now(1);
+        assert_eq!(scheduler.schedule.len(), 2);
+        assert_eq!(scheduler.schedule.peek().map(|t| t.id), Some(1));
+        assert!(scheduler.schedule.peek().unwrap().update_time < later);
+
+        scheduler.update_now(2);
+        assert_eq!(scheduler.schedule.len(), 3);
+    }
+
+    #[test]
+    fn take_overdue() {
+        let mut scheduler = UpdateScheduler::new(&[], 0.);
+        let now = Instant::now();
+        for &(id, secs_ago) in &[(0, 30), (1, 20), (0, 10), (2, 0)] {
+            scheduler.schedule.push(Task {
+                id,
+                update_time: now - Duration::from_secs(secs_ago),
+            });
+        }
+        scheduler.schedule.push(Task {
+            id: 3,
+            update_time: now + Duration::from_secs(10),
+        });
+
+        let mut tasks = vec![];
+        scheduler.take_overdue(now, &mut tasks);
+        let ids: Vec<_> = tasks.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [0, 1, 2]);
+        // Only the task which is not due yet is left
+        assert_eq!(scheduler.schedule.len(), 1);
+    }
+}
@@ -17,6 +17,9 @@ pub fn process_signals(sender: Sender<i32>) {
                 let mut signals = (sigmin..sigmax).collect::<Vec<_>>();
                 signals.push(signal_hook::consts::SIGUSR1);
                 signals.push(signal_hook::consts::SIGUSR2);
+                signals.push(signal_hook::consts::SIGHUP);
+                signals.push(signal_hook::consts::SIGTSTP);
+                signals.push(signal_hook::consts::SIGCONT);
                 let mut signals = signal_hook::iterator::Signals::new(&signals).unwrap();
                 for sig in signals.forever() {
                     sender.send(sig).unwrap();
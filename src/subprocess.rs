@@ -5,14 +5,74 @@ use std::thread;
 /// Spawns a new child process. This closes stdin and stdout, and returns to the caller after the
 /// child has been started, while a background thread waits for the child to exit.
 pub fn spawn_child_async(name: &str, args: &[&str]) -> io::Result<()> {
-    let mut child = Command::new(name)
-        .args(args)
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .spawn()?;
+    spawn_command_async(Command::new(name).args(args))
+}
+
+/// Runs the shell command `command` like `spawn_child_async`, with the environment variables
+/// `env` set in addition to the inherited ones
+pub fn spawn_shell_async(command: &str, env: &[(&str, &str)]) -> io::Result<()> {
+    spawn_command_async(
+        Command::new("sh")
+            .args(["-c", command])
+            .envs(env.iter().copied()),
+    )
+}
+
+fn spawn_command_async(command: &mut Command) -> io::Result<()> {
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::null()).spawn()?;
     thread::Builder::new()
         .name("subprocess".into())
         .spawn(move || child.wait())
         .unwrap();
     Ok(())
 }
+
+/// Runs `command` with `sh` and waits for it, returning whether it exited successfully. Its
+/// output is discarded.
+pub fn command_succeeds(command: &str) -> bool {
+    Command::new("sh")
+        .args(["-c", command])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_status() {
+        assert!(command_succeeds("true"));
+        assert!(command_succeeds("test -d /"));
+        assert!(!command_succeeds("false"));
+        assert!(!command_succeeds("exit 3"));
+    }
+
+    #[test]
+    fn shell_env() {
+        let out = crate::util::test_path("shell_env");
+        let _ = std::fs::remove_file(&out);
+        spawn_shell_async(
+            "printf %s \"$I3RS_TEST\" > \"$I3RS_TEST_OUT\"",
+            &[
+                ("I3RS_TEST", "a b"),
+                ("I3RS_TEST_OUT", out.to_str().unwrap()),
+            ],
+        )
+        .unwrap();
+        let mut contents = None;
+        for _ in 0..100 {
+            contents = std::fs::read_to_string(&out).ok().filter(|c| !c.is_empty());
+            if contents.is_some() {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(contents.as_deref(), Some("a b"));
+        std::fs::remove_file(out).unwrap();
+    }
+}
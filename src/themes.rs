@@ -1,14 +1,18 @@
 use std::default::Default;
 use std::fmt;
+use std::path::PathBuf;
 
 use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
 use serde_derive::Deserialize;
 
+use crate::errors::{self, ConfigurationError, ResultExtInternal};
 use crate::util;
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(default, deny_unknown_fields)]
 pub struct InternalTheme {
+    /// The theme (name or file) this theme inherits the unset keys from
+    pub base: Option<String>,
     pub idle_bg: Option<String>,
     pub idle_fg: Option<String>,
     pub info_bg: Option<String>,
@@ -24,11 +28,20 @@ pub struct InternalTheme {
     pub separator_fg: Option<String>,
     pub alternating_tint_bg: Option<String>,
     pub alternating_tint_fg: Option<String>,
+    pub bar_filled: Option<String>,
+    pub bar_empty: Option<String>,
+    pub graph_ramp: Option<String>,
+    pub idle_bg_end: Option<String>,
+    pub info_bg_end: Option<String>,
+    pub good_bg_end: Option<String>,
+    pub warning_bg_end: Option<String>,
+    pub critical_bg_end: Option<String>,
 }
 
 impl Default for InternalTheme {
     fn default() -> Self {
         Self {
+            base: None,
             idle_bg: None,
             idle_fg: None,
             info_bg: None,
@@ -44,6 +57,14 @@ impl Default for InternalTheme {
             separator_fg: None,
             alternating_tint_bg: None,
             alternating_tint_fg: None,
+            bar_filled: None,
+            bar_empty: None,
+            graph_ramp: None,
+            idle_bg_end: None,
+            info_bg_end: None,
+            good_bg_end: None,
+            warning_bg_end: None,
+            critical_bg_end: None,
         }
     }
 }
@@ -53,7 +74,7 @@ pub struct Theme(pub InternalTheme);
 
 impl Default for Theme {
     fn default() -> Self {
-        Self::from_file("plain").unwrap_or_else(|| Self(InternalTheme::default()))
+        Self::from_file("plain").unwrap_or_else(|_| Self(InternalTheme::default()))
     }
 }
 
@@ -71,9 +92,76 @@ impl std::ops::DerefMut for Theme {
 }
 
 impl Theme {
-    pub fn from_file(file: &str) -> Option<Theme> {
-        let file = util::find_file(file, Some("themes"), Some("toml"))?;
-        Some(Theme(util::deserialize_file(&file).ok()?))
+    /// Loads a theme by name from the theme directories (see `util::find_file`), falling back
+    /// to treating `file` as the path of a TOML file.
+    pub fn from_file(file: &str) -> errors::Result<Theme> {
+        Self::from_file_inheriting(file, &mut Vec::new())
+    }
+
+    /// `from_file`, resolving the `base` themes. `seen` holds the files already loaded for the
+    /// theme, to detect themes inheriting from themselves.
+    fn from_file_inheriting(file: &str, seen: &mut Vec<PathBuf>) -> errors::Result<Theme> {
+        let path = util::find_file(file, Some("themes"), Some("toml"))
+            .or_else(|| Some(PathBuf::from(file)).filter(|path| path.is_file()))
+            .ok_or_else(|| {
+                ConfigurationError(format!("theme '{}' not found", file), String::new())
+            })?;
+        let contents = std::fs::read_to_string(&path)
+            .configuration_error(&format!("failed to read theme file '{}'", path.display()))?;
+        let mut theme: InternalTheme = toml::from_str(&contents).map_err(|e| {
+            ConfigurationError(
+                format!("failed to parse theme file '{}': {}", path.display(), e),
+                e.to_string(),
+            )
+        })?;
+
+        if seen.contains(&path) {
+            return Err(ConfigurationError(
+                format!("theme file '{}' inherits from itself", path.display()),
+                String::new(),
+            ));
+        }
+        seen.push(path);
+
+        match theme.base.take() {
+            Some(base) => {
+                let mut base = Self::from_file_inheriting(&base, seen)?;
+                base.apply_overrides(theme);
+                Ok(base)
+            }
+            None => Ok(Theme(theme)),
+        }
+    }
+
+    /// Replaces the keys set in `overrides`, keeping the current values of all others
+    pub fn apply_overrides(&mut self, overrides: InternalTheme) {
+        self.0.idle_bg = overrides.idle_bg.or(self.0.idle_bg.take());
+        self.0.idle_fg = overrides.idle_fg.or(self.0.idle_fg.take());
+        self.0.info_bg = overrides.info_bg.or(self.0.info_bg.take());
+        self.0.info_fg = overrides.info_fg.or(self.0.info_fg.take());
+        self.0.good_bg = overrides.good_bg.or(self.0.good_bg.take());
+        self.0.good_fg = overrides.good_fg.or(self.0.good_fg.take());
+        self.0.warning_bg = overrides.warning_bg.or(self.0.warning_bg.take());
+        self.0.warning_fg = overrides.warning_fg.or(self.0.warning_fg.take());
+        self.0.critical_bg = overrides.critical_bg.or(self.0.critical_bg.take());
+        self.0.critical_fg = overrides.critical_fg.or(self.0.critical_fg.take());
+        self.0.separator = overrides.separator.or(self.0.separator.take());
+        self.0.separator_bg = overrides.separator_bg.or(self.0.separator_bg.take());
+        self.0.separator_fg = overrides.separator_fg.or(self.0.separator_fg.take());
+        self.0.alternating_tint_bg = overrides
+            .alternating_tint_bg
+            .or(self.0.alternating_tint_bg.take());
+        self.0.alternating_tint_fg = overrides
+            .alternating_tint_fg
+            .or(self.0.alternating_tint_fg.take());
+        self.0.bar_filled = overrides.bar_filled.or(self.0.bar_filled.take());
+        self.0.bar_empty = overrides.bar_empty.or(self.0.bar_empty.take());
+        self.0.graph_ramp = overrides.graph_ramp.or(self.0.graph_ramp.take());
+        self.0.idle_bg_end = overrides.idle_bg_end.or(self.0.idle_bg_end.take());
+        self.0.info_bg_end = overrides.info_bg_end.or(self.0.info_bg_end.take());
+        self.0.good_bg_end = overrides.good_bg_end.or(self.0.good_bg_end.take());
+        self.0.warning_bg_end = overrides.warning_bg_end.or(self.0.warning_bg_end.take());
+        self.0.critical_bg_end = overrides.critical_bg_end.or(self.0.critical_bg_end.take());
     }
 }
 
@@ -87,6 +175,7 @@ impl<'de> Deserialize<'de> for Theme {
         enum Field {
             Name,
             File,
+            Base,
             Overrides,
         }
 
@@ -108,8 +197,7 @@ impl<'de> Deserialize<'de> for Theme {
             where
                 E: de::Error,
             {
-                Theme::from_file(file)
-                    .ok_or_else(|| de::Error::custom(format!("Theme '{}' not found.", file)))
+                Theme::from_file(file).map_err(de::Error::custom)
             }
 
             /// Handle configs like:
@@ -118,6 +206,15 @@ impl<'de> Deserialize<'de> for Theme {
             /// [theme]
             /// name = "modern"
             /// ```
+            ///
+            /// or
+            ///
+            /// ```toml
+            /// [theme]
+            /// base = "solarized-dark"
+            /// [theme.overrides]
+            /// idle_bg = "#123456"
+            /// ```
             fn visit_map<V>(self, mut map: V) -> Result<Theme, V::Error>
             where
                 V: MapAccess<'de>,
@@ -126,16 +223,22 @@ impl<'de> Deserialize<'de> for Theme {
                 let mut overrides: Option<InternalTheme> = None;
                 while let Some(key) = map.next_key()? {
                     match key {
-                        // TODO merge name and file into one option (let's say "theme")
+                        // TODO merge name, file and base into one option (let's say "theme")
                         Field::Name => {
                             if theme.is_some() {
-                                return Err(de::Error::duplicate_field("name or file"));
+                                return Err(de::Error::duplicate_field("name, file or base"));
                             }
                             theme = Some(map.next_value()?);
                         }
                         Field::File => {
                             if theme.is_some() {
-                                return Err(de::Error::duplicate_field("name or file"));
+                                return Err(de::Error::duplicate_field("name, file or base"));
+                            }
+                            theme = Some(map.next_value()?);
+                        }
+                        Field::Base => {
+                            if theme.is_some() {
+                                return Err(de::Error::duplicate_field("name, file or base"));
                             }
                             theme = Some(map.next_value()?);
                         }
@@ -149,29 +252,10 @@ impl<'de> Deserialize<'de> for Theme {
                 }
 
                 let theme = theme.unwrap_or_else(|| "plain".to_string());
-                let mut theme = Theme::from_file(&theme)
-                    .ok_or_else(|| de::Error::custom(format!("Theme '{}' not found.", theme)))?;
+                let mut theme = Theme::from_file(&theme).map_err(de::Error::custom)?;
 
                 if let Some(overrides) = overrides {
-                    theme.0.idle_bg = overrides.idle_bg.or(theme.0.idle_bg);
-                    theme.0.idle_fg = overrides.idle_fg.or(theme.0.idle_fg);
-                    theme.0.info_bg = overrides.info_bg.or(theme.0.info_bg);
-                    theme.0.info_fg = overrides.info_fg.or(theme.0.info_fg);
-                    theme.0.good_bg = overrides.good_bg.or(theme.0.good_bg);
-                    theme.0.good_fg = overrides.good_fg.or(theme.0.good_fg);
-                    theme.0.warning_bg = overrides.warning_bg.or(theme.0.warning_bg);
-                    theme.0.warning_fg = overrides.warning_fg.or(theme.0.warning_fg);
-                    theme.0.critical_bg = overrides.critical_bg.or(theme.0.critical_bg);
-                    theme.0.critical_fg = overrides.critical_fg.or(theme.0.critical_fg);
-                    theme.0.separator = overrides.separator.or(theme.0.separator);
-                    theme.0.separator_bg = overrides.separator_bg.or(theme.0.separator_bg);
-                    theme.0.separator_fg = overrides.separator_fg.or(theme.0.separator_fg);
-                    theme.0.alternating_tint_bg = overrides
-                        .alternating_tint_bg
-                        .or(theme.0.alternating_tint_bg);
-                    theme.0.alternating_tint_fg = overrides
-                        .alternating_tint_fg
-                        .or(theme.0.alternating_tint_fg);
+                    theme.apply_overrides(overrides);
                 }
                 Ok(theme)
             }
@@ -180,3 +264,59 @@ impl<'de> Deserialize<'de> for Theme {
         deserializer.deserialize_any(ThemeVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_path;
+
+    #[test]
+    fn from_path() {
+        let valid = test_path("from_path.palette");
+        std::fs::write(&valid, "idle_bg = \"#123456\"\n").unwrap();
+        let theme = Theme::from_file(valid.to_str().unwrap());
+        assert_eq!(theme.ok().and_then(|t| t.0.idle_bg).unwrap(), "#123456");
+
+        let malformed = test_path("from_path.broken");
+        std::fs::write(&malformed, "idle_bg = ").unwrap();
+        let err = Theme::from_file(malformed.to_str().unwrap()).err().unwrap();
+        assert!(err.to_string().contains("failed to parse theme file"));
+
+        let missing = Theme::from_file("/nonexistent/i3status-rs-theme.toml");
+        assert!(missing.err().unwrap().to_string().contains("not found"));
+
+        std::fs::remove_file(valid).unwrap();
+        std::fs::remove_file(malformed).unwrap();
+    }
+
+    #[test]
+    fn inheritance() {
+        let base = test_path("inheritance-base.toml");
+        std::fs::write(&base, "idle_bg = \"#000000\"\nidle_fg = \"#FFFFFF\"\n").unwrap();
+        let child = test_path("inheritance-child.toml");
+        std::fs::write(
+            &child,
+            format!("base = \"{}\"\nidle_bg = \"#123456\"\n", base.display()),
+        )
+        .unwrap();
+        let theme = Theme::from_file(child.to_str().unwrap()).ok().unwrap();
+        assert_eq!(theme.idle_bg.as_deref(), Some("#123456"));
+        assert_eq!(theme.idle_fg.as_deref(), Some("#FFFFFF"));
+        assert_eq!(theme.base, None);
+
+        let cyclic = test_path("inheritance-cyclic.toml");
+        std::fs::write(&cyclic, format!("base = \"{}\"\n", cyclic.display())).unwrap();
+        let err = Theme::from_file(cyclic.to_str().unwrap()).err().unwrap();
+        assert!(err.to_string().contains("inherits from itself"));
+
+        std::fs::write(&child, "base = \"i3status-rs-no-such-theme\"\n").unwrap();
+        let err = Theme::from_file(child.to_str().unwrap()).err().unwrap();
+        assert!(err
+            .to_string()
+            .contains("theme 'i3status-rs-no-such-theme' not found"));
+
+        for file in &[base, child, cyclic] {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+}
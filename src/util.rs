@@ -7,11 +7,53 @@ use std::process::Command;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
 
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::themes::Theme;
 
 pub const USR_SHARE_PATH: &str = "/usr/share/i3status-rust";
 
+/// The units blocks show temperatures in: degrees Celsius, degrees Fahrenheit or Kelvin
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Units {
+    #[default]
+    Metric,
+    Imperial,
+    Kelvin,
+}
+
+impl Units {
+    /// `celsius` converted to these units
+    pub fn temperature(self, celsius: f64) -> f64 {
+        match self {
+            Units::Metric => celsius,
+            Units::Imperial => celsius * 1.8 + 32.,
+            Units::Kelvin => celsius + 273.15,
+        }
+    }
+
+    /// The symbol of the temperature unit, shown as `{unit}`
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Units::Metric => "°C",
+            Units::Imperial => "°F",
+            Units::Kelvin => "K",
+        }
+    }
+
+    /// `temperature` (already in these units) as a format value, which shows the degree sign
+    /// unless it is in Kelvin
+    pub fn temperature_value(self, temperature: i64) -> Value {
+        match self {
+            Units::Kelvin => Value::from_integer(temperature),
+            _ => Value::from_integer(temperature).degrees(),
+        }
+    }
+}
+
 pub fn pseudo_uuid() -> usize {
     static ID: AtomicUsize = AtomicUsize::new(usize::MAX);
     ID.fetch_sub(1, Ordering::SeqCst)
@@ -205,17 +247,54 @@ pub fn add_colors(
     }
 }
 
-pub fn format_vec_to_bar_graph(content: &[f64], min: Option<f64>, max: Option<f64>) -> String {
-    // (x * one eighth block) https://en.wikipedia.org/wiki/Block_Elements
-    static BARS: [char; 8] = [
-        '\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}',
-        '\u{2588}',
-    ];
+/// Linearly interpolates between the colors `a` and `b`, `t` going from 0 (`a`) to 1 (`b`)
+pub fn mix_colors(
+    a: &str,
+    b: &str,
+    t: f64,
+) -> ::std::result::Result<String, Box<dyn std::error::Error>> {
+    let (r_a, g_a, b_a, a_a) = color_from_rgba(a)?;
+    let (r_b, g_b, b_b, a_b) = color_from_rgba(b)?;
+    let mix = |x: u8, y: u8| (x as f64 + (y as f64 - x as f64) * t).round() as u8;
+
+    Ok(color_to_rgba((
+        mix(r_a, r_b),
+        mix(g_a, g_b),
+        mix(b_a, b_b),
+        mix(a_a, a_b),
+    )))
+}
+
+/// The characters graphs are drawn with, from the lowest to the highest
+/// (x * one eighth block) https://en.wikipedia.org/wiki/Block_Elements
+pub const DEFAULT_GRAPH_RAMP: &str =
+    "\u{2581}\u{2582}\u{2583}\u{2584}\u{2585}\u{2586}\u{2587}\u{2588}";
+
+/// The graph characters set by the theme's `graph_ramp`, falling back to `DEFAULT_GRAPH_RAMP`
+pub fn graph_ramp(theme: &Theme) -> Vec<char> {
+    match theme.graph_ramp {
+        Some(ref ramp) if !ramp.is_empty() => ramp.chars().collect(),
+        _ => DEFAULT_GRAPH_RAMP.chars().collect(),
+    }
+}
+
+/// Draws `content` using the characters of `ramp`. With `logarithmic`, samples are mapped
+/// with `ln(1 + x)` first, so that a single spike does not flatten all other samples.
+pub fn format_vec_to_bar_graph(
+    content: &[f64],
+    min: Option<f64>,
+    max: Option<f64>,
+    ramp: &[char],
+    logarithmic: bool,
+) -> String {
+    let scale = |x: f64| if logarithmic { x.max(0.).ln_1p() } else { x };
+    let content: Vec<f64> = content.iter().map(|x| scale(*x)).collect();
+    let (min, max) = (min.map(scale), max.map(scale));
 
     // Find min and max
     let mut min_v = std::f64::INFINITY;
     let mut max_v = -std::f64::INFINITY;
-    for v in content {
+    for v in &content {
         if *v < min_v {
             min_v = *v;
         }
@@ -228,19 +307,35 @@ pub fn format_vec_to_bar_graph(content: &[f64], min: Option<f64>, max: Option<f6
     let max = max.unwrap_or(max_v);
     let extant = max - min;
     if extant.is_normal() {
-        let length = BARS.len() as f64 - 1.0;
+        let length = ramp.len() as f64 - 1.0;
         content
             .iter()
-            .map(|x| BARS[((x.clamp(min, max) - min) / extant * length) as usize])
+            .map(|x| ramp[((x.clamp(min, max) - min) / extant * length) as usize])
             .collect()
     } else {
-        (0..content.len()).map(|_| BARS[0]).collect::<_>()
+        (0..content.len()).map(|_| ramp[0]).collect::<_>()
     }
 }
 
+/// A path in the temporary directory named after the test, and unique to the process so that
+/// parallel runs of the tests don't clash
+#[cfg(test)]
+pub fn test_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("i3status-rs-test-{}-{}", std::process::id(), name))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::util::{color_from_rgba, has_command};
+    use crate::util::{color_from_rgba, format_vec_to_bar_graph, has_command, Units};
+
+    #[test]
+    fn temperature_units() {
+        assert_eq!(Units::Metric.temperature(21.5), 21.5);
+        assert_eq!(Units::Imperial.temperature(100.), 212.);
+        assert_eq!(Units::Imperial.temperature(-40.), -40.);
+        assert!((Units::Kelvin.temperature(0.) - 273.15).abs() < 1e-9);
+        assert_eq!(Units::Kelvin.symbol(), "K");
+    }
 
     #[test]
     // we assume sh is always available
@@ -283,4 +378,24 @@ mod tests {
         let rgba = color_from_rgba(invalid);
         assert!(rgba.is_err());
     }
+
+    #[test]
+    fn test_format_vec_to_bar_graph_ramp() {
+        let ramp: Vec<char> = "_.-'".chars().collect();
+        let graph = format_vec_to_bar_graph(&[0., 1., 2., 3.], None, None, &ramp, false);
+        assert_eq!(graph, "_.-'");
+        let graph = format_vec_to_bar_graph(&[5., 5.], None, None, &ramp, false);
+        assert_eq!(graph, "__");
+    }
+
+    #[test]
+    fn test_format_vec_to_bar_graph_logarithmic() {
+        let ramp: Vec<char> = super::DEFAULT_GRAPH_RAMP.chars().collect();
+        let samples = [0., 10., 100.];
+        // Linearly, the sample that is 10 times smaller than the spike is drawn as low as 0
+        let graph = format_vec_to_bar_graph(&samples, None, None, &ramp, false);
+        assert_eq!(graph, "\u{2581}\u{2581}\u{2588}");
+        let graph = format_vec_to_bar_graph(&samples, None, None, &ramp, true);
+        assert_eq!(graph, "\u{2581}\u{2584}\u{2588}");
+    }
 }
@@ -1,3 +1,5 @@
+pub mod button;
+pub mod progressbar;
 pub mod rotatingtext;
 pub mod text;
 
@@ -40,6 +42,18 @@ impl State {
             Critical => (&theme.critical_bg, &theme.critical_fg),
         }
     }
+
+    /// The second color of the background gradient, if the theme sets one for this state
+    pub fn theme_bg_end(self, theme: &Theme) -> &Option<String> {
+        use self::State::*;
+        match self {
+            Idle => &theme.idle_bg_end,
+            Info => &theme.info_bg_end,
+            Good => &theme.good_bg_end,
+            Warning => &theme.warning_bg_end,
+            Critical => &theme.critical_bg_end,
+        }
+    }
 }
 
 impl FromStr for State {
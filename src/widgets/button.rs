@@ -0,0 +1,51 @@
+use super::text::TextWidget;
+use super::{I3BarWidget, Spacing, State};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::protocol::i3bar_block::I3BarBlock;
+use crate::protocol::i3bar_event::I3BarEvent;
+
+/// A clickable sub-element of a block.
+///
+/// Every widget of a block is sent to the bar with its own `instance`, which the bar sends back
+/// in the `I3BarEvent` of a click. Give each button of a block a distinct instance and use
+/// `is_clicked` in `Block::click` to find out which one was pressed.
+#[derive(Clone, Debug)]
+pub struct ButtonWidget {
+    instance: usize,
+    text: TextWidget,
+}
+
+impl ButtonWidget {
+    pub fn new(id: usize, instance: usize, shared_config: SharedConfig) -> Self {
+        ButtonWidget {
+            instance,
+            text: TextWidget::new(id, instance, shared_config).with_spacing(Spacing::Inline),
+        }
+    }
+
+    pub fn with_icon(mut self, name: &str) -> Result<Self> {
+        self.text = self.text.with_icon(name)?;
+        Ok(self)
+    }
+
+    pub fn with_text(mut self, content: &str) -> Self {
+        self.text = self.text.with_text(content);
+        self
+    }
+
+    pub fn set_state(&mut self, state: State) {
+        self.text.set_state(state);
+    }
+
+    /// Whether `event` is a click on this button
+    pub fn is_clicked(&self, event: &I3BarEvent) -> bool {
+        event.instance == Some(self.instance)
+    }
+}
+
+impl I3BarWidget for ButtonWidget {
+    fn get_data(&self) -> I3BarBlock {
+        self.text.get_data()
+    }
+}
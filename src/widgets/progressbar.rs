@@ -0,0 +1,137 @@
+use super::{I3BarWidget, State};
+use crate::config::SharedConfig;
+use crate::protocol::i3bar_block::I3BarBlock;
+use crate::themes::Theme;
+
+/// Width of the bar (in characters) unless set otherwise
+pub const DEFAULT_WIDTH: usize = 10;
+
+const DEFAULT_FILLED: &str = "\u{2588}";
+const DEFAULT_EMPTY: &str = "\u{2591}";
+
+/// The glyphs used for the filled and the empty part of a bar, as set by the theme
+pub fn bar_glyphs(theme: &Theme) -> (String, String) {
+    (
+        theme
+            .bar_filled
+            .clone()
+            .unwrap_or_else(|| DEFAULT_FILLED.to_string()),
+        theme
+            .bar_empty
+            .clone()
+            .unwrap_or_else(|| DEFAULT_EMPTY.to_string()),
+    )
+}
+
+/// Renders `percents` (0–100) as a bar that is `width` characters long. The number of filled
+/// characters is rounded to the nearest step, so the bar is only completely empty (or full)
+/// below (or above) half a step.
+pub fn render_bar(percents: f64, width: usize, filled: &str, empty: &str) -> String {
+    let percents = if percents.is_nan() {
+        0.
+    } else {
+        percents.clamp(0., 100.)
+    };
+    let filled_count = ((percents / 100. * width as f64).round() as usize).min(width);
+    format!(
+        "{}{}",
+        filled.repeat(filled_count),
+        empty.repeat(width - filled_count)
+    )
+}
+
+#[derive(Clone, Debug)]
+pub struct ProgressBarWidget {
+    value: f64,
+    width: usize,
+    shared_config: SharedConfig,
+    inner: I3BarBlock,
+}
+
+impl ProgressBarWidget {
+    pub fn new(id: usize, instance: usize, shared_config: SharedConfig) -> Self {
+        let (key_bg, key_fg) = State::Idle.theme_keys(&shared_config.theme); // Initial colors
+        let inner = I3BarBlock {
+            name: Some(id.to_string()),
+            instance: Some(instance.to_string()),
+            color: key_fg.clone(),
+            background: key_bg.clone(),
+            ..I3BarBlock::default()
+        };
+
+        let mut widget = ProgressBarWidget {
+            value: 0.,
+            width: DEFAULT_WIDTH,
+            shared_config,
+            inner,
+        };
+        widget.update();
+        widget
+    }
+
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        self.update();
+        self
+    }
+
+    /// Sets the value to display, in percents (0–100)
+    pub fn set_value(&mut self, value: f64) {
+        self.value = value;
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let (key_bg, key_fg) = State::Idle.theme_keys(&self.shared_config.theme);
+        let (filled, empty) = bar_glyphs(&self.shared_config.theme);
+
+        self.inner.full_text = format!(" {} ", render_bar(self.value, self.width, &filled, &empty));
+        self.inner.background = key_bg.clone();
+        self.inner.background_end = State::Idle.theme_bg_end(&self.shared_config.theme).clone();
+        self.inner.color = key_fg.clone();
+    }
+}
+
+impl I3BarWidget for ProgressBarWidget {
+    fn get_data(&self) -> I3BarBlock {
+        self.inner.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(percents: f64) -> String {
+        render_bar(percents, 4, "#", "-")
+    }
+
+    #[test]
+    fn render_bar_boundaries() {
+        assert_eq!(bar(0.), "----");
+        assert_eq!(bar(100.), "####");
+        assert_eq!(bar(-10.), "----");
+        assert_eq!(bar(150.), "####");
+        assert_eq!(bar(f64::NAN), "----");
+    }
+
+    #[test]
+    fn render_bar_steps() {
+        // With a width of 4, every character stands for 25%
+        assert_eq!(bar(12.4), "----");
+        assert_eq!(bar(12.5), "#---");
+        assert_eq!(bar(37.4), "#---");
+        assert_eq!(bar(50.), "##--");
+        assert_eq!(bar(87.4), "###-");
+        assert_eq!(bar(87.5), "####");
+    }
+
+    #[test]
+    fn render_bar_glyphs() {
+        assert_eq!(
+            render_bar(50., 2, "\u{2588}", "\u{2591}"),
+            "\u{2588}\u{2591}"
+        );
+        assert_eq!(render_bar(50., 0, "#", "-"), "");
+    }
+}
@@ -18,6 +18,7 @@ pub struct RotatingTextWidget {
     rotation_speed: Duration,
     next_rotation: Option<Instant>,
     content: String,
+    gap: String,
     icon: Option<String>,
     state: State,
     spacing: Spacing,
@@ -52,6 +53,7 @@ impl RotatingTextWidget {
             rotation_speed: speed,
             next_rotation: None,
             content: String::new(),
+            gap: String::from("|"),
             icon: None,
             state: State::Idle,
             spacing: Spacing::Normal,
@@ -69,6 +71,13 @@ impl RotatingTextWidget {
         Ok(self)
     }
 
+    /// Sets the text shown between the end and the start of the content while rotating
+    pub fn with_gap(mut self, gap: &str) -> Self {
+        self.gap = String::from(gap);
+        self.update();
+        self
+    }
+
     pub fn with_state(mut self, state: State) -> Self {
         self.state = state;
         self.update();
@@ -123,25 +132,14 @@ impl RotatingTextWidget {
 
     fn get_rotated_content(&self) -> String {
         if self.content.chars().count() > self.max_width {
-            let missing =
-                (self.rotation_pos + self.max_width).saturating_sub(self.content.chars().count());
-            if missing == 0 {
-                self.content
-                    .chars()
-                    .skip(self.rotation_pos)
-                    .take(self.max_width)
-                    .collect()
-            } else {
-                let mut avail: String = self
-                    .content
-                    .chars()
-                    .skip(self.rotation_pos)
-                    .take(self.max_width)
-                    .collect();
-                avail.push('|');
-                avail.push_str(&self.content.chars().take(missing - 1).collect::<String>());
-                avail
-            }
+            // Wrap around through the gap, so the start of the content follows its end
+            self.content
+                .chars()
+                .chain(self.gap.chars())
+                .cycle()
+                .skip(self.rotation_pos)
+                .take(self.max_width)
+                .collect()
         } else {
             self.content.clone()
         }
@@ -173,6 +171,7 @@ impl RotatingTextWidget {
             }
         };
         self.inner.background = key_bg.clone();
+        self.inner.background_end = self.state.theme_bg_end(&self.shared_config.theme).clone();
         self.inner.color = key_fg.clone();
     }
 
@@ -182,7 +181,8 @@ impl RotatingTextWidget {
             if next_rotation > now {
                 Ok((false, Some(next_rotation - now)))
             } else if self.rotating {
-                if self.rotation_pos < self.content.chars().count() {
+                // Stop once the start of the content is shown again
+                if self.rotation_pos + 1 < self.content.chars().count() + self.gap.chars().count() {
                     self.rotation_pos += 1;
                     self.next_rotation = Some(now + self.rotation_speed);
                     self.update();
@@ -209,3 +209,28 @@ impl I3BarWidget for RotatingTextWidget {
         self.inner.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_wraps_through_gap() {
+        let mut widget = RotatingTextWidget::new(
+            0,
+            0,
+            Duration::from_secs(10),
+            Duration::from_millis(500),
+            4,
+            false,
+            SharedConfig::default(),
+        )
+        .with_gap(" - ")
+        .with_text("abcdef");
+        assert_eq!(widget.get_rotated_content(), "abcd");
+        widget.rotation_pos = 4;
+        assert_eq!(widget.get_rotated_content(), "ef -");
+        widget.rotation_pos = 8;
+        assert_eq!(widget.get_rotated_content(), " abc");
+    }
+}
@@ -8,8 +8,11 @@ pub struct TextWidget {
     id: usize,
     pub instance: usize,
     content: Option<String>,
+    short_content: Option<String>,
     icon: Option<String>,
     state: State,
+    blink: bool,
+    blink_on: bool,
     spacing: Spacing,
     shared_config: SharedConfig,
     inner: I3BarBlock,
@@ -30,8 +33,11 @@ impl TextWidget {
             id,
             instance,
             content: None,
+            short_content: None,
             icon: None,
             state: State::Idle,
+            blink: false,
+            blink_on: false,
             spacing: Spacing::Normal,
             shared_config,
             inner,
@@ -56,6 +62,12 @@ impl TextWidget {
         self
     }
 
+    /// Makes the widget blink while it is in the critical state, see `blink`
+    pub fn with_blink(mut self, blink: bool) -> Self {
+        self.blink = blink;
+        self
+    }
+
     pub fn with_spacing(mut self, spacing: Spacing) -> Self {
         self.spacing = spacing;
         self.update();
@@ -68,6 +80,19 @@ impl TextWidget {
         Ok(())
     }
 
+    /// Sets the icon of the ramp `ramp` for `percents` (0–100), or the icon `fallback` if the
+    /// icon set has no such ramp
+    pub fn set_ramp_icon(&mut self, ramp: &str, percents: f64, fallback: &str) -> Result<()> {
+        match self.shared_config.get_ramp_icon(ramp, percents) {
+            Some(icon) => {
+                self.icon = Some(icon);
+                self.update();
+                Ok(())
+            }
+            None => self.set_icon(fallback),
+        }
+    }
+
     pub fn unset_icon(&mut self) {
         self.icon = None;
         self.update();
@@ -80,6 +105,15 @@ impl TextWidget {
             Spacing::Normal
         };
         self.content = Some(content);
+        self.short_content = None;
+        self.update();
+    }
+
+    /// Sets the full text and the short text the bar may switch to when space is constrained,
+    /// e.g. as returned by `FormatPair::render`
+    pub fn set_texts(&mut self, (content, short_content): (String, Option<String>)) {
+        self.set_text(content);
+        self.short_content = short_content;
         self.update();
     }
 
@@ -93,26 +127,48 @@ impl TextWidget {
         self.update();
     }
 
+    /// Advances the blink animation by swapping the colors. Returns whether the widget is
+    /// blinking, in which case the block should update again after its blink interval.
+    pub fn blink(&mut self) -> bool {
+        let blinking = self.blink && matches!(self.state, State::Critical);
+        if blinking || self.blink_on {
+            self.blink_on = blinking && !self.blink_on;
+            self.update();
+        }
+        blinking
+    }
+
     fn update(&mut self) {
         let (key_bg, key_fg) = self.state.theme_keys(&self.shared_config.theme);
 
         // When rendered inline, remove the leading space
+        let icon = self.icon.clone().unwrap_or_else(|| match self.spacing {
+            Spacing::Normal => String::from(" "),
+            _ => String::from(""),
+        });
+        let trailing = match self.spacing {
+            Spacing::Hidden => "",
+            _ => " ",
+        };
         self.inner.full_text = format!(
             "{}{}{}",
-            self.icon.clone().unwrap_or_else(|| {
-                match self.spacing {
-                    Spacing::Normal => String::from(" "),
-                    _ => String::from(""),
-                }
-            }),
+            icon,
             self.content.clone().unwrap_or_default(),
-            match self.spacing {
-                Spacing::Hidden => String::from(""),
-                _ => String::from(" "),
-            }
+            trailing
         );
-        self.inner.background = key_bg.clone();
-        self.inner.color = key_fg.clone();
+        self.inner.short_text = self
+            .short_content
+            .as_ref()
+            .map(|short| format!("{}{}{}", icon, short, trailing));
+        if self.blink_on {
+            self.inner.background = key_fg.clone();
+            self.inner.background_end = None;
+            self.inner.color = key_bg.clone();
+        } else {
+            self.inner.background = key_bg.clone();
+            self.inner.background_end = self.state.theme_bg_end(&self.shared_config.theme).clone();
+            self.inner.color = key_fg.clone();
+        }
     }
 }
 